@@ -92,7 +92,7 @@ macro_rules! pack {
 #[macro_export]
 macro_rules! var {
     ($var_id:expr => $value:expr) => {
-        $crate::jasm![$value, $crate::Asm::SetVar($var_id)]
+        $crate::block![$value, $crate::Asm::SetVar($var_id)]
     };
     ( => $var_id:expr) => {
         $crate::Asm::SetVar($var_id)
@@ -284,6 +284,41 @@ macro_rules! for_ {
     };
 }
 
+/// Runs the first block, and runs the second block with the thrown value on top of the stack if a
+/// value is thrown.
+#[macro_export]
+macro_rules! try_ {
+    ({ $($try_blk:expr)? } catch { $($catch_blk:expr)? }) => {
+        $crate::block![format!("try_block_{}", $crate::next_block()) =>
+            $crate::Asm::PushHandler($crate::AsmLocation::Label($crate::rel_label("catch"))),
+            $crate::block![
+                try_body:
+                $($try_blk,)?
+                $crate::Asm::Nop
+            ],
+            $crate::Asm::PopHandler,
+            $crate::goto!(end_try),
+            $crate::label!(catch),
+            $crate::block![
+                catch_body:
+                $($catch_blk,)?
+                $crate::Asm::Nop
+            ],
+            $crate::label!(end_try)
+        ]
+    };
+}
+
+#[macro_export]
+macro_rules! throw {
+    ($value:expr) => {
+        $crate::block![
+            $value,
+            $crate::Asm::Throw
+        ]
+    };
+}
+
 #[macro_export]
 macro_rules! expr {
 
@@ -351,6 +386,27 @@ macro_rules! expr {
             $crate::Asm::Or
         ]
     };
+    (^, $l:expr, $r:expr) => {
+        $crate::block![
+            $crate::expr!($r);
+            $crate::expr!($l);
+            $crate::Asm::Xor
+        ]
+    };
+    (<<, $l:expr, $r:expr) => {
+        $crate::block![
+            $crate::expr!($r);
+            $crate::expr!($l);
+            $crate::Asm::ShiftLeft
+        ]
+    };
+    (>>, $l:expr, $r:expr) => {
+        $crate::block![
+            $crate::expr!($r);
+            $crate::expr!($l);
+            $crate::Asm::ShiftRight
+        ]
+    };
     (==, $l:expr, $r:expr) => {
         $crate::expr!(&,
             $crate::expr!(>0,$crate::expr!(-, $l.clone(), $r.clone())),
@@ -0,0 +1,59 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::vm::{Engine, VMBuilder};
+
+/// Sums the numbers below `n` in a loop
+fn sum_below(n: u64) -> Assembly {
+    vec![
+        Asm::pub_label("main"),
+        Asm::push(0u64),
+        Asm::SetVar(0),
+        Asm::push(0u64),
+        Asm::SetVar(1),
+        Asm::label("loop"),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::push(n),
+        Asm::Gt,
+        Asm::cond_goto("body"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::Return,
+        Asm::label("body"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Add,
+        Asm::SetVar(1),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::push(1u64),
+        Asm::Add,
+        Asm::SetVar(0),
+        Asm::goto("loop"),
+    ]
+}
+
+pub fn arithmetic_loop(c: &mut Criterion) {
+    let n = 10_000u64;
+    let mut group = c.benchmark_group("arithmetic_loop");
+    for engine in [Engine::Interpreter, Engine::Threaded, Engine::Register] {
+        group.bench_with_input(BenchmarkId::new(format!("{engine:?}"), n), &n, |b, &n| {
+            let mut vm = VMBuilder::new()
+                .memory(MinimumMemory::default())
+                .alu(MinimumALU)
+                .engine(engine)
+                .build()
+                .unwrap();
+            vm.load(sum_below(n));
+            b.iter(|| vm.run("main").unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, arithmetic_loop);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_rs_vm::core_traits::{MemoryTrait, VirtualMachine};
+use jodin_rs_vm::frame_memory::FrameMemory;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::VMBuilder;
+
+/// Sums the squares of the numbers below `n`, calling a function with its own frame for every
+/// square, the way compiled functions are called
+fn sum_squares(n: u64) -> Assembly {
+    vec![
+        Asm::pub_label("square"),
+        Asm::push("square"),
+        Asm::native_method("@load_scope", 1),
+        Asm::native_method("@push_scope", None),
+        Asm::push(2u64),
+        Asm::native_method("@reserve_vars", 1),
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Multiply,
+        Asm::SetVar(1),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::native_method("@back_scope", None),
+        Asm::Return,
+        Asm::pub_label("main"),
+        Asm::push(0u64),
+        Asm::SetVar(0),
+        Asm::push(0u64),
+        Asm::SetVar(1),
+        Asm::label("loop"),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::push(n),
+        Asm::Gt,
+        Asm::cond_goto("body"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::Return,
+        Asm::label("body"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::call("square", 1),
+        Asm::Add,
+        Asm::SetVar(1),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::push(1u64),
+        Asm::Add,
+        Asm::SetVar(0),
+        Asm::goto("loop"),
+    ]
+}
+
+fn bench_memory<M: MemoryTrait + Default>(c: &mut Criterion, name: &str, n: u64) {
+    c.benchmark_group("function_frames").bench_with_input(
+        BenchmarkId::new(name, n),
+        &n,
+        |b, &n| {
+            let mut vm = VMBuilder::new()
+                .memory(M::default())
+                .alu(MinimumALU)
+                .build()
+                .unwrap();
+            vm.load(sum_squares(n));
+            b.iter(|| vm.run("main").unwrap())
+        },
+    );
+}
+
+pub fn function_frames(c: &mut Criterion) {
+    let n = 1_000u64;
+    bench_memory::<FrameMemory>(c, "FrameMemory", n);
+    bench_memory::<VMMemory>(c, "VMMemory", n);
+    bench_memory::<MinimumMemory>(c, "MinimumMemory", n);
+}
+
+criterion_group!(benches, function_frames);
+criterion_main!(benches);
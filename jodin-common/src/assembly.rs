@@ -2,9 +2,11 @@
 
 pub mod asm_block;
 pub mod asm_macros;
+pub mod debug_info;
 pub mod error;
 pub mod instructions;
 pub mod location;
+pub mod registers;
 pub mod value;
 
 pub mod prelude {
@@ -1,5 +1,6 @@
 //! Contains supporting code for inserting and creating assembly code for the compiler
 
+use crate::assembly::debug_info::{DebugInfo, DebugMarker, SourcePosition};
 use crate::assembly::instructions::{Asm, Assembly};
 use crate::assembly::location::AsmLocation;
 use crate::compilation::{Compilable, Context, PaddedWriter, Target};
@@ -50,12 +51,21 @@ impl AssemblyBlock {
 
     /// Normalizes the block into standard assembly. Relatives `@<label>` and removes `#<labels>`.
     pub fn normalize(&self) -> Assembly {
+        self.normalize_with_debug_info().0
+    }
+
+    /// Normalizes the block like [normalize](Self::normalize), also turning its
+    /// [debug markers](DebugMarker) into the debug info of the normalized assembly
+    pub fn normalize_with_debug_info(&self) -> (Assembly, DebugInfo) {
         let mut to_normalize = self.clone();
         let base_namespace = self.name.as_ref().map(Identifier::new).unwrap_or(Identifier::empty());
         to_normalize.resolve_relative_labels(&base_namespace);
         let all_labels = to_normalize.find_all_labels();
         to_normalize.resolve_nonlocal_labels(&all_labels, &base_namespace);
-        to_normalize._normalize(&base_namespace)
+        let mut output = Assembly::new();
+        let mut debug = DebugState::default();
+        to_normalize._normalize(&base_namespace, &mut output, &mut debug);
+        (output, debug.info)
     }
 
     fn reformat_nonlocal(label: String, nonlocal_hash: u64) -> String {
@@ -82,7 +92,11 @@ impl AssemblyBlock {
                         *lbl = normalized;
                     }
                 }
-                AssemblyBlockComponent::SingleInstruction(Asm::CondGoto(AsmLocation::Label(lbl))) => {
+                AssemblyBlockComponent::SingleInstruction(
+                    Asm::CondGoto(AsmLocation::Label(lbl))
+                    | Asm::PushHandler(AsmLocation::Label(lbl))
+                    | Asm::Call(AsmLocation::Label(lbl), _),
+                ) => {
                     if lbl.starts_with(RELATIVE_LABEL_MARKER) {
                         let normalized = Self::normalize_label(current_namespace, lbl);
                         *lbl = normalized;
@@ -136,7 +150,11 @@ impl AssemblyBlock {
                         *lbl = normalized;
                     }
                 }
-                AssemblyBlockComponent::SingleInstruction(Asm::CondGoto(AsmLocation::Label(lbl))) => {
+                AssemblyBlockComponent::SingleInstruction(
+                    Asm::CondGoto(AsmLocation::Label(lbl))
+                    | Asm::PushHandler(AsmLocation::Label(lbl))
+                    | Asm::Call(AsmLocation::Label(lbl), _),
+                ) => {
                     if lbl.starts_with(NONLOCAL_LABEL_MARKER) {
                         let normalized = Self::find_nonlocal_label(lbl, all_labels, current_namespace)
                             .expect(format!("Couldn't find a label in parents named {lbl}").as_str());
@@ -174,12 +192,20 @@ impl AssemblyBlock {
     }
 
 
-    fn _normalize(self, current_namespace: &Identifier) -> Assembly {
-
-        let mut output = Assembly::new();
+    fn _normalize(
+        self,
+        current_namespace: &Identifier,
+        output: &mut Assembly,
+        debug: &mut DebugState,
+    ) {
         for comp in self.assembly {
             match comp {
+                AssemblyBlockComponent::SingleInstruction(Asm::Label(lbl))
+                    if lbl.starts_with(REMOVE_LABEL_MARKER) => {}
                 AssemblyBlockComponent::SingleInstruction(s) => {
+                    if let Asm::PublicLabel(lbl) = &s {
+                        debug.function = Some(lbl.clone());
+                    }
                     output.push(s);
                 }
                 AssemblyBlockComponent::Block(b) => {
@@ -187,11 +213,11 @@ impl AssemblyBlock {
                         current_namespace,
                         b.name.as_ref().unwrap_or(&String::new()),
                     );
-                    output.extend(b._normalize(&namespace));
+                    b._normalize(&namespace, output, debug);
                 }
+                AssemblyBlockComponent::Debug(marker) => debug.mark(marker, output.len()),
             }
         }
-        output
     }
 
     fn normalize_label(current_namespace: &Identifier, lbl: &String) -> String {
@@ -319,6 +345,36 @@ pub trait InsertAsm<T>: InsertAsmHelper {
 pub enum AssemblyBlockComponent {
     SingleInstruction(Asm),
     Block(AssemblyBlock),
+    Debug(DebugMarker),
+}
+
+/// The debug info collected while normalizing a block
+#[derive(Default)]
+struct DebugState {
+    /// The positions of the nodes the instructions are in, innermost last
+    positions: Vec<SourcePosition>,
+    /// The most recent public label
+    function: Option<String>,
+    info: DebugInfo,
+}
+
+impl DebugState {
+    fn mark(&mut self, marker: DebugMarker, index: usize) {
+        match marker {
+            DebugMarker::Enter(position) => {
+                self.positions.push(position);
+                self.info.lines.push(index, Some(position));
+            }
+            DebugMarker::Exit => {
+                self.positions.pop();
+                self.info.lines.push(index, self.positions.last().copied());
+            }
+            DebugMarker::Local(var, name) => {
+                let function = self.function.clone().unwrap_or_default();
+                self.info.locals.entry(function).or_default().insert(var, name);
+            }
+        }
+    }
 }
 
 impl Debug for AssemblyBlockComponent {
@@ -335,6 +391,9 @@ impl Debug for AssemblyBlockComponent {
                     write!(f, "{:?}", block)
                 }
             },
+            AssemblyBlockComponent::Debug(marker) => {
+                write!(f, "{:?}", marker)
+            }
         }
     }
 }
@@ -407,6 +466,17 @@ where
     }
 }
 
+impl InsertAsm<DebugMarker> for AssemblyBlock {
+    fn insert_asm_at_position(&mut self, index: usize, asm: DebugMarker) -> bool {
+        if index > self.len() {
+            return false;
+        }
+        self.assembly
+            .insert(index, AssemblyBlockComponent::Debug(asm));
+        true
+    }
+}
+
 impl InsertAsm<Value> for AssemblyBlock {
     fn insert_asm_at_position(&mut self, index: usize, asm: Value) -> bool {
         self.insert_asm_at_position(index, Asm::push(asm))
@@ -537,6 +607,10 @@ impl RemoveUnused for Assembly {
                 used_labels.insert(lbl.clone());
             } else if let Asm::CondGoto(AsmLocation::Label(lbl)) = x {
                 used_labels.insert(lbl.clone());
+            } else if let Asm::PushHandler(AsmLocation::Label(lbl)) = x {
+                used_labels.insert(lbl.clone());
+            } else if let Asm::Call(AsmLocation::Label(lbl), _) = x {
+                used_labels.insert(lbl.clone());
             }
         }
 
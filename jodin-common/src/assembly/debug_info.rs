@@ -0,0 +1,168 @@
+//! Debug info that maps compiled instructions back to the source they were compiled from.
+//!
+//! The compiler marks where the code of every spanned node starts and ends in its
+//! [assembly blocks](crate::assembly::asm_block::AssemblyBlock) with [DebugMarker]s, along with the
+//! names of the local variables of every function. Normalizing a block turns these markers into a
+//! [DebugInfo], which is stored in the optional line table section of a `.jobj` file.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// A position in a source file. Lines and columns start at 1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SourcePosition {
+    /// The line of the position
+    pub line: usize,
+    /// The column of the position, in characters
+    pub column: usize,
+}
+
+impl SourcePosition {
+    /// Creates a new source position
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A position within a source file, if the file is known
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// The file the position is in
+    pub file: Option<PathBuf>,
+    /// The position within the file
+    pub position: SourcePosition,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}", file.display(), self.position),
+            None => write!(f, "{}", self.position),
+        }
+    }
+}
+
+/// Marks debug info about the instructions that follow it in an assembly block, without being an
+/// instruction itself
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugMarker {
+    /// The following instructions were compiled from the node at a position
+    Enter(SourcePosition),
+    /// Ends the most recent [Enter](DebugMarker::Enter), so the following instructions are from
+    /// the node it was in
+    Exit,
+    /// A local variable of the current function has a name in the source
+    Local(u64, String),
+}
+
+/// Maps instruction indexes to the source positions they were compiled from
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineTable {
+    /// Instruction indexes with the position of the instructions from there until the next entry,
+    /// sorted by index
+    entries: Vec<(usize, Option<SourcePosition>)>,
+}
+
+impl LineTable {
+    /// Sets the position of the instructions from `index` on. Indexes must be given in order.
+    pub fn push(&mut self, index: usize, position: Option<SourcePosition>) {
+        match self.entries.last_mut() {
+            Some((_, last)) if *last == position => {}
+            Some((last_index, last)) if *last_index == index => *last = position,
+            _ => self.entries.push((index, position)),
+        }
+    }
+
+    /// The position an instruction was compiled from, if known
+    pub fn position(&self, index: usize) -> Option<SourcePosition> {
+        let after = self.entries.partition_point(|&(start, _)| start <= index);
+        after.checked_sub(1).and_then(|entry| self.entries[entry].1)
+    }
+
+    /// The entries of the table, as instruction indexes with the position of the instructions from
+    /// there until the next entry
+    pub fn entries(&self) -> &[(usize, Option<SourcePosition>)] {
+        &self.entries
+    }
+
+    /// Whether the table has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds the entries of another table, whose instructions start at `offset`
+    pub fn append(&mut self, other: LineTable, offset: usize) {
+        for (index, position) in other.entries {
+            self.push(index + offset, position);
+        }
+    }
+}
+
+/// The debug info of compiled instructions
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebugInfo {
+    /// The source file the instructions were compiled from
+    pub source_file: Option<PathBuf>,
+    /// The source positions of the instructions
+    pub lines: LineTable,
+    /// The source names of the local variables of every function, by the public label of the
+    /// function and the number of the variable
+    pub locals: BTreeMap<String, BTreeMap<u64, String>>,
+}
+
+impl DebugInfo {
+    /// The source location an instruction was compiled from, if known
+    pub fn location(&self, index: usize) -> Option<SourceLocation> {
+        self.lines.position(index).map(|position| SourceLocation {
+            file: self.source_file.clone(),
+            position,
+        })
+    }
+
+    /// The source name of a local variable of a function
+    pub fn local_name(&self, function: &str, var: u64) -> Option<&str> {
+        self.locals
+            .get(function)
+            .and_then(|locals| locals.get(&var))
+            .map(String::as_str)
+    }
+
+    /// Adds the debug info of instructions that start at `offset`
+    pub fn append(&mut self, other: DebugInfo, offset: usize) {
+        if self.source_file.is_none() {
+            self.source_file = other.source_file;
+        }
+        self.lines.append(other.lines, offset);
+        self.locals.extend(other.locals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_of_ranges() {
+        let mut table = LineTable::default();
+        table.push(2, Some(SourcePosition::new(1, 1)));
+        table.push(5, Some(SourcePosition::new(2, 5)));
+        table.push(5, Some(SourcePosition::new(3, 5)));
+        table.push(7, None);
+        table.push(9, Some(SourcePosition::new(3, 5)));
+        table.push(10, Some(SourcePosition::new(3, 5)));
+
+        assert_eq!(table.position(0), None);
+        assert_eq!(table.position(4), Some(SourcePosition::new(1, 1)));
+        assert_eq!(table.position(5), Some(SourcePosition::new(3, 5)));
+        assert_eq!(table.position(8), None);
+        assert_eq!(table.position(100), Some(SourcePosition::new(3, 5)));
+        assert_eq!(table.entries().len(), 4);
+    }
+}
@@ -10,6 +10,8 @@ pub enum BytecodeError {
     InvalidLocationFromValue(Value),
     #[error("Variable {0} not set")]
     VariableNotSet(usize),
+    #[error("No scope to leave")]
+    NoScopeToLeave,
     #[error(transparent)]
     Other(#[from] Box<dyn Error>),
 }
@@ -2,6 +2,7 @@
 //!
 //! Does not store any information about how byte codes are actually implemented
 
+use crate::assembly::debug_info::DebugInfo;
 use crate::assembly::error::BytecodeError;
 use crate::assembly::location::AsmLocation;
 use crate::assembly::value::Value;
@@ -66,8 +67,17 @@ pub enum Asm {
 
     /// Return to the previous frame
     Return,
-    /// Calls a function. Passes a popped value as an argument.
-    Call(AsmLocation),
+
+    /// Registers an exception handler at this location. If a value is thrown before the handler is
+    /// popped, execution continues at the location with the thrown value on the stack.
+    PushHandler(AsmLocation),
+    /// Removes the most recently pushed exception handler
+    PopHandler,
+    /// Pops the top of the stack and throws it to the most recently pushed exception handler
+    Throw,
+    /// Calls a statically known function with the given number of arguments. The arguments are
+    /// already on the stack, with the first argument on top.
+    Call(AsmLocation, usize),
 
     /// Add two values
     Add,
@@ -91,6 +101,13 @@ pub enum Asm {
     Not,
     /// || two values on the stack
     Or,
+    /// ^ two values on the stack
+    Xor,
+    /// Shifts the first popped value left by the second popped value
+    ShiftLeft,
+    /// Shifts the first popped value right by the second popped value. Signed integers
+    /// are shifted arithmetically.
+    ShiftRight,
 
     /// Pop the top of the stack and makes the popped value either be
     /// a 1u8 or a 0u8.
@@ -143,6 +160,10 @@ impl Asm {
         Self::CondGoto(AsmLocation::Label(lbl.as_ref().to_string()))
     }
 
+    pub fn call(lbl: impl AsRef<str>, arity: usize) -> Self {
+        Self::Call(AsmLocation::Label(lbl.as_ref().to_string()), arity)
+    }
+
     pub fn native_method<S: AsRef<str>, I: Into<Option<usize>>>(native: S, args: I) -> Self {
         let args = args.into().unwrap_or(0);
         Self::NativeMethod(native.as_ref().to_string(), args)
@@ -181,6 +202,11 @@ impl Decode for Bytecode {
 
 pub trait GetAsm {
     fn get_asm(&self) -> Assembly;
+
+    /// The debug info of the assembly, if it has any
+    fn debug_info(&self) -> Option<DebugInfo> {
+        None
+    }
 }
 
 impl GetAsm for Assembly {
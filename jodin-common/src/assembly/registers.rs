@@ -0,0 +1,429 @@
+//! A register machine form of [Assembly].
+//!
+//! The stack form remains the format that's stored and loaded. The register form is created from
+//! it with [translate], which keeps the values a basic block pushes in registers instead of on the
+//! stack. Registers only ever hold values within a basic block: before any instruction the
+//! translation doesn't understand, before a jump and before every label, the values held in
+//! registers are spilled onto the stack in the order they would have been pushed. This means the
+//! stack is always in the same state as it would be for the stack form whenever execution leaves
+//! the register form, so the two can be freely mixed.
+
+use crate::assembly::instructions::{Asm, Assembly};
+use crate::assembly::location::AsmLocation;
+use crate::assembly::value::Value;
+
+/// The number of registers in a register file
+pub const REGISTER_COUNT: usize = 16;
+
+/// A register, which is an index into a register file
+pub type Register = u8;
+
+/// An operation on two values
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    /// Adds the values, like [Asm::Add]
+    Add,
+    /// Subtracts the values, like [Asm::Subtract]
+    Subtract,
+    /// Multiplies the values, like [Asm::Multiply]
+    Multiply,
+    /// Divides the values, like [Asm::Divide]
+    Divide,
+    /// The remainder of dividing the values, like [Asm::Remainder]
+    Remainder,
+    /// Ands the values, like [Asm::And]
+    And,
+    /// Ors the values, like [Asm::Or]
+    Or,
+    /// Exclusive ors the values, like [Asm::Xor]
+    Xor,
+    /// Shifts a value left, like [Asm::ShiftLeft]
+    ShiftLeft,
+    /// Shifts a value right, like [Asm::ShiftRight]
+    ShiftRight,
+    /// Whether a value is greater than the other, like [Asm::Gt]
+    Gt,
+}
+
+impl BinaryOp {
+    /// Gets the operation a stack instruction performs, if it's a binary operation
+    pub fn from_asm(asm: &Asm) -> Option<Self> {
+        let op = match asm {
+            Asm::Add => BinaryOp::Add,
+            Asm::Subtract => BinaryOp::Subtract,
+            Asm::Multiply => BinaryOp::Multiply,
+            Asm::Divide => BinaryOp::Divide,
+            Asm::Remainder => BinaryOp::Remainder,
+            Asm::And => BinaryOp::And,
+            Asm::Or => BinaryOp::Or,
+            Asm::Xor => BinaryOp::Xor,
+            Asm::ShiftLeft => BinaryOp::ShiftLeft,
+            Asm::ShiftRight => BinaryOp::ShiftRight,
+            Asm::Gt => BinaryOp::Gt,
+            _ => return None,
+        };
+        Some(op)
+    }
+}
+
+/// An operation on a single value
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// Inverts the bits of a value, like [Asm::Not]
+    Not,
+    /// Whether a value is greater than 0, like [Asm::GT0]
+    GT0,
+}
+
+impl UnaryOp {
+    /// Gets the operation a stack instruction performs, if it's a unary operation
+    pub fn from_asm(asm: &Asm) -> Option<Self> {
+        match asm {
+            Asm::Not => Some(UnaryOp::Not),
+            Asm::GT0 => Some(UnaryOp::GT0),
+            _ => None,
+        }
+    }
+}
+
+/// A register machine instruction. Jump targets are indexes of stack instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegisterAsm {
+    /// Loads a constant into a register
+    Const {
+        /// The register loaded into
+        dst: Register,
+        /// The constant
+        value: Value,
+    },
+    /// Loads the value of a variable into a register
+    LoadVar {
+        /// The register loaded into
+        dst: Register,
+        /// The variable
+        var: u64,
+    },
+    /// Loads a reference to a variable into a register
+    LoadRef {
+        /// The register loaded into
+        dst: Register,
+        /// The variable
+        var: u64,
+    },
+    /// Replaces the reference in a register with the value it refers to
+    Deref(Register),
+    /// Moves the value in a register into a variable
+    StoreVar {
+        /// The variable
+        var: u64,
+        /// The register moved out of
+        src: Register,
+    },
+    /// Performs a binary operation, with `left` as the value that would have been on top of the stack
+    Binary {
+        /// The operation
+        op: BinaryOp,
+        /// The register the result is put in
+        dst: Register,
+        /// The register of the left operand
+        left: Register,
+        /// The register of the right operand
+        right: Register,
+    },
+    /// Performs a unary operation on a register in place
+    Unary {
+        /// The operation
+        op: UnaryOp,
+        /// The register operated on
+        reg: Register,
+    },
+    /// Always jumps to a stack instruction
+    Jump(usize),
+    /// Jumps to a stack instruction if the value in a register is true
+    JumpIf {
+        /// The register of the condition
+        cond: Register,
+        /// The index of the stack instruction jumped to
+        target: usize,
+    },
+    /// Pushes the values of the first `n` registers onto the stack, starting from the first register
+    Spill(Register),
+    /// Runs a stack instruction
+    Stack(Asm),
+}
+
+impl RegisterAsm {
+    /// The number of registers holding values that were on the stack before this instruction
+    /// consumed them, if this instruction can fail. These are spilled onto the stack when it fails,
+    /// so the stack matches what the stack instruction would have left behind.
+    pub fn spilled_on_failure(&self) -> Option<Register> {
+        match self {
+            RegisterAsm::LoadVar { dst, .. } | RegisterAsm::LoadRef { dst, .. } => Some(*dst),
+            RegisterAsm::Deref(reg) | RegisterAsm::Unary { reg, .. } => Some(*reg),
+            RegisterAsm::Binary { dst, .. } => Some(*dst),
+            _ => None,
+        }
+    }
+}
+
+/// A register instruction along with where it came from in the stack form
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterInstruction {
+    /// The instruction
+    pub asm: RegisterAsm,
+    /// The index of the stack instruction this instruction is reported at
+    pub pc: usize,
+    /// The index of the stack instruction that comes after this instruction
+    pub next: usize,
+    /// The number of stack instructions this instruction replaces
+    pub width: usize,
+}
+
+/// The register form of an [Assembly]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RegisterAssembly {
+    /// The register instructions, in order
+    pub instructions: Vec<RegisterInstruction>,
+    /// For every stack instruction, the register instruction execution can begin at in its place.
+    /// Only stack instructions that are reached with no values held in registers have one.
+    pub entries: Vec<Option<usize>>,
+}
+
+impl RegisterAssembly {
+    /// Gets the register instruction to begin at in place of a stack instruction
+    pub fn entry(&self, pc: usize) -> Option<usize> {
+        self.entries.get(pc).copied().flatten()
+    }
+}
+
+/// Gets the index a jump from `pc` lands on, if it can be known without running the jump. Jumps
+/// before the first instruction are left as stack instructions, so they fail like they would
+/// in the interpreter.
+fn static_target(location: &AsmLocation, pc: usize) -> Option<usize> {
+    match location {
+        &AsmLocation::ByteIndex(i) => Some(i),
+        &AsmLocation::InstructionDiff(diff) => pc.checked_add_signed(diff),
+        AsmLocation::Label(_) => None,
+    }
+}
+
+/// The state of a translation
+struct Translator {
+    output: RegisterAssembly,
+    /// The number of registers holding values
+    depth: usize,
+    /// The first stack instruction not yet covered by a register instruction
+    start: usize,
+}
+
+impl Translator {
+    fn emit(&mut self, asm: RegisterAsm, pc: usize, next: usize) {
+        self.output.instructions.push(RegisterInstruction {
+            asm,
+            pc,
+            next,
+            width: next - self.start,
+        });
+        self.start = next;
+    }
+
+    /// Spills every register holding a value before the stack instruction at `pc`
+    fn spill(&mut self, pc: usize) {
+        self.spill_below(self.depth, pc);
+        self.depth = 0;
+    }
+
+    /// Spills the first `n` registers before the stack instruction at `pc`
+    fn spill_below(&mut self, n: usize, pc: usize) {
+        if n > 0 {
+            self.output.instructions.push(RegisterInstruction {
+                asm: RegisterAsm::Spill(n as Register),
+                pc,
+                next: pc,
+                width: 0,
+            });
+        }
+    }
+
+    /// Gets the register the next value pushed goes into, spilling if every register is in use
+    fn push_register(&mut self, pc: usize) -> Register {
+        if self.depth == REGISTER_COUNT {
+            self.spill(pc);
+        }
+        self.depth += 1;
+        (self.depth - 1) as Register
+    }
+
+    fn top(&self) -> Register {
+        (self.depth - 1) as Register
+    }
+}
+
+/// Translates linked stack instructions into their register form
+pub fn translate(instructions: &Assembly) -> RegisterAssembly {
+    let mut translator = Translator {
+        output: RegisterAssembly {
+            instructions: vec![],
+            entries: vec![None; instructions.len()],
+        },
+        depth: 0,
+        start: 0,
+    };
+    let mut pc = 0;
+    while pc < instructions.len() {
+        let asm = &instructions[pc];
+        if matches!(asm, Asm::Label(_) | Asm::PublicLabel(_)) {
+            // jumps land on labels with every value on the stack
+            translator.spill(pc);
+        }
+        if translator.depth == 0 && translator.start == pc {
+            translator.output.entries[pc] = Some(translator.output.instructions.len());
+        }
+        let t = &mut translator;
+        let next = pc + 1;
+        match asm {
+            Asm::Label(_) | Asm::PublicLabel(_) | Asm::Nop => {}
+            Asm::Push(value) => {
+                let dst = t.push_register(pc);
+                t.emit(
+                    RegisterAsm::Const {
+                        dst,
+                        value: value.clone(),
+                    },
+                    pc,
+                    next,
+                );
+            }
+            &Asm::GetVar(var) => {
+                let dst = t.push_register(pc);
+                if let Some(Asm::Deref) = instructions.get(next) {
+                    t.emit(RegisterAsm::LoadVar { dst, var }, pc, next + 1);
+                    pc += 2;
+                    continue;
+                }
+                t.emit(RegisterAsm::LoadRef { dst, var }, pc, next);
+            }
+            Asm::Deref if t.depth > 0 => {
+                let reg = t.top();
+                t.emit(RegisterAsm::Deref(reg), pc, next);
+            }
+            &Asm::SetVar(var) if t.depth > 0 => {
+                let src = t.top();
+                t.depth -= 1;
+                t.emit(RegisterAsm::StoreVar { var, src }, pc, next);
+            }
+            Asm::Pop if t.depth > 0 => {
+                // the value is simply forgotten
+                t.depth -= 1;
+            }
+            Asm::Goto(location) if static_target(location, pc).is_some() => {
+                t.spill(pc);
+                let target = static_target(location, pc).unwrap();
+                t.emit(RegisterAsm::Jump(target), pc, next);
+            }
+            Asm::CondGoto(location) if t.depth > 0 && static_target(location, pc).is_some() => {
+                let cond = t.top();
+                t.spill_below(cond as usize, pc);
+                t.depth = 0;
+                let target = static_target(location, pc).unwrap();
+                t.emit(RegisterAsm::JumpIf { cond, target }, pc, next);
+            }
+            asm => match (BinaryOp::from_asm(asm), UnaryOp::from_asm(asm)) {
+                (Some(op), _) if t.depth >= 2 => {
+                    let left = t.top();
+                    t.depth -= 1;
+                    let right = t.top();
+                    t.emit(
+                        RegisterAsm::Binary {
+                            op,
+                            dst: right,
+                            left,
+                            right,
+                        },
+                        pc,
+                        next,
+                    );
+                }
+                (_, Some(op)) if t.depth > 0 => {
+                    let reg = t.top();
+                    t.emit(RegisterAsm::Unary { op, reg }, pc, next);
+                }
+                _ => {
+                    t.spill(pc);
+                    t.emit(RegisterAsm::Stack(asm.clone()), pc, next);
+                }
+            },
+        }
+        pc += 1;
+    }
+    translator.spill(instructions.len());
+    translator.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_stays_in_registers() {
+        let output = translate(&vec![
+            Asm::push(1u64),
+            Asm::GetVar(0),
+            Asm::Deref,
+            Asm::Add,
+            Asm::SetVar(1),
+        ]);
+        let asm = output
+            .instructions
+            .iter()
+            .map(|i| i.asm.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            asm,
+            vec![
+                RegisterAsm::Const {
+                    dst: 0,
+                    value: Value::UInteger(1)
+                },
+                RegisterAsm::LoadVar { dst: 1, var: 0 },
+                RegisterAsm::Binary {
+                    op: BinaryOp::Add,
+                    dst: 0,
+                    left: 1,
+                    right: 0
+                },
+                RegisterAsm::StoreVar { var: 1, src: 0 },
+            ]
+        );
+        assert_eq!(output.instructions[1].width, 2);
+        assert_eq!(output.entries[0], Some(0));
+        assert_eq!(output.entries[1], None);
+    }
+
+    #[test]
+    fn spills_before_unknown_instructions() {
+        let output = translate(&vec![Asm::push(1u64), Asm::push(2u64), Asm::Return]);
+        let last = output.instructions.len() - 1;
+        assert_eq!(output.instructions[last - 1].asm, RegisterAsm::Spill(2));
+        assert_eq!(output.instructions[last - 1].pc, 2);
+        assert_eq!(
+            output.instructions[last].asm,
+            RegisterAsm::Stack(Asm::Return)
+        );
+        assert_eq!(output.entries[2], None);
+    }
+
+    #[test]
+    fn labels_are_entries() {
+        let output = translate(&vec![
+            Asm::push(1u64),
+            Asm::label("loop"),
+            Asm::goto("loop"),
+        ]);
+        assert_eq!(output.instructions[1].asm, RegisterAsm::Spill(1));
+        let entry = output.entries[1].expect("label should be an entry");
+        assert_eq!(entry, 2);
+        assert_eq!(output.instructions[entry].pc, 2);
+        assert_eq!(output.instructions[entry].width, 2);
+    }
+}
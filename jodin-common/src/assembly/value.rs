@@ -4,13 +4,15 @@ use crate::core::literal::Literal;
 
 use crate::error::{JodinError, JodinResult};
 use anyhow::anyhow;
+use serde::de::{self, EnumAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::SerializeTupleVariant;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::fmt::{write, Display, Formatter};
+use std::fmt::{write, Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum Value {
@@ -31,11 +33,18 @@ pub enum Value {
     Native,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct JRef {
     inner: Rc<RefCell<Value>>,
 }
 
+/// Only shows where the value is, as values in the heap can refer back to themselves
+impl Debug for JRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("JRef").field(&self.inner.as_ptr()).finish()
+    }
+}
+
 impl JRef {
     pub fn new(v: impl Into<Value>) -> Self {
         let value = v.into();
@@ -43,6 +52,16 @@ impl JRef {
             inner: Rc::new(RefCell::new(value)),
         }
     }
+
+    /// Creates a weak pointer to the referenced value, which doesn't keep it alive
+    pub fn downgrade(&self) -> Weak<RefCell<Value>> {
+        Rc::downgrade(&self.inner)
+    }
+
+    /// The number of strong pointers to the referenced value, including this one
+    pub fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.inner)
+    }
 }
 
 impl From<Rc<RefCell<Value>>> for JRef {
@@ -51,6 +70,12 @@ impl From<Rc<RefCell<Value>>> for JRef {
     }
 }
 
+impl From<JRef> for Rc<RefCell<Value>> {
+    fn from(r: JRef) -> Self {
+        r.inner
+    }
+}
+
 impl Deref for JRef {
     type Target = RefCell<Value>;
 
@@ -59,13 +84,79 @@ impl Deref for JRef {
     }
 }
 
+thread_local! {
+    static ALIASES: RefCell<Option<Aliases>> = RefCell::new(None);
+}
+
+/// The references serialized or deserialized so far within [with_aliases]
+#[derive(Default)]
+struct Aliases {
+    ids: HashMap<*const RefCell<Value>, u64>,
+    /// Every reference seen, by id. Also keeps serialized references alive, so their addresses
+    /// aren't reused.
+    references: Vec<JRef>,
+}
+
+/// Puts back the aliases of an enclosing [with_aliases] call, even if the function panics
+struct AliasesGuard(Option<Aliases>);
+
+impl Drop for AliasesGuard {
+    fn drop(&mut self) {
+        let outer = self.0.take();
+        ALIASES.with(|aliases| aliases.replace(outer));
+    }
+}
+
+/// Runs a function that serializes or deserializes values, keeping references shared between
+/// them shared.
+///
+/// Outside of this, a reference is serialized as a copy of the value it refers to, which is how
+/// references in bytecode are encoded. Within it, a reference is serialized along with its value
+/// the first time it's seen, and as an alias of that afterwards, so aliases and cycles are
+/// deserialized as they were. Values serialized within this must be deserialized within it too.
+///
+/// Values must only be serialized once within a call, so serializers that take more than one pass
+/// can't be used. `bincode::serialize` measures values before writing them, but
+/// `bincode::serialize_into` doesn't.
+pub fn with_aliases<R>(f: impl FnOnce() -> R) -> R {
+    let outer = ALIASES.with(|aliases| aliases.replace(Some(Aliases::default())));
+    let _guard = AliasesGuard(outer);
+    f()
+}
+
 impl Serialize for JRef {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let value = self.inner.borrow().clone();
-        value.serialize(serializer)
+        // Ok for the first time a reference is seen, Err for an alias
+        let seen = ALIASES.with(|aliases| {
+            aliases.borrow_mut().as_mut().map(|aliases| {
+                let ptr = Rc::as_ptr(&self.inner);
+                match aliases.ids.get(&ptr) {
+                    Some(&id) => Err(id),
+                    None => {
+                        let id = aliases.references.len() as u64;
+                        aliases.ids.insert(ptr, id);
+                        aliases.references.push(self.clone());
+                        Ok(id)
+                    }
+                }
+            })
+        });
+        match seen {
+            None => {
+                let value = self.inner.borrow().clone();
+                value.serialize(serializer)
+            }
+            Some(Ok(id)) => {
+                let mut shared = serializer.serialize_tuple_variant("JRef", 0, "Shared", 2)?;
+                shared.serialize_field(&id)?;
+                shared.serialize_field(&*self.inner.borrow())?;
+                shared.end()
+            }
+            Some(Err(id)) => serializer.serialize_newtype_variant("JRef", 1, "Alias", &id),
+        }
     }
 }
 
@@ -74,11 +165,78 @@ impl<'de> Deserialize<'de> for JRef {
     where
         D: Deserializer<'de>,
     {
+        if ALIASES.with(|aliases| aliases.borrow().is_some()) {
+            return deserializer.deserialize_enum("JRef", &["Shared", "Alias"], AliasedVisitor);
+        }
         let value = Value::deserialize(deserializer)?;
         Ok(JRef::new(value))
     }
 }
 
+#[derive(Deserialize)]
+#[serde(variant_identifier)]
+enum AliasedKind {
+    Shared,
+    Alias,
+}
+
+/// Deserializes a reference serialized within [with_aliases]
+struct AliasedVisitor;
+
+impl<'de> Visitor<'de> for AliasedVisitor {
+    type Value = JRef;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        write!(formatter, "a shared reference or an alias")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        match data.variant()? {
+            (AliasedKind::Shared, variant) => variant.tuple_variant(2, self),
+            (AliasedKind::Alias, variant) => {
+                let id: u64 = variant.newtype_variant()?;
+                ALIASES
+                    .with(|aliases| {
+                        aliases
+                            .borrow()
+                            .as_ref()
+                            .and_then(|aliases| aliases.references.get(id as usize).cloned())
+                    })
+                    .ok_or_else(|| de::Error::custom(format!("no reference {id} to alias")))
+            }
+        }
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let id: u64 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        // the reference is registered before its value is read, so the value can refer back to it
+        let reference = JRef::new(Value::Empty);
+        let registered = ALIASES.with(|aliases| match aliases.borrow_mut().as_mut() {
+            Some(aliases) if aliases.references.len() as u64 == id => {
+                aliases.references.push(reference.clone());
+                true
+            }
+            _ => false,
+        });
+        if !registered {
+            return Err(de::Error::custom(format!("reference {id} is out of order")));
+        }
+        let value: Value = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        *reference.borrow_mut() = value;
+        Ok(reference)
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -409,3 +567,53 @@ impl From<RefCell<Value>> for Value {
         Value::Reference(JRef { inner: Rc::new(r) })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_stay_shared() {
+        let shared = JRef::new(1u64);
+        let cycle = JRef::new(Value::Empty);
+        *cycle.borrow_mut() = Value::from(vec![Value::Reference(cycle.clone())]);
+        let values = vec![
+            Value::Reference(shared.clone()),
+            Value::Reference(shared),
+            Value::Reference(cycle),
+        ];
+
+        let mut bytes = vec![];
+        with_aliases(|| bincode::serialize_into(&mut bytes, &values)).unwrap();
+        let values: Vec<Value> = with_aliases(|| bincode::deserialize(&bytes)).unwrap();
+        match &values[..] {
+            [Value::Reference(first), Value::Reference(second), Value::Reference(cycle)] => {
+                *first.borrow_mut() = Value::from(2u64);
+                assert_eq!(*second.borrow(), Value::from(2u64));
+                match &*cycle.borrow() {
+                    Value::Array(inner) => match &inner[..] {
+                        [Value::Reference(inner)] => assert_eq!(inner.as_ptr(), cycle.as_ptr()),
+                        v => panic!("expected a reference, found {v:?}"),
+                    },
+                    v => panic!("expected an array, found {v:?}"),
+                }
+            }
+            v => panic!("expected three references, found {v:?}"),
+        }
+    }
+
+    #[test]
+    fn references_are_copied_without_aliases() {
+        let shared = JRef::new(1u64);
+        let values = vec![Value::Reference(shared.clone()), Value::Reference(shared)];
+        let bytes = bincode::serialize(&values).unwrap();
+        let values: Vec<Value> = bincode::deserialize(&bytes).unwrap();
+        match &values[..] {
+            [Value::Reference(first), Value::Reference(second)] => {
+                *first.borrow_mut() = Value::from(2u64);
+                assert_eq!(*second.borrow(), Value::from(1u64));
+            }
+            v => panic!("expected two references, found {v:?}"),
+        }
+    }
+}
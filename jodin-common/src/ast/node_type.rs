@@ -277,6 +277,25 @@ pub enum JodinNodeType {
         /// The condition to check
         cond: JodinNode,
     },
+    /// Catches values thrown while executing a statement
+    TryStatement {
+        /// The statement that may throw
+        statement: JodinNode,
+        /// The identifier the thrown value is bound to
+        catch_id: JodinNode,
+        /// The statement executed when a value is thrown
+        catch_statement: JodinNode,
+    },
+    /// Throws a value to the closest enclosing try statement
+    ThrowStatement {
+        /// The value being thrown
+        expression: JodinNode,
+    },
+    /// Hands a value to whatever resumed the generator the statement is in
+    YieldStatement {
+        /// The value being yielded
+        expression: JodinNode,
+    },
     /// Assign an expression to a value
     AssignmentExpression {
         /// `None` means that it's a default assignment
@@ -496,6 +515,17 @@ impl JodinNodeType {
             JodinNodeType::DoStatement { statement, cond } => {
                 vec![cond, statement]
             }
+            JodinNodeType::TryStatement {
+                statement,
+                catch_id,
+                catch_statement,
+            } => {
+                vec![statement, catch_id, catch_statement]
+            }
+            JodinNodeType::ThrowStatement { expression }
+            | JodinNodeType::YieldStatement { expression } => {
+                vec![expression]
+            }
             JodinNodeType::ExternDeclaration { declaration } => {
                 vec![declaration]
             }
@@ -715,6 +745,17 @@ impl JodinNodeType {
             JodinNodeType::DoStatement { statement, cond } => {
                 vec![cond, statement]
             }
+            JodinNodeType::TryStatement {
+                statement,
+                catch_id,
+                catch_statement,
+            } => {
+                vec![statement, catch_id, catch_statement]
+            }
+            JodinNodeType::ThrowStatement { expression }
+            | JodinNodeType::YieldStatement { expression } => {
+                vec![expression]
+            }
             JodinNodeType::ExternDeclaration { declaration } => {
                 vec![declaration]
             }
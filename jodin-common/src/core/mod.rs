@@ -7,6 +7,7 @@ pub mod import;
 pub mod literal;
 pub mod operator;
 pub mod privacy;
+pub mod span;
 pub mod tags;
 pub mod function_names;
 
@@ -0,0 +1,118 @@
+//! Spans of source code that nodes were parsed from.
+//!
+//! The parser tags statements, expressions and function definitions with the span of the tokens
+//! they were parsed from, which the compiler carries into the [debug info] of the code it
+//! generates.
+//!
+//! [debug info]: crate::assembly::debug_info
+
+use crate::assembly::debug_info::SourcePosition;
+use crate::core::tags::Tag;
+use std::any::Any;
+
+/// Where the lines of a source start, to find the lines and columns of offsets in it
+#[derive(Debug, Clone)]
+pub struct LineIndex<'s> {
+    source: &'s str,
+    /// The offset of the start of every line
+    line_starts: Vec<usize>,
+}
+
+impl<'s> LineIndex<'s> {
+    /// Finds the lines of a source
+    pub fn new(source: &'s str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// The position of a byte offset in the source
+    pub fn position(&self, offset: usize) -> SourcePosition {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = self.line_starts[line - 1];
+        let column = self.source[start..offset.min(self.source.len())]
+            .chars()
+            .count();
+        SourcePosition::new(line, column + 1)
+    }
+}
+
+/// The bytes of a source that a node was parsed from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// The offset of the first byte
+    pub start: usize,
+    /// The offset after the last byte
+    pub end: usize,
+    /// The position of the first byte
+    pub position: SourcePosition,
+}
+
+impl Span {
+    /// Creates the span between two offsets of an indexed source
+    pub fn new(start: usize, end: usize, lines: &LineIndex) -> Self {
+        Self {
+            start,
+            end,
+            position: lines.position(start),
+        }
+    }
+}
+
+/// The span of source a node was parsed from
+#[derive(Debug)]
+pub struct SpanTag(Span);
+
+impl SpanTag {
+    /// Creates a new span tag
+    pub fn new(span: Span) -> Self {
+        Self(span)
+    }
+
+    /// The span of the node
+    pub fn span(&self) -> Span {
+        self.0
+    }
+}
+
+impl Tag for SpanTag {
+    fn tag_type(&self) -> String {
+        "Span".to_string()
+    }
+
+    fn tag_info(&self) -> String {
+        format!(
+            "[Span {}..{} at {}]",
+            self.0.start, self.0.end, self.0.position
+        )
+    }
+
+    fn max_of_this_tag(&self) -> u32 {
+        1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let lines = LineIndex::new("fn main() {\n    return;\n}");
+        assert_eq!(lines.position(0), SourcePosition::new(1, 1));
+        assert_eq!(lines.position(16), SourcePosition::new(2, 5));
+        assert_eq!(lines.position(24), SourcePosition::new(3, 1));
+    }
+}
@@ -148,6 +148,9 @@ pub enum JodinErrorType {
     /// A UTF8 error
     #[error(transparent)]
     UTF8Error(#[from] FromUtf8Error),
+    /// A section of a compiled object couldn't be encoded or decoded
+    #[error(transparent)]
+    SerializationError(#[from] bincode::Error),
 }
 
 /// Contains both the error type and an approximate backtrace for where the error occurred.
@@ -213,6 +213,14 @@ pub enum Tok<'input> {
     Foreach,
     #[token("extern")]
     Extern,
+    #[token("try")]
+    Try,
+    #[token("catch")]
+    Catch,
+    #[token("throw")]
+    Throw,
+    #[token("yield")]
+    Yield,
     #[regex(r"[a-zA-Z_]\w*")]
     #[regex(r"@[a-zA-Z_]\w*", |lex| &lex.source()[1..])]
     Identifier(&'input str),
@@ -350,9 +358,10 @@ impl<T, E> UnwrapVector<T, E> for Vec<Result<T, E>> {
 macro_rules! parse {
     ($parser:ty, $ex:expr) => {{
         let string: &str = $ex;
+        let lines = $crate::core::span::LineIndex::new(string);
         let lexer = $crate::parsing::JodinLexer::new(string);
         let parser = <$parser>::new();
-        parser.parse(string, lexer)
+        parser.parse(string, &lines, lexer)
     }};
 }
 
@@ -382,7 +391,10 @@ pub fn parse_program<S: AsRef<str>>(expr: S) -> ParseResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assembly::debug_info::SourcePosition;
     use crate::core::literal::Literal;
+    use crate::core::span::{LineIndex, SpanTag};
+    use crate::core::tags::TagTools;
     use crate::identifier::Identifier;
     use crate::types::primitives::Primitive;
     use crate::types::Type;
@@ -402,10 +414,11 @@ mod tests {
     #[test]
     fn parse_identifiers() {
         let string = "std::mod::hello";
+        let lines = LineIndex::new(string);
         let lexer = JodinLexer::new(string);
         let identifier_parser = jodin_grammar::IdentifierParser::new();
         assert_eq!(
-            identifier_parser.parse(string, lexer).unwrap(),
+            identifier_parser.parse(string, &lines, lexer).unwrap(),
             Identifier::from_iter(["std", "mod", "hello"])
         );
         assert!(parse!(jodin_grammar::IdentifierParser, "int").is_err());
@@ -502,6 +515,44 @@ mod tests {
             "for(let i: int = 0; i < 2; ++i) { }"
         )
         .unwrap();
+        parse!(jodin_grammar::StatementParser, "yield a + 1;").unwrap();
+        let foreach = parse!(
+            jodin_grammar::StatementParser,
+            "foreach (i: int in range(0, 3)) { print(i); }"
+        )
+        .unwrap()
+        .unwrap();
+        match foreach.inner() {
+            JodinNodeType::Block { expressions } => {
+                assert!(matches!(
+                    expressions[1].inner(),
+                    JodinNodeType::WhileStatement { .. }
+                ))
+            }
+            other => panic!("foreach should be desugared into a block, found {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_spans() {
+        let statement = parse!(
+            jodin_grammar::StatementParser,
+            "if (true) {\n    return 3;\n}"
+        )
+        .unwrap()
+        .unwrap();
+        let position = |node: &JodinNode| node.get_tag::<SpanTag>().unwrap().span().position;
+        assert_eq!(position(&statement), SourcePosition::new(1, 1));
+        let block = match statement.inner() {
+            JodinNodeType::IfStatement { statement, .. } => statement,
+            other => panic!("expected an if statement, found {other:?}"),
+        };
+        match block.inner() {
+            JodinNodeType::Block { expressions } => {
+                assert_eq!(position(&expressions[0]), SourcePosition::new(2, 5))
+            }
+            other => panic!("expected a block, found {other:?}"),
+        }
     }
 
     #[test]
@@ -4,15 +4,16 @@ use crate::identifier::Identifier;
 use crate::types::intermediate_type::IntermediateType;
 use crate::types::traits::JTrait;
 use crate::types::Field;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::Cell;
 
-static BASE_TYPE_GENERATED: AtomicBool = AtomicBool::new(false);
+thread_local! {
+    static BASE_TYPE_GENERATED: Cell<bool> = Cell::new(false);
+}
 
-/// Generate the base type. Ensures that only one is ever created to prevent potential future errors
+/// Generate the base type. Ensures that only one is ever created on each thread to prevent
+/// potential future errors
 pub fn base_type() -> JodinResult<JTrait> {
-    if Ok(false)
-        == BASE_TYPE_GENERATED.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
-    {
+    if !BASE_TYPE_GENERATED.with(|generated| generated.replace(true)) {
         _base_type()
     } else {
         Err(JodinError::new(JodinErrorType::BaseTypeAlreadyGenerated))
@@ -2,7 +2,8 @@
 //! declarations
 
 use crate::asm_version::Version;
-use crate::assembly::instructions::{Assembly, Bytecode, Decode, Encode, GetAsm};
+use crate::assembly::debug_info::DebugInfo;
+use crate::assembly::instructions::{Assembly, Encode, GetAsm};
 use crate::compilation::{Compilable, Context, PaddedWriter, Target};
 use crate::core::privacy::Visibility;
 use crate::error::{JodinError, JodinErrorType, JodinResult};
@@ -144,6 +145,8 @@ pub struct CompilationObject {
     pub units: Vec<TranslationUnit>,
     /// The assembly in the compilation object
     pub jasm: Assembly,
+    /// The debug info of the assembly, stored in an optional section after it
+    pub debug_info: Option<DebugInfo>,
 }
 
 impl CompilationObject {
@@ -159,21 +162,19 @@ impl CompilationObject {
             module,
             units,
             jasm,
+            debug_info: None,
         }
     }
 
-    pub fn merge(self, other: Self) -> Result<Self, JodinError> {
-        if &self.file_location != &other.file_location {
-            return Err(anyhow!("Compilation objects must have same location (left= {:?}, right= {:?})", self.file_location, other.file_location).into());
-        }
-
-        let mut units = self.units;
-        units.extend(other.units);
-
-        let mut jasm = self.jasm;
-        jasm.extend(other.jasm);
+    /// Adds debug info for the assembly of the object
+    pub fn with_debug_info(mut self, debug_info: DebugInfo) -> Self {
+        self.debug_info = Some(debug_info);
+        self
+    }
 
-        Ok(Self::new(self.file_location, self.module, units, jasm))
+    pub fn merge(mut self, other: Self) -> Result<Self, JodinError> {
+        self.merge_from(other)?;
+        Ok(self)
     }
 
     pub fn merge_from(&mut self, other: Self) -> Result<(), JodinError> {
@@ -181,6 +182,11 @@ impl CompilationObject {
             return Err(anyhow!("Compilation objects must have same location (left= {:?}, right= {:?})", self.file_location, other.file_location).into());
         }
         self.units.extend(other.units);
+        if let Some(debug_info) = other.debug_info {
+            self.debug_info
+                .get_or_insert_with(DebugInfo::default)
+                .append(debug_info, self.jasm.len());
+        }
         self.jasm.extend(other.jasm);
         Ok(())
     }
@@ -239,6 +245,9 @@ impl<T: Target> Compilable<T> for CompilationObject {
         write!(w, "}}")?;
         let encoded = self.jasm.encode();
         w.write_all(&*encoded)?;
+        if let Some(debug_info) = &self.debug_info {
+            bincode::serialize_into(&mut *w, debug_info)?;
+        }
         w.flush()?;
         Ok(())
     }
@@ -303,10 +312,13 @@ impl TryFrom<&[u8]> for CompilationObject {
         let file_location: PathBuf = PathBuf::from(split.next().unwrap().replace('"', ""));
         let module: Identifier = Identifier::from(split.next().unwrap());
 
-        let bytecode_raw = &value[translation_units_end + 1..];
-        let bytecode: Bytecode = Bytecode::from(bytecode_raw);
-        let assembly: Assembly = bytecode.decode();
-        let output = CompilationObject::new(file_location, module, translation_units, assembly);
+        let mut bytecode_raw = &value[translation_units_end + 1..];
+        let assembly: Assembly = bincode::deserialize_from(&mut bytecode_raw)?;
+        let mut output = CompilationObject::new(file_location, module, translation_units, assembly);
+        // objects compiled without debug info end with the assembly
+        if !bytecode_raw.is_empty() {
+            output.debug_info = Some(bincode::deserialize_from(bytecode_raw)?);
+        }
         info!("Generated {}", output);
         Ok(output)
     }
@@ -341,6 +353,10 @@ impl GetAsm for CompilationObject {
     fn get_asm(&self) -> Assembly {
         self.jasm.clone()
     }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        self.debug_info.clone()
+    }
 }
 
 pub trait Incremental {
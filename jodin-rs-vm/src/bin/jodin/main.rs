@@ -1,31 +1,67 @@
 use jasm_macros::{call, jasm, label, native, push, return_};
 use jodin_common::{block, init_logging};
 use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::debugger::Debugger;
 use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::profiler::{ProfileWeight, Profiler};
 use jodin_rs_vm::scoped_memory::VMMemory;
 use jodin_rs_vm::vm::VMBuilder;
 use jodin_vm_kernel::KernelPlugin;
 use log::LevelFilter;
 use std::process::exit;
 
+/// Where the folded stacks of `--profile` are written
+const FOLDED_STACKS: &str = "jodin.folded";
+
 fn main() {
-    init_logging(LevelFilter::Info);
-    let mut vm_builder = VMBuilder::new()
-        .memory(VMMemory::default())
-        .alu(MinimumALU)
-        .build()
-        .unwrap();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let debug = args.iter().any(|arg| arg == "--debug");
+    let profile = args.iter().any(|arg| arg == "--profile");
+    // the debugger replaces the per-instruction logs, and they would dominate a profile
+    init_logging(if debug || profile {
+        LevelFilter::Warn
+    } else {
+        LevelFilter::Info
+    });
+    let mut profiler = Profiler::new();
+    let exit_code = {
+        let mut builder = VMBuilder::new().memory(VMMemory::default()).alu(MinimumALU);
+        if debug {
+            builder = builder.debugger(Debugger::stdio());
+        }
+        if profile {
+            builder = builder.observer(&mut profiler);
+        }
+        let mut vm_builder = builder.build().unwrap();
 
-    const KERNEL: &str = "target/debug/jodin_vm_kernel.dll";
+        const KERNEL: &str = "target/debug/jodin_vm_kernel.dll";
 
-    vm_builder.load_plugin::<KernelPlugin>();
+        vm_builder.load_plugin::<KernelPlugin>();
 
-    vm_builder.load(jasm![
-        label!(pub start);
-        call!(~ __start);
-        return_!();
-    ]);
+        vm_builder.load(jasm![
+            label!(pub start);
+            call!(~ __start);
+            return_!();
+        ]);
 
-    let exit_code = vm_builder.run("start").unwrap();
+        match vm_builder.run("start") {
+            Ok(exit_code) => exit_code,
+            Err(error) => {
+                eprintln!("error: {error}");
+                if let Some(stack_trace) = error.stack_trace() {
+                    eprint!("{stack_trace}");
+                }
+                1
+            }
+        }
+    };
+    if profile {
+        eprint!("{}", profiler.report());
+        std::fs::write(
+            FOLDED_STACKS,
+            profiler.folded_stacks(ProfileWeight::Instructions),
+        )
+        .expect("could not write folded stacks");
+    }
     exit(exit_code as i32);
 }
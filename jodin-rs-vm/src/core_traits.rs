@@ -1,12 +1,13 @@
 //! The core traits are the traits the define the different core functionalities of the virtual machine
 
-use crate::error::VMError;
+use crate::error::{ArithmeticError, VMError};
 use crate::fault::Fault;
 
 use jodin_common::assembly::error::BytecodeError;
 use jodin_common::assembly::instructions::{Asm, Assembly, GetAsm};
 use jodin_common::assembly::value::Value;
 
+use std::any::Any;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -26,19 +27,34 @@ pub trait VirtualMachine {
     fn enclosed(&mut self, asm: &Assembly) -> Value;
 
     /// Loads some asm into a the virtual machine for future use. Automatically runs code within "static" blocks
+    ///
+    /// # Panic
+    /// Panics if [try_load](VirtualMachine::try_load) would error
     fn load<A: GetAsm>(&mut self, asm: A);
 
+    /// Loads some asm into the virtual machine like [load](VirtualMachine::load), but errors
+    /// instead of panicking if a label was already loaded or code within "static" blocks fails.
+    /// Nothing is loaded if a label was already loaded.
+    fn try_load<A: GetAsm>(&mut self, asm: A) -> Result<(), VMError>;
+
     /// Loads some asm into the virtual machine, then RUNS said ASM
+    ///
+    /// # Panic
+    /// Panics if [try_load_static](VirtualMachine::try_load_static) would error
     fn load_static<A: GetAsm>(&mut self, asm: A);
 
+    /// Loads some asm into the virtual machine, then runs it, erroring if it couldn't be loaded,
+    /// fails or exits with a code other than 0
+    fn try_load_static<A: GetAsm>(&mut self, asm: A) -> Result<(), VMError>;
+
     /// Runs the VM starting at a label
     fn run(&mut self, start_label: &str) -> Result<u32, VMError>;
 
     /// Runs the VM starting at a label
     fn run_from_index(&mut self, index: usize) -> Result<u32, VMError>;
 
-    /// Forces the VM to encounter a fault
-    fn fault(&mut self, fault: Fault);
+    /// Forces the VM to encounter a fault. Fails if no handler is registered for the fault.
+    fn fault(&mut self, fault: Fault) -> Result<(), VMError>;
 
     /// Checks whether the virtual machine is in kernel mode.
     ///
@@ -46,6 +62,18 @@ pub trait VirtualMachine {
     fn is_kernel_mode(&self) -> bool;
 }
 
+/// The scopes taken out of a [MemoryTrait], which only that memory knows how to put back
+pub type SavedScopes = Box<dyn Any>;
+
+/// How deep into scopes a [MemoryTrait] is, used to unwind the memory back to an earlier point.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopeDepth {
+    /// The number of loaded scopes
+    pub loads: usize,
+    /// The number of scopes pushed since the most recent load
+    pub scopes: usize,
+}
+
 /// Memory defines a way of storing and getting variables.
 pub trait MemoryTrait: Debug {
     /// Sets the memory to the global scope. Works similarly to a load
@@ -58,14 +86,38 @@ pub trait MemoryTrait: Debug {
     fn push_scope(&mut self);
     /// Pops the top-most scope. If scope is not saved anywhere, all information is lost.
     ///
-    /// # Panic
-    /// Should panic if the current scope is the global scope
-    fn pop_scope(&mut self);
+    /// # Error
+    /// Should error if the current scope is the global scope
+    fn pop_scope(&mut self) -> Result<(), BytecodeError>;
     /// After a load, this returns the state of the memory to before the most recent load.
-    fn back_scope(&mut self);
+    ///
+    /// # Error
+    /// Should error if nothing but the global scope was loaded
+    fn back_scope(&mut self) -> Result<(), BytecodeError>;
+    /// Gets how deep into loaded and pushed scopes the memory currently is.
+    fn scope_depth(&self) -> ScopeDepth;
+    /// Backs out of loaded scopes and pops pushed scopes until the memory is at the given depth.
+    fn unwind_scopes(&mut self, depth: ScopeDepth);
+    /// Takes every loaded and pushed scope out of the memory, leaving only the global scope
+    /// loaded. Memories without scopes of their own have nothing to take.
+    fn take_scopes(&mut self) -> SavedScopes {
+        Box::new(())
+    }
+    /// Replaces the loaded and pushed scopes of the memory with ones taken out of it by
+    /// [take_scopes](MemoryTrait::take_scopes)
+    fn replace_scopes(&mut self, _scopes: SavedScopes) {}
+
+    /// Hints that the current scope uses variables numbered below `count`, so space for them can
+    /// be allocated up front
+    fn reserve_vars(&mut self, _count: usize) {}
 
     fn set_var(&mut self, var: usize, value: Value);
     fn get_var(&self, var: usize) -> Result<Rc<RefCell<Value>>, BytecodeError>;
+    /// Gets a copy of the value of a variable, without sharing the variable itself like
+    /// [get_var](MemoryTrait::get_var) does
+    fn var_value(&self, var: usize) -> Result<Value, BytecodeError> {
+        self.get_var(var).map(|cell| cell.borrow().clone())
+    }
     fn clear_var(&mut self, var: usize) -> Result<(), BytecodeError>;
     fn next_var_number(&self) -> usize;
     fn var_dict(&self) -> HashMap<usize, Value>;
@@ -79,21 +131,28 @@ pub trait MemoryTrait: Debug {
 
 /// This defines the way that arithmetics should be performed.
 pub trait ArithmeticsTrait {
-    fn add(&self, a: Value, b: Value) -> Value;
-    fn sub(&self, a: Value, b: Value) -> Value;
-    fn mult(&self, a: Value, b: Value) -> Value;
-    fn div(&self, a: Value, b: Value) -> Value;
-    fn rem(&self, a: Value, b: Value) -> Value;
-
-    fn and(&self, a: Value, b: Value) -> Value;
-    fn or(&self, a: Value, b: Value) -> Value;
-    fn not(&self, a: Value) -> Value;
-    fn xor(&self, a: Value, b: Value) -> Value;
-
-    fn shift_left(&self, a: Value, b: Value) -> Value;
-    fn shift_right(&self, a: Value, b: Value) -> Value;
-
-    fn greater_than(&self, a: Value, b: Value) -> Value;
+    fn add(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+    fn sub(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+    fn mult(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+    fn div(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+    fn rem(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+
+    fn and(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+    fn or(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+    fn not(&self, a: Value) -> Result<Value, ArithmeticError>;
+    fn xor(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+
+    fn shift_left(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+    fn shift_right(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+
+    fn greater_than(&self, a: Value, b: Value) -> Result<Value, ArithmeticError>;
+
+    /// Whether adding, subtracting, multiplying and comparing two unsigned or two signed integers
+    /// wraps and compares like Rust's integers do, so engines may do it without calling the alu.
+    /// Alus that treat integers any other way must leave this false.
+    fn wrapping_integers(&self) -> bool {
+        false
+    }
 }
 
 /// Defines objects that can be loaded into the VM. Prefer to use this trait when running the VM.
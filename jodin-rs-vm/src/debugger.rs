@@ -0,0 +1,287 @@
+//! An interactive, line-based debugger for guest programs.
+//!
+//! The debugger is attached to a vm with [VMBuilder::debugger](crate::vm::VMBuilder::debugger) and
+//! is consulted before every instruction. When it pauses, commands are read line by line from its
+//! input and the results are written to its output, so it can be driven from a terminal or from a
+//! script in tests. Reaching the end of the input detaches the debugger and lets the program finish.
+//!
+//! # Commands
+//! - `step` (`s`): executes one instruction
+//! - `next` (`n`): executes one instruction, stepping over calls
+//! - `finish` (`f`): runs until the current function returns
+//! - `continue` (`c`): runs until the next breakpoint
+//! - `break <label|index>` (`b`): sets a breakpoint on a label or instruction index
+//! - `delete <n>` (`d`): removes the nth breakpoint
+//! - `breakpoints`: lists the breakpoints
+//! - `backtrace` (`bt`): prints the counter stack as a symbolic backtrace
+//! - `stack`: prints the operand stack
+//! - `vars`: prints the variables of the current scope, with their source names if known
+//! - `list [n]` (`l`): prints the instructions around the program counter
+//! - `quit` (`q`): stops the program
+
+use crate::error::VMError;
+use crate::vm::VM;
+use crate::{ArithmeticsTrait, MemoryTrait};
+use std::fmt::{Display, Formatter};
+use std::io::{stdin, stdout, BufRead, BufReader, Write};
+
+/// Where the debugger should pause execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Pauses at a label, once the label has been loaded
+    Label(String),
+    /// Pauses at an instruction index
+    Index(usize),
+}
+
+impl Breakpoint {
+    /// Parses a breakpoint. Decimal and `0x` prefixed hex numbers are instruction indexes, anything
+    /// else is a label.
+    pub fn parse(s: &str) -> Self {
+        let index = match s.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        };
+        match index {
+            Some(index) => Breakpoint::Index(index),
+            None => Breakpoint::Label(s.to_string()),
+        }
+    }
+
+    fn hit<M: MemoryTrait, A: ArithmeticsTrait>(&self, vm: &VM<'_, M, A>, pc: usize) -> bool {
+        match self {
+            Breakpoint::Label(label) => vm.label_location(label) == Some(pc),
+            Breakpoint::Index(index) => *index == pc,
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Label(label) => write!(f, "{label}"),
+            Breakpoint::Index(index) => write!(f, "0x{index:016X}"),
+        }
+    }
+}
+
+/// How the debugger decides to pause when no breakpoint is hit
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StepMode {
+    /// Pause at the next instruction
+    Step,
+    /// Pause once the counter stack is at most this deep
+    Next(usize),
+    /// Pause once the counter stack is shallower than this
+    Finish(usize),
+    /// Only pause at breakpoints
+    Continue,
+}
+
+/// An interactive debugger. See the [module](self) documentation for the available commands.
+pub struct Debugger<'d> {
+    input: Box<dyn BufRead + 'd>,
+    output: Box<dyn Write + 'd>,
+    breakpoints: Vec<Breakpoint>,
+    mode: StepMode,
+    attached: bool,
+}
+
+impl<'d> Debugger<'d> {
+    /// Creates a debugger that reads commands from `input` and writes to `output`. The debugger
+    /// pauses before the first instruction.
+    pub fn new<R: BufRead + 'd, W: Write + 'd>(input: R, output: W) -> Self {
+        Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            breakpoints: vec![],
+            mode: StepMode::Step,
+            attached: true,
+        }
+    }
+
+    /// Creates a debugger driven by the standard input and output
+    pub fn stdio() -> Self {
+        Self::new(BufReader::new(stdin()), stdout())
+    }
+
+    /// Adds a breakpoint
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Called by the vm before it executes the instruction at `pc`. Pauses and reads commands if the
+    /// debugger should stop here.
+    pub fn before_instruction<M: MemoryTrait, A: ArithmeticsTrait>(
+        &mut self,
+        vm: &VM<'_, M, A>,
+        pc: usize,
+    ) -> Result<(), VMError> {
+        if !self.attached || !self.should_pause(vm, pc) {
+            return Ok(());
+        }
+        self.mode = StepMode::Continue;
+        self.print_location(vm, pc)?;
+        loop {
+            write!(self.output, "(jdb) ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                writeln!(self.output)?;
+                self.attached = false;
+                return Ok(());
+            }
+            let mut words = line.split_whitespace();
+            let command = match words.next() {
+                Some(command) => command,
+                None => continue,
+            };
+            let argument = words.next();
+            let depth = vm.counter_stack().len();
+            match command {
+                "step" | "s" => {
+                    self.mode = StepMode::Step;
+                    return Ok(());
+                }
+                "next" | "n" => {
+                    self.mode = StepMode::Next(depth);
+                    return Ok(());
+                }
+                "finish" | "f" => {
+                    self.mode = StepMode::Finish(depth);
+                    return Ok(());
+                }
+                "continue" | "c" => {
+                    self.mode = StepMode::Continue;
+                    return Ok(());
+                }
+                "break" | "b" => match argument {
+                    Some(argument) => {
+                        let breakpoint = Breakpoint::parse(argument);
+                        writeln!(
+                            self.output,
+                            "breakpoint {} at {}",
+                            self.breakpoints.len(),
+                            breakpoint
+                        )?;
+                        self.breakpoints.push(breakpoint);
+                    }
+                    None => writeln!(self.output, "usage: break <label|index>")?,
+                },
+                "delete" | "d" => match argument.and_then(|a| a.parse::<usize>().ok()) {
+                    Some(n) if n < self.breakpoints.len() => {
+                        let removed = self.breakpoints.remove(n);
+                        writeln!(self.output, "deleted breakpoint {n} at {removed}")?;
+                    }
+                    _ => writeln!(self.output, "usage: delete <breakpoint number>")?,
+                },
+                "breakpoints" => {
+                    for (n, breakpoint) in self.breakpoints.iter().enumerate() {
+                        writeln!(self.output, "{n}: {breakpoint}")?;
+                    }
+                }
+                "backtrace" | "bt" => self.print_backtrace(vm)?,
+                "stack" => {
+                    for (n, value) in vm.memory().stack().iter().rev().enumerate() {
+                        writeln!(self.output, "{n}: {value}")?;
+                    }
+                }
+                "vars" => {
+                    let mut vars = vm.memory().var_dict().into_iter().collect::<Vec<_>>();
+                    vars.sort_by_key(|(var, _)| *var);
+                    for (var, value) in vars {
+                        match vm.local_name(pc, var) {
+                            Some(name) => writeln!(self.output, "var {var} ({name}) = {value}")?,
+                            None => writeln!(self.output, "var {var} = {value}")?,
+                        }
+                    }
+                }
+                "list" | "l" => {
+                    let radius = argument.and_then(|a| a.parse().ok()).unwrap_or(3);
+                    self.print_listing(vm, pc, radius)?;
+                }
+                "help" | "h" => writeln!(
+                    self.output,
+                    "commands: step, next, finish, continue, break <label|index>, delete <n>, \
+                     breakpoints, backtrace, stack, vars, list [n], quit"
+                )?,
+                "quit" | "q" => {
+                    return Err(VMError::DebuggerQuit {
+                        location: vm.error_location(),
+                    })
+                }
+                other => writeln!(self.output, "unknown command {other:?}, try \"help\"")?,
+            }
+        }
+    }
+
+    fn should_pause<M: MemoryTrait, A: ArithmeticsTrait>(
+        &self,
+        vm: &VM<'_, M, A>,
+        pc: usize,
+    ) -> bool {
+        let depth = vm.counter_stack().len();
+        let stepped = match self.mode {
+            StepMode::Step => true,
+            StepMode::Next(start) => depth <= start,
+            StepMode::Finish(start) => depth < start,
+            StepMode::Continue => false,
+        };
+        stepped || self.breakpoints.iter().any(|b| b.hit(vm, pc))
+    }
+
+    fn print_location<M: MemoryTrait, A: ArithmeticsTrait>(
+        &mut self,
+        vm: &VM<'_, M, A>,
+        pc: usize,
+    ) -> Result<(), VMError> {
+        write!(self.output, "0x{pc:016X} in {}", vm.pc_to_recent_id(pc))?;
+        if let Some(source) = vm.source_location(pc) {
+            write!(self.output, " at {source}")?;
+        }
+        writeln!(self.output, ": {:?}", vm.instructions()[pc])?;
+        Ok(())
+    }
+
+    fn print_backtrace<M: MemoryTrait, A: ArithmeticsTrait>(
+        &mut self,
+        vm: &VM<'_, M, A>,
+    ) -> Result<(), VMError> {
+        for (frame, pc) in vm.counter_stack().iter().rev().enumerate() {
+            write!(
+                self.output,
+                "#{frame} 0x{pc:016X} in {}",
+                vm.pc_to_recent_id(*pc)
+            )?;
+            match vm.source_location(*pc) {
+                Some(source) => writeln!(self.output, " at {source}")?,
+                None => writeln!(self.output)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn print_listing<M: MemoryTrait, A: ArithmeticsTrait>(
+        &mut self,
+        vm: &VM<'_, M, A>,
+        pc: usize,
+        radius: usize,
+    ) -> Result<(), VMError> {
+        let instructions = vm.instructions();
+        let start = pc.saturating_sub(radius);
+        let end = (pc + radius).min(instructions.len() - 1);
+        for index in start..=end {
+            let marker = if index == pc { "=>" } else { "  " };
+            writeln!(
+                self.output,
+                "{marker} 0x{index:016X}: {:?}",
+                instructions[index]
+            )?;
+        }
+        Ok(())
+    }
+}
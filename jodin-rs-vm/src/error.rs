@@ -1,6 +1,12 @@
+use crate::fault::Fault;
+use crate::permissions::Capability;
+use jodin_common::assembly::debug_info::SourceLocation;
+use jodin_common::assembly::error::BytecodeError;
+use jodin_common::assembly::instructions::Asm;
 use jodin_common::assembly::value::Value;
 use jodin_common::error::JodinError;
 use std::error::Error as StdError;
+use std::fmt::{Display, Formatter};
 use std::io;
 use thiserror::Error;
 
@@ -10,12 +16,130 @@ pub enum VMError {
     NoExitCode,
     #[error("Expected UInteger exit code (found = {0:?})")]
     ExitCodeInvalidType(Value),
-    #[error("Invalid type found (expected= {expected}, found= {value:?})")]
-    InvalidType { value: Value, expected: String },
+    #[error("Stack underflow {location}")]
+    StackUnderflow { location: ErrorLocation },
+    #[error("Invalid type found (expected= {expected}, found= {value:?}) {location}")]
+    TypeMismatch {
+        value: Value,
+        expected: String,
+        location: ErrorLocation,
+    },
+    #[error("No instruction found for label (label= {label}) {location}")]
+    UnknownLabel {
+        label: String,
+        location: ErrorLocation,
+    },
+    #[error("{native:?} is not a native method {location}")]
+    InvalidNative {
+        native: String,
+        location: ErrorLocation,
+    },
+    #[error("Invalid arguments for native method {native:?}: {reason} {location}")]
+    InvalidNativeArguments {
+        native: String,
+        reason: String,
+        location: ErrorLocation,
+    },
+    #[error("{message:?} is not a valid message for {target} {location}")]
+    InvalidMessage {
+        message: String,
+        target: String,
+        location: ErrorLocation,
+    },
+    #[error("Attribute {attribute:?} does not exist {location}")]
+    MissingAttribute {
+        attribute: String,
+        location: ErrorLocation,
+    },
+    #[error("Index out of bounds (index= {index}, len= {len}) {location}")]
+    IndexOutOfBounds {
+        index: usize,
+        len: usize,
+        location: ErrorLocation,
+    },
+    #[error("Variable {var} not set {location}")]
+    VariableNotSet { var: usize, location: ErrorLocation },
+    #[error("Division by zero {location}")]
+    DivisionByZero { location: ErrorLocation },
+    #[error("Invalid instruction {asm:?} {location}")]
+    InvalidInstruction { asm: Asm, location: ErrorLocation },
+    #[error("{native} left a scope that was never entered {location}")]
+    ScopeUnderflow {
+        native: String,
+        location: ErrorLocation,
+    },
+    #[error("Jump by {diff} instructions lands outside of the program {location}")]
+    JumpOutOfBounds {
+        diff: isize,
+        location: ErrorLocation,
+    },
+    #[error("Maximum call depth exceeded {location}")]
+    StackOverflow { location: ErrorLocation },
+    #[error("Expected a reference (found= {value:?}) {location}")]
+    BadReference {
+        value: Value,
+        location: ErrorLocation,
+    },
+    #[error("{call:?} needs the {capability} capability, which the vm doesn't have {location}")]
+    PermissionDenied {
+        call: String,
+        capability: Capability,
+        location: ErrorLocation,
+    },
+    #[error("Operation can only be performed in kernel mode {location}")]
+    NotKernelMode { location: ErrorLocation },
+    #[error("No handler for fault ({fault}) {location}")]
+    UnhandledFault {
+        fault: Fault,
+        location: ErrorLocation,
+    },
+    #[error("Uncaught exception (value= {value}) {location}")]
+    UncaughtException {
+        value: Value,
+        location: ErrorLocation,
+    },
+    #[error("No exception handler to pop {location}")]
+    NoExceptionHandler { location: ErrorLocation },
+    #[error("Ran out of fuel {location}")]
+    OutOfFuel { location: ErrorLocation },
+    #[error("Operand stack limit exceeded (len= {len}, max= {max}) {location}")]
+    StackLimitExceeded {
+        len: usize,
+        max: usize,
+        location: ErrorLocation,
+    },
+    #[error("Deadline exceeded {location}")]
+    DeadlineExceeded { location: ErrorLocation },
+    #[error("Execution stopped by the debugger {location}")]
+    DebuggerQuit { location: ErrorLocation },
+    #[error("Every fiber is waiting to join another fiber {location}")]
+    Deadlock { location: ErrorLocation },
+    #[error("Sent next to a generator that has returned {location}")]
+    GeneratorFinished { location: ErrorLocation },
+    #[error("Replay diverged from the trace (expected= {expected}, found= {found}) {location}")]
+    ReplayDiverged {
+        expected: String,
+        found: String,
+        location: ErrorLocation,
+    },
+    #[error("No stopped run to resume")]
+    NothingToResume,
+    #[error("Can't take a snapshot while {0}")]
+    SnapshotUnavailable(String),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] bincode::Error),
+    #[error("Fault raised while handling a fault: {0}")]
+    DoubleFault(Box<VMError>),
     #[error("Given file is incorrect type")]
     WrongFileType,
+    #[error("Label {0:?} already registered")]
+    DuplicateLabel(String),
+    #[error("Static code exited with {0}")]
+    StaticCodeFailed(u32),
     #[error("IO Error: {0}")]
     IoError(io::Error),
+    #[error("Bytecode error: {0}")]
+    BytecodeError(#[from] BytecodeError),
     #[error("Jodin error: {0}")]
     JodinError(#[from] JodinError),
     #[error(transparent)]
@@ -26,8 +150,157 @@ pub enum VMError {
     Other(#[from] Box<dyn StdError>),
 }
 
+impl VMError {
+    /// Gets where in the guest program this error occurred, if known
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            VMError::StackUnderflow { location }
+            | VMError::TypeMismatch { location, .. }
+            | VMError::UnknownLabel { location, .. }
+            | VMError::InvalidNative { location, .. }
+            | VMError::InvalidNativeArguments { location, .. }
+            | VMError::InvalidMessage { location, .. }
+            | VMError::MissingAttribute { location, .. }
+            | VMError::IndexOutOfBounds { location, .. }
+            | VMError::VariableNotSet { location, .. }
+            | VMError::DivisionByZero { location }
+            | VMError::InvalidInstruction { location, .. }
+            | VMError::ScopeUnderflow { location, .. }
+            | VMError::JumpOutOfBounds { location, .. }
+            | VMError::StackOverflow { location }
+            | VMError::BadReference { location, .. }
+            | VMError::PermissionDenied { location, .. }
+            | VMError::NotKernelMode { location }
+            | VMError::UnhandledFault { location, .. }
+            | VMError::UncaughtException { location, .. }
+            | VMError::NoExceptionHandler { location }
+            | VMError::OutOfFuel { location }
+            | VMError::StackLimitExceeded { location, .. }
+            | VMError::DeadlineExceeded { location }
+            | VMError::DebuggerQuit { location }
+            | VMError::Deadlock { location }
+            | VMError::GeneratorFinished { location }
+            | VMError::ReplayDiverged { location, .. } => Some(location),
+            VMError::DoubleFault(inner) => inner.location(),
+            _ => None,
+        }
+    }
+
+    /// The guest's call stack when the error occurred, if it occurred while running an instruction
+    pub fn stack_trace(&self) -> Option<&StackTrace> {
+        self.location().map(|location| &location.stack_trace)
+    }
+}
+
 impl From<io::Error> for VMError {
     fn from(e: io::Error) -> Self {
         Self::IoError(e)
     }
 }
+
+/// The point of execution within the VM that an error occurred at
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorLocation {
+    /// The program counter of the instruction being executed
+    pub pc: usize,
+    /// The most recent public label before the program counter
+    pub function: Option<String>,
+    /// The guest's call stack when the error occurred
+    pub stack_trace: StackTrace,
+}
+
+impl ErrorLocation {
+    pub fn new(pc: usize, function: Option<String>) -> Self {
+        Self {
+            pc,
+            function,
+            stack_trace: StackTrace::default(),
+        }
+    }
+
+    /// Attaches the guest's call stack to the location
+    pub fn with_stack_trace(mut self, stack_trace: StackTrace) -> Self {
+        self.stack_trace = stack_trace;
+        self
+    }
+}
+
+impl Display for ErrorLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "(at 0x{:016X} in {})",
+            self.pc,
+            self.function.as_deref().unwrap_or("<none>")
+        )
+    }
+}
+
+/// A function call on the guest's call stack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StackFrame {
+    /// The instruction the frame is executing, or the call it will return from
+    pub pc: usize,
+    /// The most recent public label before the program counter
+    pub function: Option<String>,
+    /// The source location the instruction was compiled from, if it was loaded with debug info
+    pub source: Option<SourceLocation>,
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let function = self.function.as_deref().unwrap_or("<none>");
+        match &self.source {
+            Some(source) => write!(f, "at {function} ({source})"),
+            None => write!(f, "at {function} (0x{:016X})", self.pc),
+        }
+    }
+}
+
+/// The guest's call stack, with the innermost frame first. Printed with one frame per line, like
+/// the stack traces of most languages.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StackTrace {
+    pub frames: Vec<StackFrame>,
+}
+
+impl Display for StackTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for frame in &self.frames {
+            writeln!(f, "    {frame}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors that can be produced by an [ArithmeticsTrait](crate::ArithmeticsTrait) implementation.
+///
+/// The VM attaches the location of the failing instruction when converting it into a [VMError].
+#[derive(Debug, Error)]
+pub enum ArithmeticError {
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Invalid operand (expected= {expected}, found= {value:?})")]
+    InvalidOperand { value: Value, expected: String },
+}
+
+impl ArithmeticError {
+    pub fn invalid_operand(value: Value, expected: impl AsRef<str>) -> Self {
+        Self::InvalidOperand {
+            value,
+            expected: expected.as_ref().to_string(),
+        }
+    }
+
+    /// Converts this error into a [VMError] that occurred at some location
+    pub fn at(self, location: ErrorLocation) -> VMError {
+        match self {
+            ArithmeticError::DivisionByZero => VMError::DivisionByZero { location },
+            ArithmeticError::InvalidOperand { value, expected } => VMError::TypeMismatch {
+                value,
+                expected,
+                location,
+            },
+        }
+    }
+}
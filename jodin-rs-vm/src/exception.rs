@@ -0,0 +1,36 @@
+//! Exceptions are values thrown by jodin code with [Asm::Throw](jodin_common::assembly::instructions::Asm::Throw)
+//! and caught by handlers registered with [Asm::PushHandler](jodin_common::assembly::instructions::Asm::PushHandler).
+//!
+//! Unlike [faults](crate::fault), exceptions never leave user mode and are always recoverable.
+
+use crate::ScopeDepth;
+
+/// A registered exception handler, along with the state of the vm to unwind back to when a value
+/// is thrown to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExceptionHandler {
+    /// The instruction to continue at
+    pub target: usize,
+    /// The length of the counter stack when the handler was pushed
+    pub frame_depth: usize,
+    /// The length of the value stack when the handler was pushed
+    pub stack_len: usize,
+    /// The scope of the memory when the handler was pushed
+    pub scope_depth: ScopeDepth,
+}
+
+impl ExceptionHandler {
+    pub fn new(
+        target: usize,
+        frame_depth: usize,
+        stack_len: usize,
+        scope_depth: ScopeDepth,
+    ) -> Self {
+        Self {
+            target,
+            frame_depth,
+            stack_len,
+            scope_depth,
+        }
+    }
+}
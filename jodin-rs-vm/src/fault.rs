@@ -1,64 +1,235 @@
-use crate::{ArithmeticsTrait, MemoryTrait};
+use crate::error::{ErrorLocation, VMError};
+use crate::exception::ExceptionHandler;
+use crate::permissions::Capability;
 
 use jodin_common::assembly::value::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
 /// A fault is a VM-level exception. The fault should return to the original point of execution once
 /// it completes.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Fault {
     /// The following symbol is missing
     MissingSymbol(String),
+    /// An integer was divided by zero
+    DivisionByZero,
+    /// A value of the wrong type was given to an instruction
+    TypeError { value: Value, expected: String },
+    /// The maximum call depth of the vm was exceeded
+    StackOverflow,
+    /// A value that isn't a reference was used as a reference
+    BadReference(Value),
+    /// A native or plugin function was called without the capability it needs
+    PermissionDenied {
+        call: String,
+        capability: Capability,
+    },
     /// A fault occurred in a fault
     DoubleFault,
 }
 
 impl Fault {
-    pub fn handle_fault<'vm, 'l, M: MemoryTrait, A: ArithmeticsTrait>(
-        &self,
-        _handle: &FaultHandle,
-    ) {
+    /// The names of faults that can have handlers registered for them
+    pub const HANDLEABLE: [&'static str; 6] = [
+        "missing_symbol",
+        "division_by_zero",
+        "type_error",
+        "stack_overflow",
+        "bad_reference",
+        "permission_denied",
+    ];
+
+    /// Converts an error into the fault it should raise, if any
+    pub fn from_error(error: &VMError) -> Option<Self> {
+        match error {
+            VMError::UnknownLabel { label, .. } => Some(Fault::MissingSymbol(label.clone())),
+            VMError::DivisionByZero { .. } => Some(Fault::DivisionByZero),
+            VMError::TypeMismatch {
+                value, expected, ..
+            } => Some(Fault::TypeError {
+                value: value.clone(),
+                expected: expected.clone(),
+            }),
+            VMError::StackOverflow { .. } => Some(Fault::StackOverflow),
+            VMError::BadReference { value, .. } => Some(Fault::BadReference(value.clone())),
+            VMError::PermissionDenied {
+                call, capability, ..
+            } => Some(Fault::PermissionDenied {
+                call: call.clone(),
+                capability: *capability,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The name used to register a handler for this fault
+    pub fn name(&self) -> &'static str {
         match self {
-            Fault::MissingSymbol(_s) => {}
-            Fault::DoubleFault => panic!("Double Fault Encountered"),
+            Fault::MissingSymbol(_) => "missing_symbol",
+            Fault::DivisionByZero => "division_by_zero",
+            Fault::TypeError { .. } => "type_error",
+            Fault::StackOverflow => "stack_overflow",
+            Fault::BadReference(_) => "bad_reference",
+            Fault::PermissionDenied { .. } => "permission_denied",
+            Fault::DoubleFault => "double_fault",
+        }
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::MissingSymbol(s) => write!(f, "missing symbol {s:?}"),
+            Fault::DivisionByZero => write!(f, "division by zero"),
+            Fault::TypeError { value, expected } => {
+                write!(f, "type error (expected= {expected}, found= {value:?})")
+            }
+            Fault::StackOverflow => write!(f, "stack overflow"),
+            Fault::BadReference(v) => write!(f, "bad reference {v:?}"),
+            Fault::PermissionDenied { call, capability } => {
+                write!(f, "permission denied ({call:?} needs {capability})")
+            }
+            Fault::DoubleFault => write!(f, "double fault"),
+        }
+    }
+}
+
+/// What a fault handler wants the vm to do once it returns.
+///
+/// Handlers return one of the strings `"resume"`, `"retry"` or `"abort"`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultAction {
+    /// Continue at the instruction after the faulting one
+    Resume,
+    /// Execute the faulting instruction again, with the values the handler leaves on the stack or
+    /// with the operands it popped if the handler leaves none
+    Retry,
+    /// Stop the vm with the error that caused the fault
+    Abort,
+}
+
+impl FaultAction {
+    /// Gets the action a handler's return value asks for. Anything unrecognized aborts.
+    pub fn from_value(value: Option<&Value>) -> Self {
+        match value {
+            Some(Value::Str(s)) if s == "resume" => FaultAction::Resume,
+            Some(Value::Str(s)) if s == "retry" => FaultAction::Retry,
+            _ => FaultAction::Abort,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct FaultHandle {
     pub stored_pc: Vec<usize>,
     pub stored_stack: Vec<Value>,
+    /// The values the faulting instruction popped, in the order they were on the stack. They're
+    /// pushed back if the handler retries the instruction without leaving any values of its own.
+    pub operands: Vec<Value>,
     pub fault: Fault,
     pub target_function: Value,
+    /// The error that raised this fault, if it came from a failing instruction. Errors can't be
+    /// saved in [snapshots](crate::snapshot), so the fault itself is reported if a restored
+    /// handler aborts.
+    #[serde(skip)]
+    pub cause: Option<VMError>,
+    /// Where the fault occurred
+    pub location: ErrorLocation,
+    /// The exception handlers that were registered when the fault occurred
+    pub stored_handlers: Vec<ExceptionHandler>,
 }
 
 impl FaultHandle {
     pub fn new(
         stored_pc: Vec<usize>,
         stored_stack: Vec<Value>,
+        operands: Vec<Value>,
         fault: Fault,
         target_function: Value,
+        cause: Option<VMError>,
+        location: ErrorLocation,
+        stored_handlers: Vec<ExceptionHandler>,
     ) -> Self {
         FaultHandle {
             stored_pc,
             stored_stack,
+            operands,
             fault,
             target_function,
+            cause,
+            location,
+            stored_handlers,
         }
     }
+
+    /// Creates the dictionary that is passed to the fault handler.
+    ///
+    /// Always has the `fault`, `pc`, `function`, `pc_stack`, `stack` and `operands` attributes. Missing
+    /// symbols add a `symbol` attribute, type errors add `value` and `expected`, bad references
+    /// add `value`, and denied permissions add the `call` and the `capability` it needed.
+    pub fn to_value(&self) -> Value {
+        let mut dict: HashMap<String, Value> = HashMap::new();
+        dict.insert("fault".to_string(), Value::from(self.fault.name()));
+        dict.insert("pc".to_string(), Value::from(self.location.pc));
+        dict.insert(
+            "function".to_string(),
+            self.location
+                .function
+                .clone()
+                .map(Value::Str)
+                .unwrap_or(Value::Empty),
+        );
+        dict.insert("pc_stack".to_string(), Value::from(self.stored_pc.clone()));
+        dict.insert("stack".to_string(), Value::from(self.stored_stack.clone()));
+        dict.insert("operands".to_string(), Value::from(self.operands.clone()));
+        match &self.fault {
+            Fault::MissingSymbol(symbol) => {
+                dict.insert("symbol".to_string(), Value::from(symbol.as_str()));
+            }
+            Fault::TypeError { value, expected } => {
+                dict.insert("value".to_string(), value.clone());
+                dict.insert("expected".to_string(), Value::from(expected.as_str()));
+            }
+            Fault::BadReference(value) => {
+                dict.insert("value".to_string(), value.clone());
+            }
+            Fault::PermissionDenied { call, capability } => {
+                dict.insert("call".to_string(), Value::from(call.as_str()));
+                dict.insert("capability".to_string(), Value::from(capability.name()));
+            }
+            _ => {}
+        }
+        Value::Dictionary(dict)
+    }
 }
 
-#[derive(Default)]
-pub struct FaultJumpTable;
+/// Maps faults to the functions that handle them. Handlers can only be set while the vm is in
+/// kernel mode.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FaultJumpTable {
+    handlers: HashMap<String, Value>,
+}
 
 impl FaultJumpTable {
-    /// Gets the value for the fault to jump to
-    pub fn get_fault_jump(&self, fault: &Fault) -> Value {
-        match fault {
-            Fault::MissingSymbol(_) => Value::Native,
-            Fault::DoubleFault => {
-                panic!("No value for double fault")
-            }
+    /// Gets the value for the fault to jump to, if a handler was registered
+    pub fn get_fault_jump(&self, fault: &Fault) -> Option<&Value> {
+        self.handlers.get(fault.name())
+    }
+
+    /// Sets the handler of a fault. Returns `false` without setting anything if the fault can't be
+    /// handled.
+    pub fn set_fault_jump(&mut self, fault: &str, target: Value) -> bool {
+        if Fault::HANDLEABLE.contains(&fault) {
+            self.handlers.insert(fault.to_string(), target);
+            true
+        } else {
+            false
         }
     }
+
+    /// Removes the handler of a fault
+    pub fn clear_fault_jump(&mut self, fault: &str) -> Option<Value> {
+        self.handlers.remove(fault)
+    }
 }
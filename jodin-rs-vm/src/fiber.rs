@@ -0,0 +1,140 @@
+//! Fibers are lightweight threads of execution within a single vm.
+//!
+//! Every fiber has its own counter stack, operand stack, exception handlers and memory scopes, but
+//! they all share the loaded instructions, the heap and any variables that aren't in a scope.
+//! Fibers are spawned from a function value with the `@spawn` native, give up the vm with
+//! `@yield` and wait for another fiber to return with `@join`, which pushes the value it returned.
+//!
+//! Fibers are scheduled cooperatively in round-robin order, which keeps runs deterministic. A
+//! fiber can also be preempted after a number of instructions, set with
+//! [VMBuilder::preemption](crate::vm::VMBuilder::preemption). The run ends when the fiber it was
+//! started on returns, whether or not the fibers it spawned have finished.
+
+use crate::exception::ExceptionHandler;
+use crate::generator::{GeneratorId, Resumer, Resumption};
+use crate::SavedScopes;
+use jodin_common::assembly::value::Value;
+use std::collections::{HashMap, VecDeque};
+
+/// Identifies a fiber within a vm
+pub type FiberId = u64;
+
+/// The fiber a vm starts running on
+pub const MAIN_FIBER: FiberId = 0;
+
+/// The state of a thread of execution that isn't running
+pub(crate) struct Context {
+    pub counter_stack: Vec<usize>,
+    pub stack: Vec<Value>,
+    pub exception_handlers: Vec<ExceptionHandler>,
+    pub own_loads: Vec<(usize, usize)>,
+    pub scopes: SavedScopes,
+}
+
+/// The state of a fiber that isn't running
+pub(crate) struct SuspendedFiber {
+    pub id: FiberId,
+    pub context: Context,
+    /// The generators the fiber was running when it was suspended
+    pub resumers: Vec<Resumer>,
+}
+
+/// Why the running fiber is giving up the vm, or the running generator within it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Switch {
+    /// Lets the next fiber run
+    Yield,
+    /// Waits for a fiber to finish
+    Join(FiberId),
+    /// Runs a [generator](crate::generator) until it yields a value or returns
+    Resume(GeneratorId, Resumption),
+    /// Hands a value from the running generator back to whatever resumed it
+    YieldValue(Value),
+}
+
+/// Decides which fiber runs next
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    pub current: FiberId,
+    next_id: FiberId,
+    /// Fibers that can run, in the order they will
+    pub ready: VecDeque<SuspendedFiber>,
+    /// Fibers waiting for another fiber to finish
+    pub joining: HashMap<FiberId, (FiberId, SuspendedFiber)>,
+    /// The values returned by finished fibers that haven't been joined yet
+    pub results: HashMap<FiberId, Value>,
+    /// A switch requested by the running fiber, made once its instruction finishes
+    pub pending: Option<Switch>,
+    /// The number of instructions a fiber may run before it's preempted
+    pub preemption: Option<u64>,
+    /// The instructions the running fiber has left before it's preempted
+    pub slice: u64,
+}
+
+impl Scheduler {
+    pub fn new(preemption: Option<u64>) -> Self {
+        Self {
+            next_id: MAIN_FIBER + 1,
+            preemption,
+            slice: preemption.unwrap_or(0),
+            ..Default::default()
+        }
+    }
+
+    pub fn next_id(&mut self) -> FiberId {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Whether a fiber other than the running one hasn't finished
+    pub fn alive(&self, id: FiberId) -> bool {
+        self.ready.iter().any(|fiber| fiber.id == id)
+            || self.joining.values().any(|(_, fiber)| fiber.id == id)
+    }
+
+    /// Whether the running fiber is the only one, with no switch pending and no results left to
+    /// join
+    pub fn alone(&self) -> bool {
+        self.ready.is_empty()
+            && self.joining.is_empty()
+            && self.results.is_empty()
+            && self.pending.is_none()
+    }
+
+    /// Whether any fiber other than the running one is waiting to run
+    pub fn others_ready(&self) -> bool {
+        !self.ready.is_empty()
+    }
+
+    /// Counts down the running fiber's time slice, requesting a yield when it runs out
+    pub fn tick(&mut self) {
+        if let Some(preemption) = self.preemption {
+            self.slice = self.slice.saturating_sub(1);
+            if self.slice == 0 && self.pending.is_none() && self.others_ready() {
+                self.pending = Some(Switch::Yield);
+            }
+            if self.slice == 0 {
+                self.slice = preemption;
+            }
+        }
+    }
+
+    /// Records that a fiber returned a value, waking the fiber joining it if there is one
+    pub fn finish(&mut self, id: FiberId, value: Value) {
+        let joiner = self
+            .joining
+            .iter()
+            .find(|(_, (target, _))| *target == id)
+            .map(|(&joiner, _)| joiner);
+        match joiner.and_then(|joiner| self.joining.remove(&joiner)) {
+            Some((_, mut fiber)) => {
+                fiber.context.stack.push(value);
+                self.ready.push_back(fiber);
+            }
+            None => {
+                self.results.insert(id, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Natives that use the files under a root directory.
+//!
+//! A vm can only use files once it's given a root with
+//! [VMBuilder::file_root](crate::vm::VMBuilder::file_root). Paths are relative to the root, even
+//! when they start with `/`, and a path that leads outside of it, with `..` or through a symbolic
+//! link, is rejected. Opened files are kept in a table of file descriptors, which start
+//! after the descriptors of stdin, stdout and stderr.
+//!
+//! - `open(path, mode)`: opens a file, answering its descriptor. The mode is one of
+//!   - `"read"`: for reading a file that exists
+//!   - `"write"`: for writing a file, which is created if it doesn't exist or emptied if it does
+//!   - `"append"`: for writing to the end of a file, which is created if it doesn't exist
+//!   - `"create"`: for writing a new file, failing if it already exists
+//! - `read(fd)`: the rest of a file, as a string
+//! - `read(fd, count)`: an array of up to `count` bytes of a file, which is only shorter at the end
+//!   of the file
+//! - `write(fd, string)`: writes a string to a file, or to stdout or stderr with 1 or 2, which
+//!   answer nothing
+//! - `seek(fd, offset)`, `seek(fd, offset, from)`: moves to an offset from `"start"`, `"current"`
+//!   or `"end"` of a file, answering the offset from its start. Offsets are from the start unless
+//!   another place is given.
+//! - `close(fd)`: closes a file
+//! - `stat(path)`: a dictionary with the `size` of a file, and whether it `is_file` or `is_dir`
+//! - `list_dir(path)`: an array of the names of the entries of a directory, in order
+//! - `remove(path)`: removes a file or an empty directory
+//!
+//! Like the [input](crate::input) natives, every native but `close` answers a dictionary, so paths
+//! outside of the root and files that can't be used, like a file opened for reading that's written
+//! to, can be tested for.
+//! Reading at the end of a file answers [READ_EOF](crate::input::READ_EOF), and otherwise
+//! [READ_ERROR](crate::input::READ_ERROR) says why the call failed. Writing to or closing a
+//! descriptor that isn't open fails with
+//! [InvalidNativeArguments](crate::error::VMError::InvalidNativeArguments).
+//!
+//! These natives have [outside effects](crate::replay), so they're recorded and replayed. Open
+//! files aren't part of [snapshots](crate::snapshot).
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The first descriptor given to an opened file
+pub const FIRST_FILE_DESCRIPTOR: u64 = 3;
+
+/// The root directory of a vm, and the files it has open
+#[derive(Debug)]
+pub struct FileTable {
+    /// The canonical path of the root, if the vm can use files
+    root: Option<PathBuf>,
+    open: HashMap<u64, File>,
+    next_fd: u64,
+}
+
+impl FileTable {
+    /// Creates a table for files under a root, which must be a directory that exists
+    pub fn new(root: Option<&Path>) -> io::Result<Self> {
+        let root = match root {
+            Some(root) => {
+                let root = root.canonicalize()?;
+                if !root.is_dir() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("file root {:?} is not a directory", root),
+                    ));
+                }
+                Some(root)
+            }
+            None => None,
+        };
+        Ok(Self {
+            root,
+            open: HashMap::new(),
+            next_fd: FIRST_FILE_DESCRIPTOR,
+        })
+    }
+
+    /// The root directory, if the vm can use files
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Finds the path on the host of a path under the root, failing with why the path can't be used
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let root = self
+            .root
+            .as_ref()
+            .ok_or_else(|| "the vm has no file root".to_string())?;
+        let mut resolved = root.clone();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+                Component::ParentDir => {
+                    if resolved == *root {
+                        return Err(format!("{path:?} is outside of the file root"));
+                    }
+                    resolved.pop();
+                }
+                Component::Normal(name) => resolved.push(name),
+            }
+        }
+        // symbolic links can still lead outside of the root, so the part of the path that exists
+        // is checked where it really is. Links that lead nowhere can't be canonicalized, so
+        // they're rejected rather than followed when a file is created.
+        let existing = resolved
+            .ancestors()
+            .find(|ancestor| ancestor.symlink_metadata().is_ok())
+            .unwrap_or(root);
+        match existing.canonicalize() {
+            Ok(real) if real.starts_with(root) => Ok(resolved),
+            Ok(_) => Err(format!("{path:?} is outside of the file root")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Adds an opened file to the table, returning its descriptor
+    pub fn insert(&mut self, file: File) -> u64 {
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        self.open.insert(fd, file);
+        fd
+    }
+
+    /// Whether there's an open file with a descriptor
+    pub fn is_open(&self, fd: u64) -> bool {
+        self.open.contains_key(&fd)
+    }
+
+    /// The open file with a descriptor
+    pub fn get(&mut self, fd: u64) -> Option<&mut File> {
+        self.open.get_mut(&fd)
+    }
+
+    /// Closes the file with a descriptor, returning whether it was open
+    pub fn close(&mut self, fd: u64) -> bool {
+        self.open.remove(&fd).is_some()
+    }
+}
@@ -0,0 +1,391 @@
+//! A memory implementation built from contiguous call frames.
+//!
+//! Every pushed scope is a frame whose variables are slots in one contiguous vector, indexed by
+//! variable number. Frames can be sized ahead of time with
+//! [reserve_vars](MemoryTrait::reserve_vars), which the compiler does for every function from the
+//! number of variables it uses, so setting a variable never has to grow the vector.
+//!
+//! Frames that are saved with [save_current_scope](MemoryTrait::save_current_scope) can outlive
+//! the scope that created them, which is how closures capture variables. Saving a frame moves its
+//! slots out of the contiguous vector into an environment on the heap that is shared by everything
+//! that loads it, so variables set by a closure are seen by the next load of the same scope.
+
+use crate::{MemoryTrait, SavedScopes, ScopeDepth};
+use jodin_common::assembly::error::BytecodeError;
+use jodin_common::assembly::value::{JRef, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+const GLOBAL_SCOPE_IDENTIFIER: &str = "@@GLOBAL_SCOPE";
+
+/// A variable
+type Slot = Option<Rc<RefCell<Value>>>;
+
+/// The slots of a frame that has been saved
+type Environment = Rc<RefCell<Vec<Slot>>>;
+
+/// Where the variables of a frame are stored
+#[derive(Debug, Clone)]
+enum Frame {
+    /// Slots from an index of the contiguous slot vector up to the base of the next contiguous
+    /// frame
+    Contiguous { base: usize },
+    /// A saved frame
+    Saved(Environment),
+}
+
+#[derive(Debug)]
+pub struct FrameMemory {
+    slots: Vec<Slot>,
+    frames: Vec<Frame>,
+    /// The index of the first frame of each loaded scope
+    loads: Vec<usize>,
+    saved: HashMap<u64, Environment>,
+    stack: Vec<Value>,
+}
+
+fn hash_identifier<H: Hash>(identifier: H) -> u64 {
+    let mut hasher = DefaultHasher::default();
+    identifier.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Sets a slot, reusing its cell if nothing else refers to it
+fn set_slot(slot: &mut Slot, value: Value) {
+    match slot {
+        Some(cell) if Rc::strong_count(cell) == 1 => *cell.borrow_mut() = value,
+        _ => *slot = Some(Rc::new(RefCell::new(value))),
+    }
+}
+
+fn var_not_set(var: usize) -> BytecodeError {
+    BytecodeError::VariableNotSet(var)
+}
+
+impl FrameMemory {
+    fn current_frame(&self) -> &Frame {
+        self.frames.last().expect("no frames in memory")
+    }
+
+    /// Runs a function on the slots of the current frame
+    fn with_slots<R>(&self, f: impl FnOnce(&[Slot]) -> R) -> R {
+        match self.current_frame() {
+            &Frame::Contiguous { base } => f(&self.slots[base..]),
+            Frame::Saved(environment) => f(&environment.borrow()),
+        }
+    }
+
+    /// Runs a function on the slots of the current frame, making sure it has at least `len` slots
+    fn with_slots_mut<R>(&mut self, len: usize, f: impl FnOnce(&mut [Slot]) -> R) -> R {
+        match self.frames.last().expect("no frames in memory") {
+            &Frame::Contiguous { base } => {
+                // a contiguous current frame is always the last one in the slot vector
+                if self.slots.len() < base + len {
+                    self.slots.resize(base + len, None);
+                }
+                f(&mut self.slots[base..])
+            }
+            Frame::Saved(environment) => {
+                let mut slots = environment.borrow_mut();
+                if slots.len() < len {
+                    slots.resize(len, None);
+                }
+                f(&mut slots)
+            }
+        }
+    }
+
+    fn pop_frame(&mut self) {
+        if let Some(Frame::Contiguous { base }) = self.frames.pop() {
+            self.slots.truncate(base);
+        }
+    }
+
+    /// The number of frames pushed since the most recent load
+    fn scopes(&self) -> usize {
+        self.frames.len() - self.loads.last().copied().unwrap_or(0)
+    }
+}
+
+impl Default for FrameMemory {
+    fn default() -> Self {
+        let global: Environment = Default::default();
+        let mut saved = HashMap::new();
+        saved.insert(hash_identifier(GLOBAL_SCOPE_IDENTIFIER), global.clone());
+        Self {
+            slots: vec![],
+            frames: vec![Frame::Saved(global)],
+            loads: vec![0],
+            saved,
+            stack: vec![],
+        }
+    }
+}
+
+/// The form a frame memory is saved in by [snapshots](crate::snapshot). Environments can be shared
+/// by several frames and saved scopes, so each is saved once and referred to by its index.
+#[derive(Serialize, Deserialize)]
+struct SavedFrameMemory {
+    slots: Vec<Option<JRef>>,
+    frames: Vec<SavedFrame>,
+    loads: Vec<usize>,
+    environments: Vec<Vec<Option<JRef>>>,
+    saved: HashMap<u64, usize>,
+    stack: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SavedFrame {
+    Contiguous { base: usize },
+    Saved(usize),
+}
+
+fn save_slots(slots: &[Slot]) -> Vec<Option<JRef>> {
+    slots
+        .iter()
+        .map(|slot| slot.clone().map(JRef::from))
+        .collect()
+}
+
+fn restore_slots(slots: Vec<Option<JRef>>) -> Vec<Slot> {
+    slots.into_iter().map(|slot| slot.map(Rc::from)).collect()
+}
+
+impl Serialize for FrameMemory {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut environments = vec![];
+        let mut indices = HashMap::new();
+        let mut environment_index = |environment: &Environment| {
+            *indices.entry(Rc::as_ptr(environment)).or_insert_with(|| {
+                environments.push(save_slots(&environment.borrow()));
+                environments.len() - 1
+            })
+        };
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| match frame {
+                &Frame::Contiguous { base } => SavedFrame::Contiguous { base },
+                Frame::Saved(environment) => SavedFrame::Saved(environment_index(environment)),
+            })
+            .collect();
+        let saved = self
+            .saved
+            .iter()
+            .map(|(&hash, environment)| (hash, environment_index(environment)))
+            .collect();
+        SavedFrameMemory {
+            slots: save_slots(&self.slots),
+            frames,
+            loads: self.loads.clone(),
+            environments,
+            saved,
+            stack: self.stack.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FrameMemory {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let saved = SavedFrameMemory::deserialize(deserializer)?;
+        let environments: Vec<Environment> = saved
+            .environments
+            .into_iter()
+            .map(|slots| Rc::new(RefCell::new(restore_slots(slots))))
+            .collect();
+        let environment = |index: usize| {
+            environments.get(index).cloned().ok_or_else(|| {
+                serde::de::Error::custom(format!("no environment {index} in frame memory"))
+            })
+        };
+        let frames = saved
+            .frames
+            .into_iter()
+            .map(|frame| match frame {
+                SavedFrame::Contiguous { base } => Ok(Frame::Contiguous { base }),
+                SavedFrame::Saved(index) => environment(index).map(Frame::Saved),
+            })
+            .collect::<Result<_, _>>()?;
+        let saved_scopes = saved
+            .saved
+            .into_iter()
+            .map(|(hash, index)| environment(index).map(|environment| (hash, environment)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            slots: restore_slots(saved.slots),
+            frames,
+            loads: saved.loads,
+            saved: saved_scopes,
+            stack: saved.stack,
+        })
+    }
+}
+
+impl MemoryTrait for FrameMemory {
+    fn global_scope(&mut self) {
+        self.load_scope(GLOBAL_SCOPE_IDENTIFIER);
+    }
+
+    fn save_current_scope<H: Hash + Debug>(&mut self, identifier: H) {
+        let frame = self.frames.last_mut().expect("no frames in memory");
+        let environment = match frame {
+            Frame::Saved(environment) => environment.clone(),
+            &mut Frame::Contiguous { base } => {
+                // a contiguous current frame is always the last one in the slot vector
+                let environment: Environment =
+                    Rc::new(RefCell::new(self.slots.drain(base..).collect()));
+                *frame = Frame::Saved(environment.clone());
+                environment
+            }
+        };
+        trace!("Saved current scope to {identifier:?}");
+        self.saved.insert(hash_identifier(identifier), environment);
+    }
+
+    fn load_scope<H: Hash + Debug>(&mut self, identifier: H) {
+        let environment = self
+            .saved
+            .entry(hash_identifier(&identifier))
+            .or_default()
+            .clone();
+        trace!("Loading scope {identifier:?}");
+        self.loads.push(self.frames.len());
+        self.frames.push(Frame::Saved(environment));
+    }
+
+    fn push_scope(&mut self) {
+        self.frames.push(Frame::Contiguous {
+            base: self.slots.len(),
+        });
+    }
+
+    fn pop_scope(&mut self) -> Result<(), BytecodeError> {
+        // the global frame is never popped, so there is always a current frame
+        if self.scopes() == 0 || self.frames.len() == 1 {
+            return Err(BytecodeError::NoScopeToLeave);
+        }
+        self.pop_frame();
+        Ok(())
+    }
+
+    fn back_scope(&mut self) -> Result<(), BytecodeError> {
+        if self.loads.len() == 1 {
+            return Err(BytecodeError::NoScopeToLeave);
+        }
+        let start = self.loads.pop().unwrap();
+        while self.frames.len() > start {
+            self.pop_frame();
+        }
+        Ok(())
+    }
+
+    fn scope_depth(&self) -> ScopeDepth {
+        ScopeDepth {
+            loads: self.loads.len(),
+            scopes: self.scopes(),
+        }
+    }
+
+    fn unwind_scopes(&mut self, depth: ScopeDepth) {
+        while self.loads.len() > depth.loads && self.back_scope().is_ok() {}
+        while self.scopes() > depth.scopes {
+            self.pop_frame();
+        }
+    }
+
+    fn take_scopes(&mut self) -> SavedScopes {
+        let global = self.saved[&hash_identifier(GLOBAL_SCOPE_IDENTIFIER)].clone();
+        let slots = std::mem::take(&mut self.slots);
+        let frames = std::mem::replace(&mut self.frames, vec![Frame::Saved(global)]);
+        let loads = std::mem::replace(&mut self.loads, vec![0]);
+        Box::new((slots, frames, loads))
+    }
+
+    fn replace_scopes(&mut self, scopes: SavedScopes) {
+        let (slots, frames, loads) = *scopes
+            .downcast::<(Vec<Slot>, Vec<Frame>, Vec<usize>)>()
+            .expect("scopes were not taken from this memory");
+        self.slots = slots;
+        self.frames = frames;
+        self.loads = loads;
+    }
+
+    fn reserve_vars(&mut self, count: usize) {
+        self.with_slots_mut(count, |_| ());
+    }
+
+    fn set_var(&mut self, var: usize, value: Value) {
+        self.with_slots_mut(var + 1, |slots| set_slot(&mut slots[var], value));
+    }
+
+    fn get_var(&self, var: usize) -> Result<Rc<RefCell<Value>>, BytecodeError> {
+        self.with_slots(|slots| slots.get(var).cloned().flatten())
+            .ok_or_else(|| var_not_set(var))
+    }
+
+    fn var_value(&self, var: usize) -> Result<Value, BytecodeError> {
+        self.with_slots(|slots| {
+            slots
+                .get(var)
+                .and_then(Option::as_ref)
+                .map(|cell| cell.borrow().clone())
+        })
+        .ok_or_else(|| var_not_set(var))
+    }
+
+    fn clear_var(&mut self, var: usize) -> Result<(), BytecodeError> {
+        let cleared = self.with_slots_mut(0, |slots| slots.get_mut(var).and_then(Option::take));
+        cleared.map(|_| ()).ok_or_else(|| var_not_set(var))
+    }
+
+    fn next_var_number(&self) -> usize {
+        self.with_slots(|slots| {
+            slots
+                .iter()
+                .position(Option::is_none)
+                .unwrap_or(slots.len())
+        })
+    }
+
+    fn var_dict(&self) -> HashMap<usize, Value> {
+        self.with_slots(|slots| {
+            slots
+                .iter()
+                .enumerate()
+                .filter_map(|(var, slot)| slot.as_ref().map(|cell| (var, cell.borrow().clone())))
+                .collect()
+        })
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Option<Value> {
+        self.stack.pop()
+    }
+
+    fn take_stack(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.stack)
+    }
+
+    fn replace_stack(&mut self, stack: Vec<Value>) {
+        self.stack = stack;
+    }
+
+    fn stack(&self) -> &[Value] {
+        &*self.stack
+    }
+}
@@ -0,0 +1,101 @@
+//! Generators are functions that can give up the vm part way through, handing a value back to
+//! whatever resumed them, and carry on from where they left off the next time they're resumed.
+//!
+//! A generator is created from a function value and its arguments with the `@generator` native,
+//! which pushes a generator object. The function doesn't start running until the object is sent
+//! `next` or `has_next`. Either message resumes the generator until it passes a value to the
+//! `@yield_value` native, which `next` pushes, or until it returns, after which `has_next` pushes
+//! false and `next` fails. The value yielded to answer `has_next` is kept for the `next` that
+//! follows it, so a generator never runs further ahead than it has to.
+//!
+//! Like a [fiber](crate::fiber), a generator has its own counter stack, operand stack, exception
+//! handlers and memory scopes, which are saved while it's suspended, so its locals survive between
+//! values. Whatever resumed a generator waits for it, and an exception the generator doesn't catch
+//! ends it and is thrown from where it was resumed. Generators are freed along with their object.
+
+use crate::fiber::Context;
+use jodin_common::assembly::value::{JRef, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Weak;
+
+/// Identifies a generator within a vm
+pub type GeneratorId = u64;
+
+/// The attribute of a generator object that holds the id of its generator
+pub const GENERATOR_ID: &str = "@generator";
+
+/// The message a generator was resumed to answer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Resumption {
+    Next,
+    HasNext,
+}
+
+/// Whatever resumed a running generator, waiting for it to yield or return
+pub(crate) struct Resumer {
+    pub generator: GeneratorId,
+    pub resumption: Resumption,
+    pub context: Context,
+}
+
+pub(crate) struct Generator {
+    object: Weak<RefCell<Value>>,
+    /// The state of the generator while it's suspended
+    pub context: Option<Context>,
+    /// A value yielded to answer `has_next` that hasn't been taken by `next`
+    pub peeked: Option<Value>,
+    pub finished: bool,
+}
+
+impl Generator {
+    pub fn new(object: &JRef, context: Context) -> Self {
+        Self {
+            object: object.downgrade(),
+            context: Some(context),
+            peeked: None,
+            finished: false,
+        }
+    }
+
+    pub fn running(&self) -> bool {
+        self.context.is_none() && !self.finished
+    }
+}
+
+/// The generators of a vm
+#[derive(Default)]
+pub(crate) struct Generators {
+    next_id: GeneratorId,
+    generators: HashMap<GeneratorId, Generator>,
+    /// The generators the running fiber is inside of, innermost last
+    pub resumers: Vec<Resumer>,
+}
+
+impl Generators {
+    pub fn next_id(&mut self) -> GeneratorId {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Adds a generator, dropping any that can never be resumed again
+    pub fn insert(&mut self, id: GeneratorId, generator: Generator) {
+        self.generators
+            .retain(|_, generator| generator.running() || generator.object.strong_count() > 0);
+        self.generators.insert(id, generator);
+    }
+
+    /// Whether any generator is running or can still be resumed
+    pub fn any_alive(&self) -> bool {
+        !self.resumers.is_empty()
+            || self
+                .generators
+                .values()
+                .any(|generator| generator.running() || generator.object.strong_count() > 0)
+    }
+
+    pub fn get_mut(&mut self, id: GeneratorId) -> Option<&mut Generator> {
+        self.generators.get_mut(&id)
+    }
+}
@@ -0,0 +1,218 @@
+//! The heap that owns the values guest programs create references to.
+//!
+//! References are reference counted, so a value that refers back to itself, directly or through
+//! other values, would never be freed. The heap keeps track of every reference the vm allocates
+//! and periodically collects the cycles among them.
+//!
+//! Collection works by trial deletion. For every tracked value, the references to it from other
+//! tracked values are counted. Any value with more references than that is referred to from
+//! outside the heap, such as the operand stack, a variable, a saved scope or the host, and is a
+//! root. Everything reachable from a root is kept, and the rest can only be referred to by other
+//! unreachable values, so it's cleared, which breaks the cycles and lets them be freed.
+//!
+//! References to variables are owned by the [memory](crate::MemoryTrait) rather than the heap,
+//! so they are never collected, but they still keep everything they refer to alive.
+
+use jodin_common::assembly::value::{JRef, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::rc::Weak;
+
+/// The number of allocations between collections by default
+pub const DEFAULT_COLLECTION_THRESHOLD: usize = 1 << 10;
+
+/// When the heap collects cycles
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CollectionPolicy {
+    /// Only when [collect](Heap::collect) is called, either by the host or with the
+    /// `@collect_garbage` native
+    Manual,
+    /// Once there have been this many allocations since the last collection, or as many as there
+    /// were values left by it, whichever is more
+    Threshold(usize),
+}
+
+impl Default for CollectionPolicy {
+    fn default() -> Self {
+        CollectionPolicy::Threshold(DEFAULT_COLLECTION_THRESHOLD)
+    }
+}
+
+/// Statistics about a heap
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct HeapStats {
+    /// The number of values in the heap that are still alive
+    pub live_objects: usize,
+    /// An estimate of the bytes used by the values that are still alive
+    pub bytes: usize,
+    /// The number of values ever allocated
+    pub allocations: u64,
+    /// The number of collections run
+    pub collections: u64,
+    /// The number of values freed by collections
+    pub collected: u64,
+}
+
+/// Tracks the values references have been created to
+#[derive(Debug, Default)]
+pub struct Heap {
+    objects: Vec<Weak<RefCell<Value>>>,
+    policy: CollectionPolicy,
+    /// Allocations since the last collection
+    pending: usize,
+    /// The number of values left by the last collection
+    survivors: usize,
+    allocations: u64,
+    collections: u64,
+    collected: u64,
+}
+
+/// Runs a function on every reference directly contained in a value, without following them
+fn for_each_reference(value: &Value, f: &mut impl FnMut(&JRef)) {
+    match value {
+        Value::Reference(reference) => f(reference),
+        Value::Array(values) => values.iter().for_each(|v| for_each_reference(v, f)),
+        Value::Dictionary(dict) => dict.values().for_each(|v| for_each_reference(v, f)),
+        _ => {}
+    }
+}
+
+/// Estimates the bytes a value uses outside of itself, not counting the values it refers to
+fn heap_size(value: &Value) -> usize {
+    match value {
+        Value::Str(s) => s.capacity(),
+        Value::Bytecode(bytecode) => bytecode.capacity(),
+        Value::Array(values) => {
+            values.capacity() * size_of::<Value>() + values.iter().map(heap_size).sum::<usize>()
+        }
+        Value::Dictionary(dict) => dict
+            .iter()
+            .map(|(key, value)| size_of::<(String, Value)>() + key.capacity() + heap_size(value))
+            .sum(),
+        _ => 0,
+    }
+}
+
+impl Heap {
+    pub fn new(policy: CollectionPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> CollectionPolicy {
+        self.policy
+    }
+
+    /// Whether enough allocations have happened for the policy to call for a collection
+    pub fn should_collect(&self) -> bool {
+        match self.policy {
+            CollectionPolicy::Manual => false,
+            CollectionPolicy::Threshold(threshold) => self.pending >= threshold.max(self.survivors),
+        }
+    }
+
+    /// Creates a reference to a value that's owned by the heap
+    pub fn allocate(&mut self, value: Value) -> Value {
+        let reference = JRef::new(value);
+        self.objects.push(reference.downgrade());
+        self.pending += 1;
+        self.allocations += 1;
+        Value::Reference(reference)
+    }
+
+    /// Tracks a value that was allocated outside of the heap, such as one restored from a
+    /// [snapshot](crate::snapshot)
+    pub(crate) fn track(&mut self, reference: &JRef) {
+        self.objects.push(reference.downgrade());
+    }
+
+    /// The values in the heap that are still alive
+    pub(crate) fn live(&self) -> impl Iterator<Item = JRef> + '_ {
+        self.objects
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(JRef::from)
+    }
+
+    /// Frees every value that can't be reached from outside the heap. Returns the number of
+    /// values freed.
+    pub fn collect(&mut self) -> usize {
+        let objects = self.live().collect::<Vec<_>>();
+        self.objects = objects.iter().map(JRef::downgrade).collect();
+        self.pending = 0;
+        self.collections += 1;
+
+        let index = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| (object.as_ptr() as *const Value, i))
+            .collect::<HashMap<_, _>>();
+        let mut contents = Vec::with_capacity(objects.len());
+        let mut internal = vec![0; objects.len()];
+        for object in &objects {
+            let borrowed = match object.try_borrow() {
+                Ok(borrowed) => borrowed,
+                // something is changing the value, so nothing can be known about it
+                Err(_) => {
+                    self.survivors = objects.len();
+                    return 0;
+                }
+            };
+            let mut referred = vec![];
+            for_each_reference(&borrowed, &mut |reference| {
+                if let Some(&i) = index.get(&(reference.as_ptr() as *const Value)) {
+                    internal[i] += 1;
+                    referred.push(i);
+                }
+            });
+            contents.push(referred);
+        }
+
+        // `objects` holds one strong pointer to each value itself
+        let mut reachable = vec![false; objects.len()];
+        let mut pending = (0..objects.len())
+            .filter(|&i| objects[i].strong_count() - 1 > internal[i])
+            .collect::<Vec<_>>();
+        while let Some(i) = pending.pop() {
+            if !std::mem::replace(&mut reachable[i], true) {
+                pending.extend(contents[i].iter().copied().filter(|&j| !reachable[j]));
+            }
+        }
+
+        let mut garbage = vec![];
+        for (object, _) in objects.iter().zip(&reachable).filter(|(_, &r)| !r) {
+            garbage.push(object.replace(Value::Empty));
+        }
+        let freed = garbage.len();
+        trace!("Collected {freed} of {} values", objects.len());
+        drop(objects);
+        drop(garbage);
+        self.objects.retain(|object| object.strong_count() > 0);
+        self.survivors = self.objects.len();
+        self.collected += freed as u64;
+        freed
+    }
+
+    /// Gets statistics about the heap
+    pub fn stats(&self) -> HeapStats {
+        let mut live_objects = 0;
+        let mut bytes = 0;
+        for object in self.live() {
+            live_objects += 1;
+            bytes += size_of::<RefCell<Value>>() + 2 * size_of::<usize>();
+            if let Ok(value) = object.try_borrow() {
+                bytes += heap_size(&value);
+            }
+        }
+        HeapStats {
+            live_objects,
+            bytes,
+            allocations: self.allocations,
+            collections: self.collections,
+            collected: self.collected,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Natives that read the stdin of the vm.
+//!
+//! The stdin is the one given to [VMBuilder::with_stdin](crate::vm::VMBuilder::with_stdin), or the
+//! stdin of the process. It's buffered, so reads that stop partway through the input, like
+//! `read_line`, leave the rest of it for the next read.
+//!
+//! - `read_line()`: the next line, without its line ending
+//! - `read_bytes(count)`: an array of up to `count` bytes, which is only shorter at the end of
+//!   the input
+//! - `read_all()`: the rest of the input, as a string
+//! - `read_int()`: the next line, parsed as an integer
+//! - `read_float()`: the next line, parsed as a float
+//!
+//! Every read answers a dictionary, so running out of input and input that can't be parsed can be
+//! tested for. The [READ_OK] entry says whether the read succeeded, and then [READ_VALUE] holds
+//! what was read. Otherwise [READ_EOF] says whether there was nothing left to read, and if there
+//! was, [READ_ERROR] says why it couldn't be read. Numbers are parsed with the whitespace around
+//! them ignored.
+//!
+//! These natives have [outside effects](crate::replay), so they're recorded and replayed.
+
+use crate::error::VMError;
+use crate::natives::NativeCall;
+use jodin_common::assembly::value::Value;
+use std::fmt::Display;
+use std::io::{ErrorKind, Read};
+use std::str::FromStr;
+
+/// The entry of a read result that says whether the read succeeded
+pub const READ_OK: &str = "ok";
+
+/// The entry of a read result that says whether there was nothing left to read
+pub const READ_EOF: &str = "eof";
+
+/// The entry of a successful read result that holds what was read
+pub const READ_VALUE: &str = "value";
+
+/// The entry of a failed read result that says why the input couldn't be read
+pub const READ_ERROR: &str = "error";
+
+/// The result of a read, or of any other call, that succeeded
+pub(crate) fn success(value: Value) -> Value {
+    Value::from([
+        (READ_OK, Value::from(true)),
+        (READ_EOF, Value::from(false)),
+        (READ_VALUE, value),
+    ])
+}
+
+/// The result of a read at the end of the input
+pub(crate) fn eof() -> Value {
+    Value::from([(READ_OK, false), (READ_EOF, true)])
+}
+
+/// The result of a read of input that couldn't be read, or of any other call that failed
+pub(crate) fn failure(reason: impl ToString) -> Value {
+    Value::from([
+        (READ_OK, Value::from(false)),
+        (READ_EOF, Value::from(false)),
+        (READ_ERROR, Value::Str(reason.to_string())),
+    ])
+}
+
+/// Reads the next line, without its line ending. Answers `Ok(None)` at the end of the input, and
+/// the result to answer if it isn't valid utf-8.
+fn next_line(call: &mut NativeCall<'_, '_>) -> Result<Result<Option<String>, Value>, VMError> {
+    let mut line = String::new();
+    match call.stdin().read_line(&mut line) {
+        Ok(0) => Ok(Ok(None)),
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Ok(Ok(Some(line)))
+        }
+        Err(e) if e.kind() == ErrorKind::InvalidData => Ok(Err(failure(e))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads the next line, parsed as a number
+fn parse_line<T>(call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError>
+where
+    T: FromStr + Into<Value>,
+    T::Err: Display,
+{
+    Ok(Some(match next_line(call)? {
+        Ok(Some(line)) => match line.trim().parse::<T>() {
+            Ok(number) => success(number.into()),
+            Err(e) => failure(format!("can not parse {:?}: {e}", line.trim())),
+        },
+        Ok(None) => eof(),
+        Err(result) => result,
+    }))
+}
+
+pub(crate) fn read_line(call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError> {
+    Ok(Some(match next_line(call)? {
+        Ok(Some(line)) => success(Value::Str(line)),
+        Ok(None) => eof(),
+        Err(result) => result,
+    }))
+}
+
+pub(crate) fn read_bytes(call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError> {
+    let count = call.args().uint()?;
+    let mut bytes = vec![];
+    call.stdin().take(count).read_to_end(&mut bytes)?;
+    Ok(Some(if bytes.is_empty() && count > 0 {
+        eof()
+    } else {
+        success(Value::Array(bytes.into_iter().map(Value::Byte).collect()))
+    }))
+}
+
+pub(crate) fn read_all(call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError> {
+    let mut bytes = vec![];
+    call.stdin().read_to_end(&mut bytes)?;
+    Ok(Some(if bytes.is_empty() {
+        eof()
+    } else {
+        match String::from_utf8(bytes) {
+            Ok(s) => success(Value::Str(s)),
+            Err(e) => failure(e),
+        }
+    }))
+}
+
+pub(crate) fn read_int(call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError> {
+    parse_line::<i64>(call)
+}
+
+pub(crate) fn read_float(call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError> {
+    parse_line::<f64>(call)
+}
@@ -34,15 +34,34 @@ extern crate log;
 #[macro_use]
 extern crate anyhow;
 
+#[macro_use]
+extern crate serde_derive;
+
 use crate::core_traits::{ArithmeticsTrait, MemoryTrait, VirtualMachine};
 use jodin_common::core::function_names::{CALL, RECEIVE_MESSAGE};
 
 pub mod core_traits;
 pub use core_traits::*;
+pub mod debugger;
 pub mod error;
+pub mod exception;
 pub mod fault;
+pub mod fiber;
+pub mod files;
+pub mod frame_memory;
+pub mod generator;
+pub mod heap;
+pub mod input;
 pub mod kernel;
+pub mod limits;
 pub mod loadables;
 pub mod mvp;
+pub mod natives;
+pub mod observer;
+pub mod permissions;
+pub mod profiler;
+pub mod protocol;
+pub mod replay;
 pub mod scoped_memory;
+pub mod snapshot;
 pub mod vm;
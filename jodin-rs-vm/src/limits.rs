@@ -0,0 +1,43 @@
+//! Execution budgets that stop the vm before a guest program can hang or exhaust the host.
+//!
+//! Limits are set with [VMBuilder](crate::vm::VMBuilder). Exceeding the call depth raises a
+//! [stack overflow](crate::fault::Fault::StackOverflow) fault, while the other limits stop the vm
+//! with an error that can't be handled by guest code.
+
+use crate::vm::MAX_CALL_DEPTH;
+use std::time::Instant;
+
+/// The number of instructions executed between checks of the deadline
+pub const DEADLINE_CHECK_INTERVAL: u64 = 1 << 8;
+
+/// The limits placed on a vm. By default only the call depth is limited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionLimits {
+    /// The number of instructions the vm may still execute
+    pub fuel: Option<u64>,
+    /// The deepest the counter stack may get
+    pub max_call_depth: usize,
+    /// The longest the operand stack may get
+    pub max_stack_size: Option<usize>,
+    /// When the vm must stop running by. Only checked every [DEADLINE_CHECK_INTERVAL] instructions.
+    pub deadline: Option<Instant>,
+}
+
+impl ExecutionLimits {
+    /// Whether any limit has to be checked before every instruction. The call depth is checked
+    /// by calls instead.
+    pub(crate) fn checked_per_instruction(&self) -> bool {
+        self.fuel.is_some() || self.max_stack_size.is_some() || self.deadline.is_some()
+    }
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self {
+            fuel: None,
+            max_call_depth: MAX_CALL_DEPTH,
+            max_stack_size: None,
+            deadline: None,
+        }
+    }
+}
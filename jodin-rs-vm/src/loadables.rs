@@ -38,9 +38,9 @@ impl VMTryLoadable for RegularFile {
                     .unwrap()
                     .starts_with("static")
                 {
-                    vm.load_static(compilable);
+                    vm.try_load_static(compilable)?;
                 } else {
-                    vm.load(compilable);
+                    vm.try_load(compilable)?;
                 }
                 return Ok(());
             }
@@ -1,17 +1,46 @@
-use crate::{ArithmeticsTrait, MemoryTrait};
+use crate::error::ArithmeticError;
+use crate::{ArithmeticsTrait, MemoryTrait, ScopeDepth};
 use jodin_common::assembly::error::BytecodeError;
 use jodin_common::assembly::value::Value;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::hash::Hash;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
 use std::rc::Rc;
 
 /// Only has stack implementations and non-scoped variables
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct MinimumMemory {
     stack: Vec<Value>,
-    vars: HashMap<usize, Rc<RefCell<Value>>>,
+    #[serde(with = "crate::snapshot::variables")]
+    vars: HashMap<usize, Rc<RefCell<Value>>, BuildHasherDefault<VarHasher>>,
+}
+
+/// Hashes variable numbers with a multiplication, which is much faster than the default hasher
+/// for them. Only the speed of getting and setting variables suffers if variable numbers collide.
+#[derive(Default)]
+struct VarHasher(u64);
+
+impl Hasher for VarHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64((self.0 << 8) | byte as u64);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        let hash = n.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        // the low bits pick the bucket, so they have to depend on the high bits too
+        self.0 = hash ^ (hash >> 32);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
 }
 
 impl MemoryTrait for MinimumMemory {
@@ -23,12 +52,28 @@ impl MemoryTrait for MinimumMemory {
 
     fn push_scope(&mut self) {}
 
-    fn pop_scope(&mut self) {}
+    fn pop_scope(&mut self) -> Result<(), BytecodeError> {
+        Ok(())
+    }
 
-    fn back_scope(&mut self) {}
+    fn back_scope(&mut self) -> Result<(), BytecodeError> {
+        Ok(())
+    }
+
+    fn scope_depth(&self) -> ScopeDepth {
+        ScopeDepth::default()
+    }
+
+    fn unwind_scopes(&mut self, _depth: ScopeDepth) {}
 
     fn set_var(&mut self, var: usize, value: Value) {
-        self.vars.insert(var, Rc::new(RefCell::new(value)));
+        match self.vars.get(&var) {
+            // nothing else can see the old value, so it can be replaced in place
+            Some(cell) if Rc::strong_count(cell) == 1 => *cell.borrow_mut() = value,
+            _ => {
+                self.vars.insert(var, Rc::new(RefCell::new(value)));
+            }
+        }
     }
 
     fn get_var(&self, var: usize) -> Result<Rc<RefCell<Value>>, BytecodeError> {
@@ -38,6 +83,13 @@ impl MemoryTrait for MinimumMemory {
             .ok_or(BytecodeError::VariableNotSet(var))
     }
 
+    fn var_value(&self, var: usize) -> Result<Value, BytecodeError> {
+        self.vars
+            .get(&var)
+            .map(|cell| cell.borrow().clone())
+            .ok_or(BytecodeError::VariableNotSet(var))
+    }
+
     fn clear_var(&mut self, var: usize) -> Result<(), BytecodeError> {
         self.vars
             .remove(&var)
@@ -76,77 +128,190 @@ impl MemoryTrait for MinimumMemory {
     }
 }
 
+/// Operands after numeric promotion.
+///
+/// When two operands have different types, both are promoted to the wider of the two, where
+/// `Byte < UInteger < Integer < Float`. Unsigned values are reinterpreted when promoted to
+/// signed integers, so values greater than `i64::MAX` wrap.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Promoted {
+    Byte(u8, u8),
+    UInteger(u64, u64),
+    Integer(i64, i64),
+    Float(f64, f64),
+}
+
+impl Promoted {
+    fn new(a: Value, b: Value) -> Result<Self, ArithmeticError> {
+        let promoted = match (a, b) {
+            (Value::Byte(a), Value::Byte(b)) => Promoted::Byte(a, b),
+            (Value::Byte(a), Value::UInteger(b)) => Promoted::UInteger(a as u64, b),
+            (Value::UInteger(a), Value::Byte(b)) => Promoted::UInteger(a, b as u64),
+            (Value::UInteger(a), Value::UInteger(b)) => Promoted::UInteger(a, b),
+            (Value::Integer(a), Value::Integer(b)) => Promoted::Integer(a, b),
+            (Value::Integer(a), Value::Byte(b)) => Promoted::Integer(a, b as i64),
+            (Value::Byte(a), Value::Integer(b)) => Promoted::Integer(a as i64, b),
+            (Value::Integer(a), Value::UInteger(b)) => Promoted::Integer(a, b as i64),
+            (Value::UInteger(a), Value::Integer(b)) => Promoted::Integer(a as i64, b),
+            (Value::Float(a), b) => Promoted::Float(a, as_float(b)?),
+            (a, b) => Promoted::Float(as_float(a)?, as_float(b)?),
+        };
+        Ok(promoted)
+    }
+}
+
+fn as_float(value: Value) -> Result<f64, ArithmeticError> {
+    match value {
+        Value::Byte(b) => Ok(b as f64),
+        Value::UInteger(u) => Ok(u as f64),
+        Value::Integer(i) => Ok(i as f64),
+        Value::Float(f) => Ok(f),
+        v => Err(ArithmeticError::invalid_operand(v, "numeric")),
+    }
+}
+
+/// Gets the amount to shift by. Shift amounts must be non-negative integers.
+fn shift_amount(value: Value) -> Result<u32, ArithmeticError> {
+    let amount = match value {
+        Value::Byte(b) => b as u64,
+        Value::UInteger(u) => u,
+        Value::Integer(i) if i >= 0 => i as u64,
+        v => return Err(ArithmeticError::invalid_operand(v, "non-negative integer")),
+    };
+    Ok(u32::try_from(amount).unwrap_or(u32::MAX))
+}
+
 #[derive(Default)]
 pub struct MinimumALU;
 
 impl ArithmeticsTrait for MinimumALU {
-    fn add(&self, a: Value, b: Value) -> Value {
-        match (a, b) {
-            (Value::UInteger(a), Value::UInteger(b)) => Value::UInteger(u64::wrapping_add(a, b)),
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(i64::wrapping_add(a, b)),
-            (a, b) => panic!("Invalid operation operands (l: {}, b: {})", a, b),
-        }
+    fn add(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(a, b) => Value::Byte(u8::wrapping_add(a, b)),
+            Promoted::UInteger(a, b) => Value::UInteger(u64::wrapping_add(a, b)),
+            Promoted::Integer(a, b) => Value::Integer(i64::wrapping_add(a, b)),
+            Promoted::Float(a, b) => Value::Float(a + b),
+        })
     }
 
-    fn sub(&self, a: Value, b: Value) -> Value {
-        match (a, b) {
-            (Value::UInteger(a), Value::UInteger(b)) => Value::UInteger(u64::wrapping_sub(a, b)),
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(i64::wrapping_sub(a, b)),
-            (a, b) => panic!("Invalid operation operands (l: {}, b: {})", a, b),
-        }
+    fn sub(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(a, b) => Value::Byte(u8::wrapping_sub(a, b)),
+            Promoted::UInteger(a, b) => Value::UInteger(u64::wrapping_sub(a, b)),
+            Promoted::Integer(a, b) => Value::Integer(i64::wrapping_sub(a, b)),
+            Promoted::Float(a, b) => Value::Float(a - b),
+        })
     }
 
-    fn mult(&self, a: Value, b: Value) -> Value {
-        match (a, b) {
-            (Value::UInteger(a), Value::UInteger(b)) => Value::UInteger(u64::wrapping_mul(a, b)),
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(i64::wrapping_mul(a, b)),
-            (a, b) => panic!("Invalid operation operands (l: {}, b: {})", a, b),
-        }
+    fn mult(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(a, b) => Value::Byte(u8::wrapping_mul(a, b)),
+            Promoted::UInteger(a, b) => Value::UInteger(u64::wrapping_mul(a, b)),
+            Promoted::Integer(a, b) => Value::Integer(i64::wrapping_mul(a, b)),
+            Promoted::Float(a, b) => Value::Float(a * b),
+        })
     }
 
-    fn div(&self, _a: Value, _b: Value) -> Value {
-        todo!()
+    fn div(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(_, 0) | Promoted::UInteger(_, 0) | Promoted::Integer(_, 0) => {
+                return Err(ArithmeticError::DivisionByZero)
+            }
+            Promoted::Byte(a, b) => Value::Byte(a / b),
+            Promoted::UInteger(a, b) => Value::UInteger(a / b),
+            Promoted::Integer(a, b) => Value::Integer(i64::wrapping_div(a, b)),
+            Promoted::Float(a, b) => Value::Float(a / b),
+        })
     }
 
-    fn rem(&self, _a: Value, _b: Value) -> Value {
-        todo!()
+    fn rem(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(_, 0) | Promoted::UInteger(_, 0) | Promoted::Integer(_, 0) => {
+                return Err(ArithmeticError::DivisionByZero)
+            }
+            Promoted::Byte(a, b) => Value::Byte(a % b),
+            Promoted::UInteger(a, b) => Value::UInteger(a % b),
+            Promoted::Integer(a, b) => Value::Integer(i64::wrapping_rem(a, b)),
+            Promoted::Float(a, b) => Value::Float(a % b),
+        })
     }
 
-    fn and(&self, _a: Value, _b: Value) -> Value {
-        todo!()
+    fn and(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(a, b) => Value::Byte(a & b),
+            Promoted::UInteger(a, b) => Value::UInteger(a & b),
+            Promoted::Integer(a, b) => Value::Integer(a & b),
+            Promoted::Float(a, _) => {
+                return Err(ArithmeticError::invalid_operand(Value::Float(a), "integer"))
+            }
+        })
     }
 
-    fn or(&self, _a: Value, _b: Value) -> Value {
-        todo!()
+    fn or(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(a, b) => Value::Byte(a | b),
+            Promoted::UInteger(a, b) => Value::UInteger(a | b),
+            Promoted::Integer(a, b) => Value::Integer(a | b),
+            Promoted::Float(a, _) => {
+                return Err(ArithmeticError::invalid_operand(Value::Float(a), "integer"))
+            }
+        })
     }
 
-    fn not(&self, a: Value) -> Value {
+    fn not(&self, a: Value) -> Result<Value, ArithmeticError> {
         match a {
-            Value::Byte(b) => Value::Byte(if b != 0 { 0 } else { 1 }),
-            v => {
-                panic!("Invalid operation operand (v: {})", v)
-            }
+            Value::Byte(b) => Ok(Value::Byte(if b != 0 { 0 } else { 1 })),
+            Value::UInteger(u) => Ok(Value::UInteger(!u)),
+            Value::Integer(i) => Ok(Value::Integer(!i)),
+            v => Err(ArithmeticError::invalid_operand(v, "integer")),
         }
     }
 
-    fn xor(&self, _a: Value, _b: Value) -> Value {
-        todo!()
+    fn xor(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(a, b) => Value::Byte(a ^ b),
+            Promoted::UInteger(a, b) => Value::UInteger(a ^ b),
+            Promoted::Integer(a, b) => Value::Integer(a ^ b),
+            Promoted::Float(a, _) => {
+                return Err(ArithmeticError::invalid_operand(Value::Float(a), "integer"))
+            }
+        })
+    }
+
+    fn shift_left(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        let amount = shift_amount(b)?;
+        match a {
+            Value::Byte(a) => Ok(Value::Byte(a.checked_shl(amount).unwrap_or(0))),
+            Value::UInteger(a) => Ok(Value::UInteger(a.checked_shl(amount).unwrap_or(0))),
+            Value::Integer(a) => Ok(Value::Integer(a.checked_shl(amount).unwrap_or(0))),
+            v => Err(ArithmeticError::invalid_operand(v, "integer")),
+        }
     }
 
-    fn shift_left(&self, _a: Value, _b: Value) -> Value {
-        todo!()
+    fn shift_right(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        let amount = shift_amount(b)?;
+        match a {
+            Value::Byte(a) => Ok(Value::Byte(a.checked_shr(amount).unwrap_or(0))),
+            Value::UInteger(a) => Ok(Value::UInteger(a.checked_shr(amount).unwrap_or(0))),
+            Value::Integer(a) => Ok(Value::Integer(a.checked_shr(amount).unwrap_or(if a < 0 {
+                -1
+            } else {
+                0
+            }))),
+            v => Err(ArithmeticError::invalid_operand(v, "integer")),
+        }
     }
 
-    fn shift_right(&self, _a: Value, _b: Value) -> Value {
-        todo!()
+    fn greater_than(&self, a: Value, b: Value) -> Result<Value, ArithmeticError> {
+        Ok(match Promoted::new(a, b)? {
+            Promoted::Byte(a, b) => Value::from(a > b),
+            Promoted::UInteger(a, b) => Value::from(a > b),
+            Promoted::Integer(a, b) => Value::from(a > b),
+            Promoted::Float(a, b) => Value::from(a > b),
+        })
     }
 
-    fn greater_than(&self, a: Value, b: Value) -> Value {
-        match (a, b) {
-            (Value::UInteger(a), Value::UInteger(b)) => Value::from(a > b),
-            (Value::Integer(a), Value::Integer(b)) => Value::from(a > b),
-            (Value::Float(a), Value::Float(b)) => Value::from(a > b),
-            (a, b) => panic!("Invalid operation operands (l: {}, b: {})", a, b),
-        }
+    fn wrapping_integers(&self) -> bool {
+        true
     }
 }
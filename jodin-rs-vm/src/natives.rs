@@ -0,0 +1,242 @@
+//! The natives a vm can call, and registering more of them.
+//!
+//! Natives are called with [NativeMethod](jodin_common::assembly::instructions::Asm::NativeMethod)
+//! or by sending a message to [Value::Native]. Every vm has a registry of them, which starts with
+//! the built-in natives like `print`, `write`, `invoke`, `ref`, the [input](crate::input) natives
+//! and the `@` natives the compiler emits. Embedders add their own with [VMBuilder::native](crate::vm::VMBuilder::native) or
+//! [VM::register_native](crate::vm::VM::register_native), without writing a plugin:
+//!
+//! ```
+//! # use jodin_common::assembly::value::Value;
+//! # use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+//! # use jodin_rs_vm::natives::{Arity, NativeCall};
+//! # use jodin_rs_vm::vm::VMBuilder;
+//! let vm = VMBuilder::new()
+//!     .memory(MinimumMemory::default())
+//!     .alu(MinimumALU)
+//!     .native("shout", Arity::Exactly(1), |call: &mut NativeCall| {
+//!         let s = call.args().str()?;
+//!         writeln!(call.stdout(), "{}!", s.to_uppercase())?;
+//!         Ok(Some(Value::Empty))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//! A native is called with a [NativeCall], which has a typed view of its arguments and the
+//! stdin, stdout and stderr of the vm. It answers the value it pushes, if any. Natives called like
+//! functions should always answer a value, using [Value::Empty] when there's nothing to return.
+//! Calling a native that isn't registered fails with
+//! [InvalidNative](crate::error::VMError::InvalidNative), and calling one with a number of
+//! arguments its [Arity] doesn't accept fails with
+//! [InvalidNativeArguments](crate::error::VMError::InvalidNativeArguments).
+//!
+//! Natives that reach outside of the vm can need a [capability](crate::permissions::Capability),
+//! so a vm whose permissions don't allow it can't call them. They're registered with
+//! [VMBuilder::native_in](crate::vm::VMBuilder::native_in) or
+//! [VM::register_native_in](crate::vm::VM::register_native_in).
+
+use crate::error::{ErrorLocation, VMError};
+use jodin_common::assembly::value::Value;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Stderr, StdinLock, Stdout, Write};
+
+/// The number of arguments a native accepts
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// Whether a native with this arity can be called with a number of arguments
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exactly(arity) => count == arity,
+            Arity::AtLeast(arity) => count >= arity,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exactly(arity) => write!(f, "{arity}"),
+            Arity::AtLeast(arity) => write!(f, "at least {arity}"),
+        }
+    }
+}
+
+/// A native that can be registered with a vm
+pub trait NativeFunction {
+    /// Runs the native, answering the value it pushes, if any
+    fn call(&self, call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError>;
+}
+
+impl<F> NativeFunction for F
+where
+    F: Fn(&mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError>,
+{
+    fn call(&self, call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError> {
+        self(call)
+    }
+}
+
+/// The arguments of a native, taken in the order they were given
+pub struct NativeArgs<'a> {
+    native: &'a str,
+    args: std::vec::IntoIter<Value>,
+    locate: &'a dyn Fn() -> ErrorLocation,
+}
+
+impl<'a> NativeArgs<'a> {
+    /// The number of arguments left
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Whether every argument has been taken
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes the next argument
+    pub fn value(&mut self) -> Result<Value, VMError> {
+        match self.args.next() {
+            Some(value) => Ok(value),
+            None => Err(self.invalid("missing argument")),
+        }
+    }
+
+    /// Takes the next argument as a string
+    pub fn str(&mut self) -> Result<String, VMError> {
+        match self.value()? {
+            Value::Str(s) => Ok(s),
+            v => Err(self.type_mismatch(v, "Str")),
+        }
+    }
+
+    /// Takes the next argument as an unsigned integer, which can also be a byte
+    pub fn uint(&mut self) -> Result<u64, VMError> {
+        match self.value()? {
+            Value::Byte(b) => Ok(b as u64),
+            Value::UInteger(u) => Ok(u),
+            v => Err(self.type_mismatch(v, "UInteger")),
+        }
+    }
+
+    /// Takes the next argument as an integer, which can also be a byte or an unsigned integer that
+    /// fits
+    pub fn int(&mut self) -> Result<i64, VMError> {
+        match self.value()? {
+            Value::Byte(b) => Ok(b as i64),
+            Value::Integer(i) => Ok(i),
+            Value::UInteger(u) if u <= i64::MAX as u64 => Ok(u as i64),
+            v => Err(self.type_mismatch(v, "Integer")),
+        }
+    }
+
+    /// Takes the next argument as a float, which can also be any integer
+    pub fn float(&mut self) -> Result<f64, VMError> {
+        match self.value()? {
+            Value::Byte(b) => Ok(b as f64),
+            Value::Integer(i) => Ok(i as f64),
+            Value::UInteger(u) => Ok(u as f64),
+            Value::Float(f) => Ok(f),
+            v => Err(self.type_mismatch(v, "Float")),
+        }
+    }
+
+    /// Takes every argument that's left
+    pub fn rest(&mut self) -> Vec<Value> {
+        self.args.by_ref().collect()
+    }
+
+    /// An error for when the arguments of the native are invalid
+    pub fn invalid(&self, reason: impl AsRef<str>) -> VMError {
+        VMError::InvalidNativeArguments {
+            native: self.native.to_string(),
+            reason: reason.as_ref().to_string(),
+            location: (self.locate)(),
+        }
+    }
+
+    /// An error for when an argument of the native isn't of the expected type
+    pub fn type_mismatch(&self, value: Value, expected: impl AsRef<str>) -> VMError {
+        VMError::TypeMismatch {
+            value,
+            expected: expected.as_ref().to_string(),
+            location: (self.locate)(),
+        }
+    }
+}
+
+/// A call to a registered native
+pub struct NativeCall<'a, 'l> {
+    args: NativeArgs<'a>,
+    stdin: &'a mut Option<Box<dyn BufRead + 'l>>,
+    stdout: &'a mut Option<Box<dyn Write + 'l>>,
+    stderr: &'a mut Option<Box<dyn Write + 'l>>,
+    /// What's used when the vm wasn't given its own stdin, stdout or stderr
+    process: (Option<StdinLock<'static>>, Stdout, Stderr),
+}
+
+impl<'a, 'l> NativeCall<'a, 'l> {
+    pub(crate) fn new(
+        native: &'a str,
+        args: Vec<Value>,
+        locate: &'a dyn Fn() -> ErrorLocation,
+        stdin: &'a mut Option<Box<dyn BufRead + 'l>>,
+        stdout: &'a mut Option<Box<dyn Write + 'l>>,
+        stderr: &'a mut Option<Box<dyn Write + 'l>>,
+    ) -> Self {
+        Self {
+            args: NativeArgs {
+                native,
+                args: args.into_iter(),
+                locate,
+            },
+            stdin,
+            stdout,
+            stderr,
+            process: (None, std::io::stdout(), std::io::stderr()),
+        }
+    }
+
+    /// The name the native was called by
+    pub fn name(&self) -> &str {
+        self.args.native
+    }
+
+    /// The arguments of the call
+    pub fn args(&mut self) -> &mut NativeArgs<'a> {
+        &mut self.args
+    }
+
+    /// The stdin of the vm, which is the stdin of the process unless the vm was given one
+    pub fn stdin(&mut self) -> &mut dyn BufRead {
+        match self.stdin {
+            Some(stdin) => stdin.as_mut(),
+            None => self
+                .process
+                .0
+                .get_or_insert_with(|| std::io::stdin().lock()),
+        }
+    }
+
+    /// The stdout of the vm, which is the stdout of the process unless the vm was given one
+    pub fn stdout(&mut self) -> &mut dyn Write {
+        match self.stdout {
+            Some(stdout) => stdout.as_mut(),
+            None => &mut self.process.1,
+        }
+    }
+
+    /// The stderr of the vm, which is the stderr of the process unless the vm was given one
+    pub fn stderr(&mut self) -> &mut dyn Write {
+        match self.stderr {
+            Some(stderr) => stderr.as_mut(),
+            None => &mut self.process.2,
+        }
+    }
+}
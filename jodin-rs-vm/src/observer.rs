@@ -0,0 +1,68 @@
+//! Observers are notified of events within the vm as it runs, allowing tools such as profilers,
+//! coverage tools and tracers to be built outside of the vm.
+//!
+//! Observers are registered with [VMBuilder::observer](crate::vm::VMBuilder::observer). Every
+//! callback has an empty default implementation, so an observer only needs to implement the
+//! events it cares about.
+
+use crate::error::ErrorLocation;
+use crate::fault::{Fault, FaultAction};
+use jodin_common::assembly::instructions::Asm;
+use jodin_common::assembly::value::Value;
+
+/// Receives callbacks for events that occur while the vm runs
+pub trait VMObserver {
+    /// Called before the instruction at `pc` is executed
+    fn on_instruction(&mut self, _pc: usize, _instruction: &Asm) {}
+
+    /// Called when a new frame is pushed for a function starting at `pc`, including the frame the
+    /// vm starts running in. `function` is the most recent public label at the start of the
+    /// function, and `depth` is the length of the counter stack after the frame was pushed.
+    fn on_call(&mut self, _pc: usize, _function: Option<&str>, _depth: usize) {}
+
+    /// Called when a frame is popped, either by returning or by an exception unwinding through it.
+    /// `depth` is the length of the counter stack after the frame was popped.
+    fn on_return(&mut self, _depth: usize) {}
+
+    /// Called when a [SendMessage](Asm::SendMessage) instruction dispatches a message
+    fn on_send_message(&mut self, _target: &Value, _message: &str, _args: &[Value]) {}
+
+    /// Called when a native method is invoked, with the arguments in the order they are consumed
+    fn on_native(&mut self, _native: &str, _args: &[Value]) {}
+
+    /// Called when the vm enters a fault handler
+    fn on_fault(&mut self, _fault: &Fault, _location: &ErrorLocation) {}
+
+    /// Called when a fault handler finishes, with the action it returned
+    fn on_fault_end(&mut self, _fault: &Fault, _action: FaultAction) {}
+}
+
+impl<O: VMObserver + ?Sized> VMObserver for &mut O {
+    fn on_instruction(&mut self, pc: usize, instruction: &Asm) {
+        (**self).on_instruction(pc, instruction)
+    }
+
+    fn on_call(&mut self, pc: usize, function: Option<&str>, depth: usize) {
+        (**self).on_call(pc, function, depth)
+    }
+
+    fn on_return(&mut self, depth: usize) {
+        (**self).on_return(depth)
+    }
+
+    fn on_send_message(&mut self, target: &Value, message: &str, args: &[Value]) {
+        (**self).on_send_message(target, message, args)
+    }
+
+    fn on_native(&mut self, native: &str, args: &[Value]) {
+        (**self).on_native(native, args)
+    }
+
+    fn on_fault(&mut self, fault: &Fault, location: &ErrorLocation) {
+        (**self).on_fault(fault, location)
+    }
+
+    fn on_fault_end(&mut self, fault: &Fault, action: FaultAction) {
+        (**self).on_fault_end(fault, action)
+    }
+}
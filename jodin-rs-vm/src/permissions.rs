@@ -0,0 +1,146 @@
+//! Which groups of natives a vm may call, so code that isn't trusted can be run.
+//!
+//! Natives that reach outside of the vm, or into the scopes of other code, belong to a
+//! [Capability]. A vm is given the capabilities it has with
+//! [VMBuilder::permissions](crate::vm::VMBuilder::permissions), and by default has all of them.
+//! Calling a native whose capability the vm doesn't have raises a
+//! [permission denied](crate::fault::Fault::PermissionDenied) fault instead of running it, which
+//! stops the vm unless a handler was registered for it.
+//!
+//! The built-in natives are grouped as
+//! - [Console](Capability::Console): `print`, `@print_stack`, and `write` to stdout or stderr
+//! - [Stdin](Capability::Stdin): the [input](crate::input) natives
+//! - [Filesystem](Capability::Filesystem): the [file](crate::files) natives, including `write` to
+//!   an open file
+//! - [Plugins](Capability::Plugins): `dynamic_call`, and calling any function a plugin provides
+//! - [Scopes](Capability::Scopes): `@load_scope`, `@save_scope`, `@global_scope` and
+//!   `@back_scope`. A function can still load the scope saved under its own label and back out of
+//!   that load without it, which is how every compiled function enters and leaves its scope.
+//!
+//! None of the built-in natives read the clock, make random numbers or read the environment of the
+//! process. Natives that do are put in those groups when they're registered with
+//! [VMBuilder::native_in](crate::vm::VMBuilder::native_in) or
+//! [VM::register_native_in](crate::vm::VM::register_native_in). The rest of the natives, like
+//! `invoke` or the `@` natives compiled functions need, can always be called.
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+/// A group of natives that a vm may be allowed to call
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Capability {
+    /// Writing to stdout and stderr
+    Console,
+    /// Reading from stdin
+    Stdin,
+    /// Using the files under the file root
+    Filesystem,
+    /// Reading the time
+    Clock,
+    /// Making random numbers
+    Random,
+    /// Reading the environment of the process
+    Environment,
+    /// Calling functions provided by plugins
+    Plugins,
+    /// Loading, saving and moving between scopes that aren't the current function's
+    Scopes,
+}
+
+impl Capability {
+    /// Every capability
+    pub const ALL: [Capability; 8] = [
+        Capability::Console,
+        Capability::Stdin,
+        Capability::Filesystem,
+        Capability::Clock,
+        Capability::Random,
+        Capability::Environment,
+        Capability::Plugins,
+        Capability::Scopes,
+    ];
+
+    /// The name of the capability, which is given to fault handlers
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Console => "console",
+            Capability::Stdin => "stdin",
+            Capability::Filesystem => "filesystem",
+            Capability::Clock => "clock",
+            Capability::Random => "random",
+            Capability::Environment => "environment",
+            Capability::Plugins => "plugins",
+            Capability::Scopes => "scopes",
+        }
+    }
+
+    /// The capability needed to call a built-in native, if any. `write` needs one that depends on
+    /// where it writes, so it isn't in a group.
+    pub fn of_builtin(native: &str) -> Option<Capability> {
+        match native {
+            "print" | "@print_stack" => Some(Capability::Console),
+            "read_line" | "read_bytes" | "read_all" | "read_int" | "read_float" => {
+                Some(Capability::Stdin)
+            }
+            "open" | "read" | "seek" | "close" | "stat" | "list_dir" | "remove" => {
+                Some(Capability::Filesystem)
+            }
+            "dynamic_call" => Some(Capability::Plugins),
+            "@load_scope" | "@save_scope" | "@global_scope" | "@back_scope" => {
+                Some(Capability::Scopes)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The capabilities a vm has. Has all of them by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    allowed: HashSet<Capability>,
+}
+
+impl Permissions {
+    /// Permissions with every capability
+    pub fn all() -> Self {
+        Self {
+            allowed: Capability::ALL.into_iter().collect(),
+        }
+    }
+
+    /// Permissions with no capabilities, for code that should only compute
+    pub fn none() -> Self {
+        Self {
+            allowed: HashSet::new(),
+        }
+    }
+
+    /// Adds a capability
+    pub fn allow(mut self, capability: Capability) -> Self {
+        self.allowed.insert(capability);
+        self
+    }
+
+    /// Removes a capability
+    pub fn deny(mut self, capability: Capability) -> Self {
+        self.allowed.remove(&capability);
+        self
+    }
+
+    /// Whether a capability was allowed
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.allowed.contains(&capability)
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::all()
+    }
+}
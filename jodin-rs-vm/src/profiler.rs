@@ -0,0 +1,286 @@
+//! A counting profiler, built as a [VMObserver].
+//!
+//! The profiler counts executed instructions and measures wall time, attributing both to the call
+//! path that was active when they occurred. Call paths are made of the public labels of the
+//! functions on the counter stack. Results are available as a text report with per function
+//! inclusive and exclusive totals, or as [folded stacks](https://github.com/brendangregg/FlameGraph)
+//! for flamegraph tools.
+//!
+//! ```no_run
+//! # use jodin_rs_vm::profiler::{Profiler, ProfileWeight};
+//! # use jodin_rs_vm::vm::VMBuilder;
+//! # use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+//! # use jodin_rs_vm::core_traits::VirtualMachine;
+//! let mut profiler = Profiler::new();
+//! {
+//!     let mut vm = VMBuilder::new()
+//!         .memory(MinimumMemory::default())
+//!         .alu(MinimumALU)
+//!         .observer(&mut profiler)
+//!         .build()
+//!         .unwrap();
+//!     vm.run("main").unwrap();
+//! }
+//! println!("{}", profiler.report());
+//! println!("{}", profiler.folded_stacks(ProfileWeight::Instructions));
+//! ```
+
+use crate::error::ErrorLocation;
+use crate::fault::{Fault, FaultAction};
+use crate::observer::VMObserver;
+use jodin_common::assembly::instructions::Asm;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// A node in the call tree. Each node is a unique call path.
+#[derive(Debug)]
+struct CallNode {
+    name: String,
+    parent: Option<usize>,
+    children: HashMap<String, usize>,
+    calls: u64,
+    instructions: u64,
+    time: Duration,
+}
+
+impl CallNode {
+    fn new(name: String, parent: Option<usize>) -> Self {
+        Self {
+            name,
+            parent,
+            children: HashMap::new(),
+            calls: 0,
+            instructions: 0,
+            time: Duration::ZERO,
+        }
+    }
+}
+
+/// A function on the profiler's view of the call stack
+#[derive(Debug, Copy, Clone)]
+struct Frame {
+    node: usize,
+    /// The length of the counter stack when this frame was pushed. Frames pushed while entering a
+    /// fault handler use 0 so they are never popped by the handler.
+    depth: usize,
+}
+
+/// The totals of a single function across every call path it appears in
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    /// The number of times the function was called
+    pub calls: u64,
+    /// Instructions executed within the function itself
+    pub exclusive_instructions: u64,
+    /// Instructions executed within the function or anything it called
+    pub inclusive_instructions: u64,
+    pub exclusive_time: Duration,
+    pub inclusive_time: Duration,
+}
+
+/// What the counts of folded stacks measure
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProfileWeight {
+    /// The number of executed instructions
+    Instructions,
+    /// Wall time in microseconds
+    Time,
+}
+
+/// A counting profiler. Register it on a vm with [VMBuilder::observer](crate::vm::VMBuilder::observer).
+#[derive(Debug)]
+pub struct Profiler {
+    nodes: Vec<CallNode>,
+    frames: Vec<Frame>,
+    saved_frames: Vec<Vec<Frame>>,
+    last_instruction: Option<Instant>,
+}
+
+impl Profiler {
+    /// The name of the call path that is active when no function is on the stack
+    const ROOT: &'static str = "<root>";
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![CallNode::new(Self::ROOT.to_string(), None)],
+            frames: vec![],
+            saved_frames: vec![],
+            last_instruction: None,
+        }
+    }
+
+    fn current_node(&self) -> usize {
+        self.frames.last().map(|f| f.node).unwrap_or(0)
+    }
+
+    /// Charges the time since the previous instruction to the current call path
+    fn charge_time(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_instruction.replace(now) {
+            let node = self.current_node();
+            self.nodes[node].time += now - last;
+        }
+    }
+
+    /// Gets the child of a node with a name, creating it if it doesn't exist
+    fn child(&mut self, parent: usize, name: &str) -> usize {
+        if let Some(&child) = self.nodes[parent].children.get(name) {
+            return child;
+        }
+        let child = self.nodes.len();
+        self.nodes
+            .push(CallNode::new(name.to_string(), Some(parent)));
+        self.nodes[parent].children.insert(name.to_string(), child);
+        child
+    }
+
+    fn push_frame(&mut self, name: &str, depth: usize) -> usize {
+        let node = self.child(self.current_node(), name);
+        self.frames.push(Frame { node, depth });
+        node
+    }
+
+    /// The names along the call path of a node, starting from the outermost function
+    fn path(&self, mut node: usize) -> Vec<&str> {
+        let mut path = vec![];
+        while let Some(parent) = self.nodes[node].parent {
+            path.push(self.nodes[node].name.as_str());
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// The total number of instructions executed while profiling
+    pub fn total_instructions(&self) -> u64 {
+        self.nodes.iter().map(|n| n.instructions).sum()
+    }
+
+    /// Gets the totals of every function, sorted by inclusive instructions from most to least.
+    ///
+    /// Recursive calls only count towards a function's inclusive totals once.
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut functions: HashMap<&str, FunctionProfile> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            let entry = functions
+                .entry(node.name.as_str())
+                .or_insert_with(|| FunctionProfile {
+                    name: node.name.clone(),
+                    ..Default::default()
+                });
+            entry.calls += node.calls;
+            entry.exclusive_instructions += node.instructions;
+            entry.exclusive_time += node.time;
+
+            let mut path = self.path(index);
+            path.sort_unstable();
+            path.dedup();
+            for name in path {
+                let entry = functions
+                    .get_mut(name)
+                    .expect("path contains unknown function");
+                entry.inclusive_instructions += node.instructions;
+                entry.inclusive_time += node.time;
+            }
+        }
+        let mut functions = functions.into_values().collect::<Vec<_>>();
+        functions.sort_by(|a, b| {
+            b.inclusive_instructions
+                .cmp(&a.inclusive_instructions)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        functions
+    }
+
+    /// Creates a text report of the totals of every function
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        writeln!(
+            report,
+            "{:<32} {:>10} {:>14} {:>14} {:>12} {:>12}",
+            "function", "calls", "excl instrs", "incl instrs", "excl ms", "incl ms"
+        )
+        .unwrap();
+        for function in self.functions() {
+            writeln!(
+                report,
+                "{:<32} {:>10} {:>14} {:>14} {:>12.3} {:>12.3}",
+                function.name,
+                function.calls,
+                function.exclusive_instructions,
+                function.inclusive_instructions,
+                function.exclusive_time.as_secs_f64() * 1000.0,
+                function.inclusive_time.as_secs_f64() * 1000.0
+            )
+            .unwrap();
+        }
+        writeln!(report, "total instructions: {}", self.total_instructions()).unwrap();
+        report
+    }
+
+    /// Creates folded stacks, one line per call path in the form `main;fib;fib 1234`. Call paths
+    /// with a weight of 0 are left out. Lines are sorted by call path.
+    pub fn folded_stacks(&self, weight: ProfileWeight) -> String {
+        let mut lines = vec![];
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            let count = match weight {
+                ProfileWeight::Instructions => node.instructions as u128,
+                ProfileWeight::Time => node.time.as_micros(),
+            };
+            if count > 0 {
+                lines.push(format!("{} {}", self.path(index).join(";"), count));
+            }
+        }
+        lines.sort();
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VMObserver for Profiler {
+    fn on_instruction(&mut self, _pc: usize, _instruction: &Asm) {
+        self.charge_time();
+        let node = self.current_node();
+        self.nodes[node].instructions += 1;
+    }
+
+    fn on_call(&mut self, _pc: usize, function: Option<&str>, depth: usize) {
+        self.charge_time();
+        let node = self.push_frame(function.unwrap_or("<none>"), depth);
+        self.nodes[node].calls += 1;
+    }
+
+    fn on_return(&mut self, depth: usize) {
+        self.charge_time();
+        while self.frames.last().map_or(false, |f| f.depth > depth) {
+            self.frames.pop();
+        }
+    }
+
+    fn on_fault(&mut self, fault: &Fault, _location: &ErrorLocation) {
+        self.charge_time();
+        let mut handler_frames = self.frames.clone();
+        for frame in &mut handler_frames {
+            frame.depth = 0;
+        }
+        self.saved_frames
+            .push(std::mem::replace(&mut self.frames, handler_frames));
+        // handlers run with a counter stack of [0, handler]
+        let node = self.push_frame(&format!("[fault {}]", fault.name()), 2);
+        self.nodes[node].calls += 1;
+    }
+
+    fn on_fault_end(&mut self, _fault: &Fault, _action: FaultAction) {
+        self.charge_time();
+        if let Some(frames) = self.saved_frames.pop() {
+            self.frames = frames;
+        }
+    }
+}
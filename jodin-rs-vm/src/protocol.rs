@@ -0,0 +1,50 @@
+//! The messages every built-in value answers.
+//!
+//! Values that aren't functions or objects with a [receive message](jodin_common::core::function_names::RECEIVE_MESSAGE)
+//! attribute answer a standard set of messages natively. In jodin code they're sent with method
+//! call syntax, like `name.to_upper()`, and the arguments are given in order. Every message pushes
+//! its result, or void when there's nothing to return. Indexes count from 0, and the indexes of
+//! strings count characters, not bytes.
+//!
+//! # Strings
+//! - `length()`: the number of characters
+//! - `index(i)`: the character at `i`, as a string
+//! - `slice(start, end)`: the characters from `start` up to `end`
+//! - `concat(other)`: the string followed by another
+//! - `find(pattern)`: the index of the first occurrence of a pattern, or -1
+//! - `split(separator)`: an array of the strings between the occurrences of a separator
+//! - `to_upper()`: the string in upper case
+//!
+//! # Arrays
+//! - `push(value)`: adds a value to the end
+//! - `pop()`: removes the last value and pushes it
+//! - `insert(i, value)`: inserts a value at `i`, moving the values after it along
+//! - `remove(i)`: removes the value at `i` and pushes it
+//! - `len()`: the number of values
+//! - `iterate()`: an iterator over the values the array has when it's sent, which answers
+//!   `has_next` and `next` like a [generator](crate::generator), so it can be used by `foreach`
+//!
+//! # Dictionaries
+//! Dictionaries also answer `get(name)` and `put(name, value)`.
+//! - `keys()`: an array of the names of the entries, in order
+//! - `values()`: an array of the values of the entries, in the order of their names
+//! - `contains(name)`: whether there's an entry with a name
+//! - `remove(name)`: removes the entry with a name and pushes its value
+//! - `len()`: the number of entries
+//!
+//! # Numbers
+//! Bytes, integers, unsigned integers and floats answer
+//! - `to_string()`: the number as a string, as `print` would write it
+//! - `eq(other)`, `ne(other)`, `lt(other)`, `le(other)`, `gt(other)`, `ge(other)`: compare the
+//!   number to another, with the same promotions as arithmetic
+//! - `compare(other)`: -1, 0 or 1 as the number is less than, equal to or greater than another
+//!
+//! Messages that change their receiver, like `push` and `remove`, only last when they're sent to a
+//! value behind a reference. Sending a value a message it doesn't answer fails with
+//! [InvalidMessage](crate::error::VMError::InvalidMessage).
+
+/// The attribute of an array iterator that holds the values it iterates over
+pub const ITERATED_ARRAY: &str = "@iterated";
+
+/// The attribute of an array iterator that holds the index of its next value
+pub const ITERATOR_INDEX: &str = "@index";
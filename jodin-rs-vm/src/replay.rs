@@ -0,0 +1,111 @@
+//! Recording and replaying the calls a vm makes that have effects outside of it.
+//!
+//! A vm built with [VMBuilder::record](crate::vm::VMBuilder::record) logs every call to one of the
+//! [OUTSIDE_NATIVES] and every call to a plugin function in a [Trace], along with the arguments it
+//! was called with and the value it pushed. A vm built with
+//! [VMBuilder::replay](crate::vm::VMBuilder::replay) runs from a trace instead. Those calls are
+//! checked against the trace and answered with the values it recorded, without writing anything
+//! or calling any plugins, so a replayed run is the same as the recorded one however often it's
+//! replayed, including under the [debugger](crate::debugger). A call that doesn't match the trace
+//! stops the vm with [ReplayDiverged](crate::error::VMError::ReplayDiverged).
+//!
+//! Natives called by a plugin function are part of its call, so they aren't recorded on their own.
+
+use crate::error::VMError;
+use jodin_common::assembly::value::Value;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+
+/// The natives that have effects outside of the vm
+pub const OUTSIDE_NATIVES: &[&str] = &[
+    "print",
+    "write",
+    "read_line",
+    "read_bytes",
+    "read_all",
+    "read_int",
+    "read_float",
+    "open",
+    "read",
+    "seek",
+    "close",
+    "stat",
+    "list_dir",
+    "remove",
+];
+
+/// Whether a native has effects outside of the vm, so it's recorded and replayed
+pub fn has_outside_effects(native: &str) -> bool {
+    OUTSIDE_NATIVES.contains(&native)
+}
+
+/// What was called
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TraceCall {
+    Native(String),
+    Plugin(String),
+}
+
+impl Display for TraceCall {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceCall::Native(native) => write!(f, "native {native:?}"),
+            TraceCall::Plugin(function) => write!(f, "plugin function {function:?}"),
+        }
+    }
+}
+
+/// A recorded call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    pub call: TraceCall,
+    /// The arguments of a native, or the values a plugin function popped with the top of the
+    /// stack last
+    pub args: Vec<Value>,
+    /// The value the call pushed, if any
+    pub result: Option<Value>,
+}
+
+impl Display for TraceEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} with args {:?}", self.call, self.args)
+    }
+}
+
+/// The calls with outside effects made by a run, in the order they were made
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    /// Writes the trace, so it can be read back by [load](Trace::load)
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), VMError> {
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+
+    /// Reads a trace written by [save](Trace::save)
+    pub fn load<R: Read>(reader: R) -> Result<Self, VMError> {
+        Ok(bincode::deserialize_from(reader)?)
+    }
+}
+
+/// Whether a vm records or replays its calls with outside effects
+#[derive(Debug)]
+pub(crate) enum Tracer {
+    Recording(Trace),
+    Replaying {
+        trace: Trace,
+        /// The index of the next event to replay
+        next: usize,
+    },
+}
+
+impl Tracer {
+    pub fn trace(&self) -> &Trace {
+        match self {
+            Tracer::Recording(trace) | Tracer::Replaying { trace, .. } => trace,
+        }
+    }
+}
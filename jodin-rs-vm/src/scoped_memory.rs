@@ -1,6 +1,6 @@
 //! The scoped memory module is the improved memory abstraction for the VM
 
-use crate::MemoryTrait;
+use crate::{MemoryTrait, SavedScopes, ScopeDepth};
 use jodin_common::assembly::error::BytecodeError;
 use jodin_common::assembly::value::Value;
 use std::cell::RefCell;
@@ -21,9 +21,10 @@ mod helper_structs {
     use std::ops::Add;
     use std::rc::Rc;
 
-    #[derive(Debug)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub(super) struct MemNode {
         id: usize,
+        #[serde(with = "crate::snapshot::variables")]
         num_to_value: HashMap<usize, Rc<RefCell<Value>>>,
     }
 
@@ -59,7 +60,7 @@ mod helper_structs {
         }
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub(super) struct VarIdPool {
         next_id: Option<usize>,
         reclaimed: VecDeque<usize>,
@@ -110,7 +111,7 @@ mod helper_structs {
 }
 use helper_structs::*;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct VMMemory {
     mem_nodes: HashMap<usize, MemNode>,
     global_scope_id: usize,
@@ -250,7 +251,7 @@ impl MemoryTrait for VMMemory {
             self.push_scope();
             self.save_current_scope(identifier);
             self.pop_scope_no_reclaim();
-            self.back_scope();
+            self.back_scope().expect("global scope was just loaded");
             id = self.hash_to_id.get(&hashed).cloned();
             assert!(id.is_some(), "No scope saved to hash value {}", hashed);
         } else {
@@ -281,23 +282,60 @@ impl MemoryTrait for VMMemory {
         trace!("Scope stack: {:?}", self.mem_node_stack);
     }
 
-    fn pop_scope(&mut self) {
-        let popped_id = self.last_stack().pop().expect("No mem nodes in stack");
+    fn pop_scope(&mut self) -> Result<(), BytecodeError> {
+        if self.mem_node_stack.len() == 1 && self.last_stack_len() == 1 {
+            return Err(BytecodeError::NoScopeToLeave);
+        }
+        let popped_id = self
+            .last_stack()
+            .pop()
+            .ok_or(BytecodeError::NoScopeToLeave)?;
         if !self.is_referenced(popped_id) {
             self.remove_node(popped_id);
         }
         trace!("Popped scope (id = {popped_id})");
         trace!("Scope stack: {:?}", self.mem_node_stack);
+        Ok(())
     }
 
-    fn back_scope(&mut self) {
-        let _ = 0i32;
+    fn back_scope(&mut self) -> Result<(), BytecodeError> {
+        if self.mem_node_stack.len() == 1 {
+            return Err(BytecodeError::NoScopeToLeave);
+        }
         while self.last_stack_len() > 0 {
-            self.pop_scope();
+            self.pop_scope()?;
         }
         self.mem_node_stack.pop();
         trace!("Went back a scope (id = {})", self.current_node_id());
         trace!("Scope stack: {:?}", self.mem_node_stack);
+        Ok(())
+    }
+
+    fn scope_depth(&self) -> ScopeDepth {
+        ScopeDepth {
+            loads: self.mem_node_stack.len(),
+            scopes: self.mem_node_stack.last().map(|s| s.len()).unwrap_or(0),
+        }
+    }
+
+    fn unwind_scopes(&mut self, depth: ScopeDepth) {
+        while self.mem_node_stack.len() > depth.loads && self.back_scope().is_ok() {}
+        while self.last_stack_len() > depth.scopes && self.pop_scope().is_ok() {}
+    }
+
+    fn take_scopes(&mut self) -> SavedScopes {
+        let global = vec![vec![self.global_scope_id]];
+        Box::new(std::mem::replace(&mut self.mem_node_stack, global))
+    }
+
+    fn replace_scopes(&mut self, scopes: SavedScopes) {
+        self.unwind_scopes(ScopeDepth {
+            loads: 1,
+            scopes: 1,
+        });
+        self.mem_node_stack = *scopes
+            .downcast::<Vec<Vec<usize>>>()
+            .expect("scopes were not taken from this memory");
     }
 
     fn set_var(&mut self, var: usize, value: Value) {
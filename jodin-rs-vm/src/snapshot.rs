@@ -0,0 +1,83 @@
+//! Snapshots save the complete state of a vm, so a run can be stopped and continued later, by
+//! another vm or on another host.
+//!
+//! A snapshot holds the loaded instructions with their labels and debug info, the counter stack,
+//! the memory along with its operand stack and scopes, the values in the heap, the exception
+//! handlers and the fault handlers, including one that's running. Values are saved
+//! [with aliases](jodin_common::assembly::value::with_aliases), so references that were shared,
+//! including references to variables, are still shared once the snapshot is restored, and cycles
+//! between them survive.
+//!
+//! Snapshots are written with [VM::snapshot](crate::vm::VM::snapshot) and read by
+//! [VM::restore](crate::vm::VM::restore) into a vm with the same kind of memory. A run that was
+//! stopped, such as by running out of fuel, can then be continued with
+//! [VM::resume](crate::vm::VM::resume). What the host configured, like the standard streams,
+//! limits, permissions, engine, observers and plugins, isn't part of a snapshot. Snapshots can't be
+//! taken while other [fibers](crate::fiber) or [generators](crate::generator) are alive.
+
+use crate::exception::ExceptionHandler;
+use crate::fault::FaultJumpTable;
+use jodin_common::assembly::debug_info::DebugInfo;
+use jodin_common::assembly::instructions::Assembly;
+use jodin_common::assembly::value::JRef;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The state of a vm. Written with a borrowed memory and fault handle, and read with owned ones.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot<M, H> {
+    pub instructions: Assembly,
+    pub label_to_instruction: HashMap<String, usize>,
+    pub label_references: HashMap<String, Vec<usize>>,
+    pub counter_stack: Vec<usize>,
+    pub debug_info: Vec<(Range<usize>, DebugInfo)>,
+    pub memory: M,
+    /// Every value in the heap that's still alive
+    pub heap: Vec<JRef>,
+    pub exception_handlers: Vec<ExceptionHandler>,
+    pub own_loads: Vec<(usize, usize)>,
+    pub handler: Option<H>,
+    pub fault_table: FaultJumpTable,
+    pub kernel_mode: bool,
+    pub next_anonymous_function: u64,
+    pub executed_instructions: u64,
+}
+
+/// Serializes variables as [JRef]s, so they stay shared with the references to them. Used with
+/// `#[serde(with = "crate::snapshot::variables")]`.
+pub(crate) mod variables {
+    use jodin_common::assembly::value::{JRef, Value};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::hash::BuildHasher;
+    use std::rc::Rc;
+
+    pub fn serialize<S, H>(
+        variables: &HashMap<usize, Rc<RefCell<Value>>, H>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(
+            variables
+                .iter()
+                .map(|(&var, cell)| (var, JRef::from(cell.clone()))),
+        )
+    }
+
+    pub fn deserialize<'de, D, H>(
+        deserializer: D,
+    ) -> Result<HashMap<usize, Rc<RefCell<Value>>, H>, D::Error>
+    where
+        D: Deserializer<'de>,
+        H: BuildHasher + Default,
+    {
+        let variables = HashMap::<usize, JRef>::deserialize(deserializer)?;
+        Ok(variables
+            .into_iter()
+            .map(|(var, reference)| (var, reference.into()))
+            .collect())
+    }
+}
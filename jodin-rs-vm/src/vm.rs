@@ -1,7 +1,20 @@
-use crate::error::VMError;
-use crate::fault::{Fault, FaultHandle, FaultJumpTable};
+use crate::debugger::Debugger;
+use crate::error::{ArithmeticError, ErrorLocation, StackFrame, StackTrace, VMError};
+use crate::exception::ExceptionHandler;
+use crate::fault::{Fault, FaultAction, FaultHandle, FaultJumpTable};
+use crate::fiber::Scheduler;
+use crate::files::FileTable;
+use crate::generator::{Generators, GENERATOR_ID};
+use crate::heap::{CollectionPolicy, Heap, HeapStats};
+use crate::limits::{ExecutionLimits, DEADLINE_CHECK_INTERVAL};
+use crate::natives::{Arity, NativeFunction};
+use crate::observer::VMObserver;
+use crate::permissions::{Capability, Permissions};
+use crate::protocol::ITERATED_ARRAY;
+use crate::replay::{has_outside_effects, Trace, Tracer};
 use crate::{ArithmeticsTrait, MemoryTrait, VMTryLoadable, VirtualMachine, CALL, RECEIVE_MESSAGE};
 
+use jodin_common::assembly::debug_info::{DebugInfo, SourceLocation};
 use jodin_common::assembly::instructions::{Asm, Assembly, Decode, GetAsm};
 use jodin_common::assembly::location::AsmLocation;
 use jodin_common::assembly::value::{JRef, Value};
@@ -10,18 +23,35 @@ use jodin_common::identifier::Identifier;
 use jodin_vm_plugins::plugins::{LoadablePlugin, PluginManager, Stack, VMHandle};
 use jodin_vm_plugins::Plugin;
 use more_collection_macros::{map, set};
-use std::collections::hash_map::{DefaultHasher, Entry};
-use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::OsStr;
 use std::fmt::{Debug, Formatter};
-use std::hash::Hasher;
-use std::io::{stderr, stdout, Read, Write};
-use std::ops::{Add, Deref};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::{Add, Deref, Range};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
+mod fibers;
+mod files;
+mod generators;
+mod messages;
+mod natives;
+mod registers;
+mod snapshots;
+mod threaded;
+mod traces;
+
+use jodin_common::assembly::registers::{RegisterAssembly, REGISTER_COUNT};
+use natives::{builtin_natives, Native, Natives};
+use threaded::ThreadedOp;
+
+/// The default deepest the call stack of the vm can get before a stack overflow occurs
+pub const MAX_CALL_DEPTH: usize = 1 << 14;
+
 pub struct VM<'l, M, A>
 where
     M: MemoryTrait,
@@ -31,21 +61,57 @@ where
     alu: A,
     cont: bool,
 
-    instructions: Assembly,
+    /// Shared so the dispatch loop can hold instructions by reference while executing them
+    instructions: Rc<Assembly>,
     label_to_instruction: HashMap<String, usize>,
+    /// The instructions that refer to each label, so they can be relinked if the label is redefined
+    label_references: HashMap<String, Vec<usize>>,
     counter_stack: Vec<usize>,
+    /// The debug info of the loaded objects that had any, with the instructions it's for
+    debug_info: Vec<(Range<usize>, DebugInfo)>,
 
-    stdin: Option<Box<dyn Read + 'l>>,
+    stdin: Option<Box<dyn BufRead + 'l>>,
     stdout: Option<Box<dyn Write + 'l>>,
     stderr: Option<Box<dyn Write + 'l>>,
 
     next_anonymous_function: AtomicU64,
 
     handler: Option<FaultHandle>,
+    /// The operands an instruction popped before it failed, so it can be retried by a fault handler
+    fault_operands: Vec<Value>,
+    exception_handlers: Vec<ExceptionHandler>,
+    /// The loads functions made of their own scopes without the [Scopes](Capability::Scopes)
+    /// capability, as the call depth and load depth they were made at, so they can be backed out of
+    own_loads: Vec<(usize, usize)>,
 
     fault_table: FaultJumpTable,
     kernel_mode: bool,
 
+    debugger: Option<Debugger<'l>>,
+    observers: Vec<Box<dyn VMObserver + 'l>>,
+
+    limits: ExecutionLimits,
+    executed_instructions: u64,
+    /// Instructions left until the deadline is checked again
+    deadline_countdown: u64,
+
+    engine: Engine,
+    /// The compiled instructions, if the threaded code engine is used
+    threaded: Option<Rc<Vec<ThreadedOp<'l, M, A>>>>,
+    /// The register form of the instructions, if the register engine is used and it's up to date
+    register_code: Option<Rc<RegisterAssembly>>,
+    /// Registers never hold values across a call, so every frame shares the same register file
+    registers: Vec<Value>,
+
+    heap: Heap,
+    files: FileTable,
+    scheduler: Scheduler,
+    generators: Generators,
+    /// Records or replays the calls with outside effects, if either was asked for
+    tracer: Option<Tracer>,
+
+    natives: Natives<'l, M, A>,
+    permissions: Permissions,
     plugin_manager: Arc<RwLock<PluginManager>>,
 }
 
@@ -59,18 +125,12 @@ where
             .field("instructions", &self.instructions.len())
             .field("program_counter", &self.program_counter())
             .field(
-                "counter_stack",
+                "stack_trace",
                 &self
-                    .counter_stack
+                    .stack_trace()
+                    .frames
                     .iter()
-                    .map(|c| {
-                        format!(
-                            "{} -> {}",
-                            *c,
-                            self.most_recent_public_label(*c)
-                                .unwrap_or(&"<none>".to_string())
-                        )
-                    })
+                    .map(StackFrame::to_string)
                     .collect::<Vec<_>>(),
             )
             .field("memory", &self.memory)
@@ -96,7 +156,7 @@ where
     }
 
     pub fn set_stdin<R: Read + 'l>(&mut self, reader: R) {
-        self.stdin = Some(Box::new(reader));
+        self.stdin = Some(Box::new(BufReader::new(reader)));
     }
 
     pub fn set_stdout<W: Write + 'l>(&mut self, writer: W) {
@@ -107,8 +167,79 @@ where
         self.stderr = Some(Box::new(writer));
     }
 
+    /// Attaches a debugger that is consulted before every instruction
+    pub fn set_debugger(&mut self, debugger: Debugger<'l>) {
+        self.debugger = Some(debugger);
+    }
+
+    /// Registers an observer that is notified of events as the vm runs
+    pub fn add_observer<O: VMObserver + 'l>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// The program counters of every active frame, with the current frame last
+    pub fn counter_stack(&self) -> &[usize] {
+        &self.counter_stack
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    /// The number of instructions the vm may still execute, if limited
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.limits.fuel
+    }
+
+    /// Gives the vm more instructions to execute. Does nothing if fuel isn't limited.
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(remaining) = &mut self.limits.fuel {
+            *remaining = remaining.saturating_add(fuel);
+        }
+    }
+
+    /// The total number of instructions executed by the vm
+    pub fn executed_instructions(&self) -> u64 {
+        self.executed_instructions
+    }
+
+    /// The capabilities the vm has
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    /// How this vm executes instructions
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Gets statistics about the values in the heap
+    pub fn heap_stats(&self) -> HeapStats {
+        self.heap.stats()
+    }
+
+    /// Frees the values in the heap that can't be reached. Returns the number of values freed.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.heap.collect()
+    }
+
+    /// Creates a reference to a value in the heap, collecting first if the heap calls for it
+    fn allocate(&mut self, value: Value) -> Value {
+        if self.heap.should_collect() {
+            self.heap.collect();
+        }
+        self.heap.allocate(value)
+    }
+
+    /// Gets the instruction index of a loaded label
+    pub fn label_location(&self, label: &str) -> Option<usize> {
+        self.label_to_instruction.get(label).copied()
+    }
+
     pub fn most_recent_public_label(&self, instruction: usize) -> Option<&String> {
-        let range = (0..=instruction).into_iter().rev();
+        let range = (0..=instruction.min(self.instructions.len() - 1))
+            .into_iter()
+            .rev();
 
         for i in range {
             let asm = &self.instructions[i];
@@ -131,132 +262,306 @@ where
         Identifier::new_alt_delimiter(string, "_")
     }
 
-    fn native_method(&mut self, message: &str, mut args: Vec<Value>) {
-        info!(
-            "Running native method {:?} with args ({})",
-            message,
-            args.iter()
-                .rev()
-                .map(|a| format!("{a:?}"))
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-        match message {
-            "print" => {
-                let s = format!("{:#}", args.remove(0));
-                match &mut self.stdout {
-                    None => {
-                        print!("{}", s);
-                    }
-                    Some(stdout) => {
-                        write!(stdout, "{}", s).expect("Couldn't print to output");
-                    }
-                }
-                self.memory.push(Value::Empty);
-            }
-            "write" => {
-                let fd = if let Value::UInteger(fd) = args.remove(0) {
-                    fd
-                } else {
-                    panic!("File descriptors should only be unsigned ints")
-                };
-                let mut stdout: Box<dyn Write> = Box::new(stdout());
-                let mut stderr: Box<dyn Write> = Box::new(stderr());
-                let output = match fd {
-                    1 => self.stdout.as_mut().unwrap_or(&mut stdout),
-                    2 => self.stderr.as_mut().unwrap_or(&mut stderr),
-                    _ => {
-                        panic!("{} is not a valid file descriptor for writing", fd);
-                    }
-                };
-                if let Value::Str(s) = args.remove(0) {
-                    write!(output, "{}", s).expect("Couldn't write");
-                } else {
-                    panic!("Can not only pass strings to the write function")
-                }
-                self.memory.push(Value::Empty);
-            }
-            "invoke" => {
-                // invokes the message (arg 2) on the target (arg 1) with args (arg 3..)
-                let mut target = args.pop().unwrap();
-                let msg = args
-                    .pop()
-                    .unwrap()
-                    .into_string()
-                    .expect("String expected for message");
-                if let Value::Array(args) = args.pop().unwrap() {
-                    self.send_message(&mut target, &msg, args);
-                } else {
-                    panic!("Expected a value of type array")
-                }
-            }
-            "ref" => {
-                let target = args.remove(0);
-
-                let as_ref = target.into_reference();
-                self.memory.push(as_ref);
-            }
-            "copy" => {
-                let target = args.remove(0);
-                let cloned = target.clone();
-                self.memory.push(target);
-                self.memory.push(cloned);
-            }
-            "dynamic_call" => {
-                if let Value::Str(function) = args.remove(0) {
-                    let plugin_manager = self.plugin_manager.read().unwrap();
-                    let mut stack = self.stack();
-                    let mut handle = DefaultVmHandle::new(self);
-                    let result = plugin_manager
-                        .call_function(&*function, &mut stack, &mut handle)
-                        .expect("failed!");
-                    self.memory.push(result);
-                } else {
-                    panic!("Expected a value of type String")
+    /// The location of the instruction currently being executed
+    pub fn error_location(&self) -> ErrorLocation {
+        self.location_in(&self.counter_stack)
+    }
+
+    /// The location of the instruction at the top of a counter stack, with the stack trace of the
+    /// whole counter stack
+    pub(crate) fn location_in(&self, counter_stack: &[usize]) -> ErrorLocation {
+        let pc = counter_stack.last().copied().unwrap_or(0);
+        ErrorLocation::new(pc, self.most_recent_public_label(pc).cloned())
+            .with_stack_trace(self.stack_trace_of(counter_stack))
+    }
+
+    /// The guest's call stack, resolving every frame to the function it's in
+    pub fn stack_trace(&self) -> StackTrace {
+        self.stack_trace_of(&self.counter_stack)
+    }
+
+    fn stack_trace_of(&self, counter_stack: &[usize]) -> StackTrace {
+        // the frame a run started from is at 0 and has no instructions of its own
+        let frames = counter_stack
+            .iter()
+            .rev()
+            .filter(|&&pc| pc != 0)
+            .map(|&pc| StackFrame {
+                pc,
+                function: self.most_recent_public_label(pc).cloned(),
+                source: self.source_location(pc),
+            })
+            .collect();
+        StackTrace { frames }
+    }
+
+    /// The source location an instruction was compiled from, if it was loaded with debug info
+    pub fn source_location(&self, instruction: usize) -> Option<SourceLocation> {
+        let (range, info) = self.debug_info_of(instruction)?;
+        info.location(instruction - range.start)
+    }
+
+    /// The source name of a variable of the function an instruction is in, if it was loaded with
+    /// debug info
+    pub fn local_name(&self, instruction: usize, var: usize) -> Option<&str> {
+        let (_, info) = self.debug_info_of(instruction)?;
+        let function = self.most_recent_public_label(instruction)?;
+        info.local_name(function, var as u64)
+    }
+
+    fn debug_info_of(&self, instruction: usize) -> Option<&(Range<usize>, DebugInfo)> {
+        self.debug_info
+            .iter()
+            .find(|(range, _)| range.contains(&instruction))
+    }
+
+    /// Pops a value from the stack, failing if there are no values on the stack
+    fn pop(&mut self) -> Result<Value, VMError> {
+        self.memory.pop().ok_or_else(|| VMError::StackUnderflow {
+            location: self.error_location(),
+        })
+    }
+
+    fn type_mismatch(&self, value: Value, expected: impl AsRef<str>) -> VMError {
+        VMError::TypeMismatch {
+            value,
+            expected: expected.as_ref().to_string(),
+            location: self.error_location(),
+        }
+    }
+
+    fn invalid_native_arguments(&self, native: &str, reason: impl AsRef<str>) -> VMError {
+        VMError::InvalidNativeArguments {
+            native: native.to_string(),
+            reason: reason.as_ref().to_string(),
+            location: self.error_location(),
+        }
+    }
+
+    /// Checks that none of the labels of some asm were already loaded, other than `@@` labels,
+    /// which can be loaded again to replace them
+    fn check_labels(&self, asm: &Assembly) -> Result<(), VMError> {
+        let mut labels = HashSet::new();
+        for asm in asm {
+            if let Asm::Label(label) | Asm::PublicLabel(label) = asm {
+                let loaded = self.label_to_instruction.contains_key(label) || !labels.insert(label);
+                if loaded && !label.starts_with("@@") {
+                    return Err(VMError::DuplicateLabel(label.clone()));
                 }
             }
-            "@load_scope" => {
-                let scope = args.remove(0);
-                let mut hasher = DefaultHasher::default();
-                scope.try_hash(&mut hasher).unwrap();
-                let hashed = hasher.finish();
-                self.memory.load_scope(hashed);
-            }
-            "@save_scope" => {
-                let scope = args.remove(0);
-                let mut hasher = DefaultHasher::default();
-                scope.try_hash(&mut hasher).unwrap();
-                let hashed = hasher.finish();
-                self.memory.save_current_scope(hashed);
+        }
+        Ok(())
+    }
+
+    fn scope_underflow(&self, native: &str) -> VMError {
+        VMError::ScopeUnderflow {
+            native: native.to_string(),
+            location: self.error_location(),
+        }
+    }
+
+    fn invalid_message(&self, message: &str, target: impl AsRef<str>) -> VMError {
+        VMError::InvalidMessage {
+            message: message.to_string(),
+            target: target.as_ref().to_string(),
+            location: self.error_location(),
+        }
+    }
+
+    fn label_index(&self, label: &str) -> Result<usize, VMError> {
+        self.label_to_instruction
+            .get(label)
+            .copied()
+            .ok_or_else(|| VMError::UnknownLabel {
+                label: label.to_string(),
+                location: self.error_location(),
+            })
+    }
+
+    /// Gets the label an instruction refers to, if it should be resolved at link time.
+    ///
+    /// Functions provided by plugins are left as labels so they can still be found by name.
+    fn linkable_label<'a>(&self, asm: &'a Asm) -> Option<&'a str> {
+        match asm {
+            Asm::Goto(AsmLocation::Label(l))
+            | Asm::CondGoto(AsmLocation::Label(l))
+            | Asm::PushHandler(AsmLocation::Label(l))
+            | Asm::GetSymbol(l) => Some(l),
+            Asm::Push(Value::Function(AsmLocation::Label(l)))
+            | Asm::Call(AsmLocation::Label(l), _)
+                if !self.plugin_manager.read().unwrap().loaded_label(l) =>
+            {
+                Some(l)
             }
-            "@push_scope" => {
-                self.memory.push_scope();
+            _ => None,
+        }
+    }
+
+    /// Rewrites an instruction that refers to a label to instead refer to the label's index
+    fn resolve_reference(asm: &mut Asm, index: usize) {
+        match asm {
+            Asm::Goto(location)
+            | Asm::CondGoto(location)
+            | Asm::PushHandler(location)
+            | Asm::Push(Value::Function(location))
+            | Asm::Call(location, _) => *location = AsmLocation::ByteIndex(index),
+            Asm::GetSymbol(_) => *asm = Asm::Push(Value::Function(AsmLocation::ByteIndex(index))),
+            _ => {}
+        }
+    }
+
+    /// Resolves the labels used by the instructions starting at `start`. Labels that aren't
+    /// defined yet are left as is, and resolved once they are defined.
+    fn link(&mut self, start: usize) {
+        let mut resolved = vec![];
+        for index in start..self.instructions.len() {
+            if let Some(label) = self.linkable_label(&self.instructions[index]) {
+                self.label_references
+                    .entry(label.to_string())
+                    .or_default()
+                    .push(index);
+                if let Some(&target) = self.label_to_instruction.get(label) {
+                    resolved.push((index, target));
+                }
             }
-            "@pop_scope" => {
-                self.memory.pop_scope();
+        }
+        let instructions = Rc::make_mut(&mut self.instructions);
+        for &(index, target) in &resolved {
+            Self::resolve_reference(&mut instructions[index], target);
+        }
+        for (index, _) in resolved {
+            self.recompile(index);
+        }
+    }
+
+    /// Points every instruction that refers to a label at the label's current index
+    fn relink(&mut self, label: &str) {
+        let target = match self.label_to_instruction.get(label) {
+            Some(&target) => target,
+            None => return,
+        };
+        if let Some(references) = self.label_references.get(label).cloned() {
+            let instructions = Rc::make_mut(&mut self.instructions);
+            for &index in &references {
+                Self::resolve_reference(&mut instructions[index], target);
             }
-            "@global_scope" => {
-                self.memory.global_scope();
+            for index in references {
+                self.recompile(index);
             }
-            "@back_scope" => {
-                self.memory.back_scope();
+        }
+    }
+
+    /// Compiles every instruction that hasn't been compiled yet, if the threaded engine is used
+    fn compile_threaded(&mut self) {
+        if let Some(ops) = &mut self.threaded {
+            let ops = Rc::make_mut(ops);
+            let start = ops.len();
+            // the last compiled instructions may be fused with the first new ones
+            for index in start.saturating_sub(threaded::LONGEST_FUSION - 1)..start {
+                ops[index] = threaded::compile(&self.instructions, index);
             }
-            "@print_stack" => {
-                println!("memory: {:#?}", self.memory);
+            for index in start..self.instructions.len() {
+                ops.push(threaded::compile(&self.instructions, index));
             }
-            "@call" => {
-                if let Value::Str(method) = args.remove(0) {
-                    self.native_method(&method, args)
-                } else {
-                    panic!("Must have a string as the first argument if message is {CALL}")
+        }
+    }
+
+    /// Compiles an instruction again after it was changed
+    fn recompile(&mut self, index: usize) {
+        // the register form is translated again the next time it's needed
+        self.register_code = None;
+        if let Some(ops) = &mut self.threaded {
+            let ops = Rc::make_mut(ops);
+            // the instructions before it may be fused with it
+            for index in index.saturating_sub(threaded::LONGEST_FUSION - 1)..=index {
+                if index < ops.len() {
+                    ops[index] = threaded::compile(&self.instructions, index);
                 }
             }
-            _ => panic!("{:?} is not a native method", message),
         }
     }
 
-    fn stack(&self) -> VMStack<M> {
-        unsafe { VMStack::new(&mut *(&self.memory as *const M as *mut M)) }
+    /// Whether the engine can run instructions without the checks the interpreter loop makes
+    /// around each instruction
+    fn can_run_compiled(&self) -> bool {
+        let engine = match self.engine {
+            Engine::Interpreter => false,
+            Engine::Threaded => true,
+            // register instructions can stand for several instructions, which fuel can't split
+            Engine::Register => self.limits.fuel.is_none(),
+        };
+        engine
+            && self.debugger.is_none()
+            && self.observers.is_empty()
+            // preemption counts instructions one at a time
+            && self.scheduler.preemption.is_none()
+            && !log_enabled!(target: "virtual_machine", log::Level::Info)
+    }
+
+    /// Takes the first argument of a native method
+    fn native_arg(&self, native: &str, args: &mut Vec<Value>) -> Result<Value, VMError> {
+        if args.is_empty() {
+            Err(self.invalid_native_arguments(native, "missing argument"))
+        } else {
+            Ok(args.remove(0))
+        }
+    }
+
+    /// Runs some arithmetic on the alu, attaching the current location to any errors
+    fn arithmetic<F>(&self, op: F) -> Result<Value, VMError>
+    where
+        F: FnOnce(&A) -> Result<Value, ArithmeticError>,
+    {
+        op(&self.alu).map_err(|e| e.at(self.error_location()))
+    }
+
+    /// Runs a binary operation on the alu with the values that were on top of the stack. If it
+    /// fails, the values are kept so a fault handler can retry the operation with them.
+    fn binary_arithmetic<F>(&mut self, left: Value, right: Value, op: F) -> Result<Value, VMError>
+    where
+        F: FnOnce(&A, Value, Value) -> Result<Value, ArithmeticError>,
+    {
+        op(&self.alu, left.clone(), right.clone()).map_err(|e| {
+            self.fault_operands = vec![right, left];
+            e.at(self.error_location())
+        })
+    }
+
+    fn native_method(&mut self, message: &str, args: Vec<Value>) -> Result<(), VMError> {
+        info!(
+            "Running native method {:?} with args ({})",
+            message,
+            args.iter()
+                .rev()
+                .map(|a| format!("{a:?}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        for observer in &mut self.observers {
+            observer.on_native(message, &args);
+        }
+        let found = self.find_native(message, args.len())?;
+        if self.tracer.is_some() && has_outside_effects(message) {
+            return self.trace_native(message, found, args);
+        }
+        self.run_native(message, found, args)
+    }
+
+    /// Calls a function provided by a plugin, which pops its arguments from the stack, returning
+    /// its result
+    fn call_plugin(&mut self, function: &str) -> Result<Value, VMError> {
+        self.require_capability(Capability::Plugins, function)?;
+        if self.tracer.is_some() {
+            return self.trace_plugin(function);
+        }
+        let plugin_manager = self.plugin_manager.clone();
+        let plugin_manager = plugin_manager.read().unwrap();
+        let mut handle = DefaultVmHandle::new(self);
+        let result = plugin_manager.call_function(function, &mut handle);
+        if let Some(error) = handle.error.take() {
+            return Err(error);
+        }
+        Ok(result?)
     }
 
     fn send_message(
@@ -264,7 +569,7 @@ where
         target: &mut Value,
         message: &str,
         mut args: Vec<Value>,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, VMError> {
         info!(
             "Sending {:?} to {:?} with args ({})",
             message,
@@ -277,51 +582,36 @@ where
         );
         match target {
             Value::Empty => {}
-            Value::Byte(_) => {}
-            Value::Float(_) => {}
-            Value::Integer(_) => {}
-            Value::UInteger(_) => {}
-            Value::Str(_) => {}
+            number
+            @ (Value::Byte(_) | Value::Float(_) | Value::Integer(_) | Value::UInteger(_)) => {
+                let ret = self.number_message(number.clone(), message, args)?;
+                self.memory.push(ret);
+            }
+            Value::Str(string) => {
+                let ret = self.string_message(string, message, args)?;
+                self.memory.push(ret);
+            }
             Value::Dictionary(dict) => {
                 if let Some(mut receive_msg) = dict.get(RECEIVE_MESSAGE).cloned() {
                     if receive_msg != Value::Native {
                         return self.send_message(&mut receive_msg, CALL, args);
                     }
                 }
-
-                let ret = match message {
-                    "get" => {
-                        let name = args
-                            .remove(0)
-                            .into_string()
-                            .expect("first value should be a string");
-                        dict.get(&*name)
-                            .expect(&*format!("{} not in dictionary", name))
-                            .clone()
-                    }
-                    "put" => {
-                        let name = args
-                            .remove(0)
-                            .into_string()
-                            .expect("first value should be a string");
-                        let value = args.remove(0);
-                        dict.insert(name, value);
-                        Value::Empty
-                    }
-                    "contains" => {
-                        todo!()
-                    }
-                    "remove" => {
-                        todo!()
-                    }
-                    "len" => {
-                        todo!()
-                    }
-                    m => panic!("{:?} is not a valid message for dictionary", m),
+                if let Some(&Value::UInteger(id)) = dict.get(GENERATOR_ID) {
+                    self.generator_message(id, message)?;
+                    return Ok(None);
+                }
+                let ret = if dict.contains_key(ITERATED_ARRAY) {
+                    self.iterator_message(dict, message)?
+                } else {
+                    self.dictionary_message(dict, message, args)?
                 };
                 self.memory.push(ret);
             }
-            Value::Array(_) => {}
+            Value::Array(array) => {
+                let ret = self.array_message(array, message, args)?;
+                self.memory.push(ret);
+            }
             Value::Reference(reference) => {
                 let mut as_mut = reference.borrow_mut();
                 let as_mut_ref = &mut *as_mut;
@@ -329,13 +619,13 @@ where
             }
             Value::Bytecode(bytecode) => {
                 if message != CALL {
-                    panic!("Can only call bytecode objects")
+                    return Err(self.invalid_message(message, "bytecode"));
                 }
                 let mut decoded = bytecode.clone().decode();
                 let name = self.anonymous_function_label();
                 let label = Asm::Label(name.clone());
                 decoded.insert(0, label);
-                self.load(decoded);
+                self.try_load(decoded)?;
 
                 let mut value = Value::Function(AsmLocation::Label(name.clone()));
                 self.memory.save_current_scope(&name);
@@ -344,22 +634,27 @@ where
             }
             Value::Function(f) => {
                 if message != CALL {
-                    panic!("Can only call function objects")
+                    return Err(self.invalid_message(message, "function"));
                 }
                 return self.call(f, args);
             }
             Value::Native => {
-                self.native_method(message, args);
+                self.native_method(message, args)?;
             }
         }
-        return None;
+        Ok(None)
     }
 
     fn program_counter(&self) -> usize {
         self.counter_stack.last().copied().unwrap_or(0)
     }
 
-    fn call(&mut self, asm_location: &AsmLocation, mut args: Vec<Value>) -> Option<usize> {
+    /// Calls a function with arguments given in the order they were packed
+    fn call(
+        &mut self,
+        asm_location: &AsmLocation,
+        mut args: Vec<Value>,
+    ) -> Result<Option<usize>, VMError> {
         info!(
             "Attempting to call {:?} with args ({})",
             asm_location,
@@ -369,30 +664,73 @@ where
                 .collect::<Vec<_>>()
                 .join(", ")
         );
+        let target = self.call_target(asm_location)?;
         args.reverse();
         for arg in args {
             self.memory.push(arg);
         }
-        let next_pc = match asm_location {
-            &AsmLocation::ByteIndex(i) => i,
+        self.enter_function(target)
+    }
+
+    /// Calls a function whose `arity` arguments are already on the stack, with the first argument
+    /// on top
+    fn call_direct(
+        &mut self,
+        asm_location: &AsmLocation,
+        arity: usize,
+    ) -> Result<Option<usize>, VMError> {
+        if self.memory.stack().len() < arity {
+            return Err(VMError::StackUnderflow {
+                location: self.error_location(),
+            });
+        }
+        let target = self.call_target(asm_location)?;
+        self.enter_function(target)
+    }
+
+    /// Finds what a call to a location should run, without changing the state of the vm
+    fn call_target(&self, asm_location: &AsmLocation) -> Result<CallTarget, VMError> {
+        if self.counter_stack.len() >= self.limits.max_call_depth {
+            return Err(VMError::StackOverflow {
+                location: self.error_location(),
+            });
+        }
+        match asm_location {
+            &AsmLocation::ByteIndex(i) => Ok(CallTarget::Instruction(i)),
             AsmLocation::InstructionDiff(_) => {
-                panic!("Illegal for calling functions")
+                Err(self.type_mismatch(Value::Function(asm_location.clone()), "function location"))
             }
             AsmLocation::Label(l) => {
-                let read = self.plugin_manager.read().unwrap();
-                if read.loaded_label(l) {
-                    let ref mut stack = self.stack();
-                    let ref mut handle = DefaultVmHandle::new(self);
-                    let output = read.call_function(l.as_ref(), stack, handle).unwrap();
-                    self.memory.push(output);
-                    return None;
+                if self.plugin_manager.read().unwrap().loaded_label(l) {
+                    Ok(CallTarget::Plugin(l.clone()))
+                } else {
+                    self.label_index(l).map(CallTarget::Instruction)
                 }
-                self.label_to_instruction[l]
+            }
+        }
+    }
+
+    /// Enters a function whose arguments have been pushed. Returns the instruction to continue at,
+    /// or `None` if the function was provided by a plugin and has already returned.
+    fn enter_function(&mut self, target: CallTarget) -> Result<Option<usize>, VMError> {
+        let next_pc = match target {
+            CallTarget::Instruction(i) => i,
+            CallTarget::Plugin(label) => {
+                let output = self.call_plugin(&label)?;
+                self.memory.push(output);
+                return Ok(None);
             }
         };
         debug!("Returning next PC to function at index 0x{:016X}", next_pc);
         self.counter_stack.push(0);
-        Some(next_pc)
+        if !self.observers.is_empty() {
+            let function = self.most_recent_public_label(next_pc).cloned();
+            let depth = self.counter_stack.len();
+            for observer in &mut self.observers {
+                observer.on_call(next_pc, function.as_deref(), depth);
+            }
+        }
+        Ok(Some(next_pc))
     }
 
     fn anonymous_function_label(&self) -> String {
@@ -405,23 +743,306 @@ where
         self.counter_stack.push(pc);
     }
 
+    /// Gets the instruction index a jump from `instruction_pointer` to `location` lands on
+    fn jump_target(
+        &self,
+        location: &AsmLocation,
+        instruction_pointer: usize,
+    ) -> Result<usize, VMError> {
+        match location {
+            &AsmLocation::ByteIndex(i) => Ok(i),
+            &AsmLocation::InstructionDiff(diff) => instruction_pointer
+                .checked_add_signed(diff)
+                .ok_or_else(|| VMError::JumpOutOfBounds {
+                    diff,
+                    location: self.error_location(),
+                }),
+            AsmLocation::Label(l) => self.label_index(l),
+        }
+    }
+
     pub fn in_fault(&self) -> bool {
         self.handler.is_some()
     }
 
-    fn end_fault(&mut self, handle: FaultHandle) {
+    /// Sets the function that is called when a fault occurs. Handlers are called with a dictionary
+    /// describing the fault, and must return `"resume"`, `"retry"` or `"abort"`. Any values left on
+    /// the stack below the returned action are pushed onto the restored stack. If there are none,
+    /// a retried instruction gets back the operands it popped.
+    pub fn set_fault_handler(&mut self, fault: &str, handler: AsmLocation) -> bool {
+        self.fault_table
+            .set_fault_jump(fault, Value::Function(handler))
+    }
+
+    /// Unwinds the vm back to the most recently pushed exception handler, then pushes the thrown
+    /// value. Returns the instruction the handler continues at.
+    fn throw(&mut self, value: Value) -> Result<usize, VMError> {
+        let handler = loop {
+            match self.exception_handlers.pop() {
+                Some(handler) => break handler,
+                // generators are ended by exceptions they don't catch
+                None if !self.generators.resumers.is_empty() => {
+                    self.leave_generator();
+                }
+                None => {
+                    return Err(VMError::UncaughtException {
+                        value,
+                        location: self.error_location(),
+                    })
+                }
+            }
+        };
+        info!("Unwinding to exception handler {:?}", handler);
+        while self.counter_stack.len() > handler.frame_depth {
+            self.counter_stack.pop();
+            let depth = self.counter_stack.len();
+            for observer in &mut self.observers {
+                observer.on_return(depth);
+            }
+        }
+        self.memory.unwind_scopes(handler.scope_depth);
+        self.own_loads.retain(|&(depth, loads)| {
+            depth <= handler.frame_depth && loads <= handler.scope_depth.loads
+        });
+        let mut stack = self.memory.take_stack();
+        stack.truncate(handler.stack_len);
+        self.memory.replace_stack(stack);
+        self.memory.push(value);
+        Ok(handler.target)
+    }
+
+    /// Uses up one instruction of the vm's budget, failing if any execution limit was exceeded
+    fn check_limits(&mut self) -> Result<(), VMError> {
+        if let Some(fuel) = &mut self.limits.fuel {
+            if *fuel == 0 {
+                return Err(VMError::OutOfFuel {
+                    location: self.error_location(),
+                });
+            }
+            *fuel -= 1;
+        }
+        self.executed_instructions += 1;
+        if let Some(max) = self.limits.max_stack_size {
+            let len = self.memory.stack().len();
+            if len > max {
+                return Err(VMError::StackLimitExceeded {
+                    len,
+                    max,
+                    location: self.error_location(),
+                });
+            }
+        }
+        if let Some(deadline) = self.limits.deadline {
+            self.deadline_countdown = self.deadline_countdown.saturating_sub(1);
+            if self.deadline_countdown == 0 {
+                self.deadline_countdown = DEADLINE_CHECK_INTERVAL;
+                if Instant::now() >= deadline {
+                    return Err(VMError::DeadlineExceeded {
+                        location: self.error_location(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn require_kernel_mode(&self) -> Result<(), VMError> {
+        if self.kernel_mode {
+            Ok(())
+        } else {
+            Err(VMError::NotKernelMode {
+                location: self.error_location(),
+            })
+        }
+    }
+
+    /// Checks the vm has the capability a call needs
+    fn require_capability(&self, capability: Capability, call: &str) -> Result<(), VMError> {
+        if self.permissions.allows(capability) {
+            Ok(())
+        } else {
+            Err(VMError::PermissionDenied {
+                call: call.to_string(),
+                capability,
+                location: self.error_location(),
+            })
+        }
+    }
+
+    fn bad_reference(&self, value: Value) -> VMError {
+        VMError::BadReference {
+            value,
+            location: self.error_location(),
+        }
+    }
+
+    /// Tries to turn an error produced by an instruction into a fault. Returns the error if it
+    /// can't be handled.
+    fn raise_fault(&mut self, error: VMError) -> Result<(), VMError> {
+        let operands = std::mem::take(&mut self.fault_operands);
+        if self.in_fault() {
+            return Err(VMError::DoubleFault(Box::new(error)));
+        }
+        match Fault::from_error(&error) {
+            Some(fault) if self.fault_table.get_fault_jump(&fault).is_some() => {
+                self.enter_fault(fault, Some(error), operands)
+            }
+            _ => Err(error),
+        }
+    }
+
+    fn enter_fault(
+        &mut self,
+        fault: Fault,
+        cause: Option<VMError>,
+        operands: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let location = self.error_location();
+        let target = match self.fault_table.get_fault_jump(&fault) {
+            Some(target) => target.clone(),
+            None => return Err(cause.unwrap_or(VMError::UnhandledFault { fault, location })),
+        };
+        let handler_pc = match &target {
+            Value::Function(AsmLocation::ByteIndex(i)) => *i,
+            Value::Function(AsmLocation::Label(l)) => self.label_index(l)?,
+            v => return Err(self.type_mismatch(v.clone(), "function")),
+        };
+
+        let saved_counter = std::mem::replace(&mut self.counter_stack, vec![0, handler_pc]);
+        let saved_stack = self.memory.take_stack();
+        let saved_handlers = std::mem::take(&mut self.exception_handlers);
+        let handle = FaultHandle::new(
+            saved_counter,
+            saved_stack,
+            operands,
+            fault,
+            target,
+            cause,
+            location,
+            saved_handlers,
+        );
+        info!("Entering fault handler for {}", handle.fault);
+        for observer in &mut self.observers {
+            observer.on_fault(&handle.fault, &handle.location);
+        }
+        self.memory.push(handle.to_value());
+        self.handler = Some(handle);
+        self.kernel_mode = true;
+        Ok(())
+    }
+
+    fn end_fault(&mut self, handle: FaultHandle) -> Result<(), VMError> {
         let FaultHandle {
-            stored_pc,
-            stored_stack,
-            fault: _,
+            mut stored_pc,
+            mut stored_stack,
+            operands,
+            fault,
             target_function: _,
+            cause,
+            location,
+            stored_handlers,
         } = handle;
+        self.kernel_mode = false;
+        self.exception_handlers = stored_handlers;
 
+        let action = FaultAction::from_value(self.memory.pop().as_ref());
+        info!("Fault handler for {fault} returned {action:?}");
+        for observer in &mut self.observers {
+            observer.on_fault_end(&fault, action);
+        }
+        if action == FaultAction::Abort {
+            return Err(cause.unwrap_or(VMError::UnhandledFault { fault, location }));
+        }
+        let left = self.memory.take_stack();
+        if action == FaultAction::Retry && left.is_empty() {
+            stored_stack.extend(operands);
+        } else {
+            stored_stack.extend(left);
+        }
+        if action == FaultAction::Resume {
+            if let Some(pc) = stored_pc.last_mut() {
+                *pc += 1;
+            }
+        }
         self.counter_stack = stored_pc;
         self.memory.replace_stack(stored_stack);
+        Ok(())
     }
 
-    fn handle_native_fault(&mut self, _handle: &FaultHandle) {}
+    /// Runs from the top of the counter stack until the frame at its bottom returns, then pops the
+    /// exit code
+    fn continue_run(&mut self) -> Result<u32, VMError> {
+        self.cont = true;
+        let fiber = self.scheduler.current;
+        loop {
+            while self.cont && (1..=self.instructions.len() - 1).contains(&self.program_counter()) {
+                if self.scheduler.pending.is_some() && !self.in_fault() {
+                    self.switch_fibers()?;
+                    continue;
+                }
+                if self.can_run_compiled() {
+                    let ran = match self.engine {
+                        Engine::Threaded => self.run_threaded()?,
+                        Engine::Register => self.run_registers()?,
+                        Engine::Interpreter => false,
+                    };
+                    if ran {
+                        continue;
+                    }
+                }
+                self.check_limits()?;
+                let pc = self.program_counter();
+                let instructions = Rc::clone(&self.instructions);
+                let instruction = &instructions[pc];
+                info!(
+                    target: "virtual_machine",
+                    "[{function:^18}] 0x{pc:016X}: {asm: <24}  {top}",
+                    function=Identifier::abbreviate_identifier(self.pc_to_recent_id(pc), 18),
+                    asm=format!("{:?}", instruction),
+                    top=self.memory.stack().last().map(|s| format!("(top = {})", s)).unwrap_or(String::new())
+                );
+                if let Some(mut debugger) = self.debugger.take() {
+                    let result = debugger.before_instruction(self, pc);
+                    self.debugger = Some(debugger);
+                    result?;
+                }
+                for observer in &mut self.observers {
+                    observer.on_instruction(pc, instruction);
+                }
+                let next = match self.interpret_instruction(instruction, pc) {
+                    Ok(next) => next,
+                    Err(error) => {
+                        self.raise_fault(error)?;
+                        self.program_counter()
+                    }
+                };
+                self.set_program_counter(next);
+                self.scheduler.tick();
+                trace!(target: "virtual_machine", "vm: {:#?}", self);
+            }
+
+            match std::mem::replace(&mut self.handler, None) {
+                None if self.cont && !self.generators.resumers.is_empty() => {
+                    self.finish_generator()?
+                }
+                None if self.cont && self.scheduler.current != fiber => self.finish_fiber()?,
+                None => break,
+                Some(handle) => {
+                    self.end_fault(handle)?;
+                }
+            }
+        }
+        if self.counter_stack.is_empty() {
+            // fibers that haven't finished by the end of a run are dropped
+            self.scheduler = Scheduler::new(self.scheduler.preemption);
+        }
+        let output = match self.memory.pop() {
+            None => Err(VMError::NoExitCode),
+            Some(Value::UInteger(u)) => Ok(u as u32),
+            Some(v) => Err(VMError::ExitCodeInvalidType(v)),
+        };
+        output
+    }
 
     pub fn load_plugin<P: LoadablePlugin>(&mut self) {
         self.with_plugin(P::new())
@@ -453,12 +1074,23 @@ where
     ) -> Result<usize, VMError> {
         let mut next_instruction = instruction_pointer + 1;
         match bytecode {
-            Asm::Label(_) | Asm::PublicLabel(_) | Asm::Nop => {}
+            Asm::Label(_) | Asm::PublicLabel(_) | Asm::Static | Asm::Nop => {}
             Asm::Pop => {
-                self.memory.pop().unwrap();
+                self.pop()?;
             }
             Asm::Return => {
                 self.counter_stack.pop();
+                let depth = self.counter_stack.len();
+                for observer in &mut self.observers {
+                    observer.on_return(depth);
+                }
+                while self
+                    .exception_handlers
+                    .last()
+                    .map_or(false, |h| h.frame_depth > self.counter_stack.len())
+                {
+                    self.exception_handlers.pop();
+                }
                 let next = self
                     .counter_stack
                     .last()
@@ -469,131 +1101,140 @@ where
                 next_instruction = next;
             }
             Asm::Goto(location) => {
-                next_instruction = match location {
-                    &AsmLocation::ByteIndex(i) => i,
-                    &AsmLocation::InstructionDiff(diff) => {
-                        if diff > 0 {
-                            instruction_pointer + (diff as usize)
-                        } else {
-                            instruction_pointer - ((-diff) as usize)
-                        }
-                    }
-                    AsmLocation::Label(l) => *self
-                        .label_to_instruction
-                        .get(l)
-                        .expect(format!("No instruction found for label (label={})", l).as_str()),
-                }
+                next_instruction = self.jump_target(location, instruction_pointer)?;
             }
             Asm::CondGoto(location) => {
-                let pop = self.memory.pop().unwrap();
+                let pop = self.pop()?;
                 let cond = match pop {
                     Value::Byte(b) if b != 0 => true,
                     r @ Value::Reference(_) => !r.is_null_ptr(),
                     _ => false,
                 };
                 if cond {
-                    next_instruction = match location {
-                        &AsmLocation::ByteIndex(i) => i,
-                        &AsmLocation::InstructionDiff(diff) => {
-                            if diff > 0 {
-                                instruction_pointer + (diff as usize)
-                            } else {
-                                instruction_pointer - ((-diff) as usize)
-                            }
-                        }
-                        AsmLocation::Label(l) => *self.label_to_instruction.get(l).expect(
-                            format!("No instruction found for label (label={})", l).as_str(),
-                        ),
-                    }
+                    next_instruction = self.jump_target(location, instruction_pointer)?;
                 }
             }
             Asm::Halt => {
                 self.cont = false;
             }
+            Asm::PushHandler(location) => {
+                let target = self.jump_target(location, instruction_pointer)?;
+                let handler = ExceptionHandler::new(
+                    target,
+                    self.counter_stack.len(),
+                    self.memory.stack().len(),
+                    self.memory.scope_depth(),
+                );
+                self.exception_handlers.push(handler);
+            }
+            Asm::PopHandler => {
+                if self.exception_handlers.pop().is_none() {
+                    return Err(VMError::NoExceptionHandler {
+                        location: self.error_location(),
+                    });
+                }
+            }
+            Asm::Throw => {
+                let thrown = self.pop()?;
+                next_instruction = self.throw(thrown)?;
+            }
             Asm::Push(v) => {
                 self.memory.push(v.clone());
             }
             Asm::GetAttribute(attr) => {
-                let dict = self.memory.pop().expect("No value found on stack");
+                let dict = self.pop()?;
                 let val = match dict {
-                    Value::Dictionary(mut dict) => {
-                        dict.remove(attr.as_str()).expect("Attribute must exist")
-                    }
+                    Value::Dictionary(mut dict) => dict.remove(attr.as_str()),
                     Value::Reference(refr) => {
                         let inner = refr.borrow();
                         if let Value::Dictionary(dict) = &*inner {
-                            dict.get(attr.as_str())
-                                .expect("Attribute must exist")
-                                .clone()
+                            dict.get(attr.as_str()).cloned()
                         } else {
-                            return Err(VMError::InvalidType {
-                                value: inner.deref().clone(),
-                                expected: "Dictionary".to_string(),
-                            });
+                            return Err(self.type_mismatch(inner.deref().clone(), "Dictionary"));
                         }
                     }
                     v => {
-                        return Err(VMError::InvalidType {
-                            value: v,
-                            expected: "Dictionary".to_string(),
-                        });
+                        return Err(self.type_mismatch(v, "Dictionary"));
                     }
                 };
-                self.memory.push(val);
+                match val {
+                    Some(val) => self.memory.push(val),
+                    None => {
+                        return Err(VMError::MissingAttribute {
+                            attribute: attr.clone(),
+                            location: self.error_location(),
+                        })
+                    }
+                }
             }
             &Asm::SetVar(v) => {
-                let value = self
-                    .memory
-                    .pop()
-                    .expect("value expected from stack to save to ");
+                let value = self.pop()?;
                 self.memory.set_var(v as usize, value);
             }
             &Asm::GetVar(v) => {
-                let val = self
-                    .memory
-                    .get_var(v as usize)
-                    .expect(format!("no var set: {:#?}", self.memory).as_str());
+                let val = match self.memory.get_var(v as usize) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        return Err(VMError::VariableNotSet {
+                            var: v as usize,
+                            location: self.error_location(),
+                        })
+                    }
+                };
                 let as_jref = JRef::from(val);
                 let value: Value = Value::Reference(as_jref);
                 self.memory.push(value);
             }
-            &Asm::ClearVar(_v) => {}
-            Asm::GetSymbol(string) => match self.label_to_instruction.get(string) {
-                None => {
-                    self.fault(Fault::MissingSymbol(string.clone()));
+            &Asm::ClearVar(v) => {
+                if self.memory.clear_var(v as usize).is_err() {
+                    return Err(VMError::VariableNotSet {
+                        var: v as usize,
+                        location: self.error_location(),
+                    });
                 }
-                Some(_) => {
-                    let value = Value::Function(AsmLocation::Label(string.clone()));
-                    self.memory.push(value);
+            }
+            Asm::GetSymbol(string) => {
+                self.label_index(string)?;
+                let value = Value::Function(AsmLocation::Label(string.clone()));
+                self.memory.push(value);
+            }
+            Asm::SetSymbol(string) => {
+                self.require_kernel_mode()?;
+                let index = match self.pop()? {
+                    Value::Function(AsmLocation::ByteIndex(i)) => i,
+                    Value::Function(AsmLocation::Label(l)) => self.label_index(&l)?,
+                    v => return Err(self.type_mismatch(v, "function")),
+                };
+                self.label_to_instruction.insert(string.clone(), index);
+                self.relink(string);
+            }
+            Asm::Call(location, arity) => {
+                if let Some(next) = self.call_direct(location, *arity)? {
+                    next_instruction = next;
                 }
-            },
+            }
             Asm::SendMessage => {
-                let mut target = self
-                    .memory
-                    .pop()
-                    .expect("There should be a target value on the stack");
-                let message = if let Some(Value::Str(msg)) = self.memory.pop() {
-                    msg
-                } else {
-                    panic!("Message must exist and must be of type String")
+                let mut target = self.pop()?;
+                let message = match self.pop()? {
+                    Value::Str(msg) => msg,
+                    v => return Err(self.type_mismatch(v, "Str")),
                 };
-                let args = if let Some(Value::Array(args)) = self.memory.pop() {
-                    args
-                } else {
-                    panic!("Arguments must be an array of values")
+                let args = match self.pop()? {
+                    Value::Array(args) => args,
+                    v => return Err(self.type_mismatch(v, "Array")),
                 };
-                if let Some(next) = self.send_message(&mut target, &*message, args) {
+                for observer in &mut self.observers {
+                    observer.on_send_message(&target, &message, &args);
+                }
+                if let Some(next) = self.send_message(&mut target, &*message, args)? {
                     next_instruction = next;
                 }
             }
             Asm::IntoReference => {
                 let mut target = Value::Native;
                 let message = "ref";
-                let args = vec![self
-                    .memory
-                    .pop()
-                    .expect("There should be a target value on the stack")];
-                if let Some(next) = self.send_message(&mut target, message, args) {
+                let args = vec![self.pop()?];
+                if let Some(next) = self.send_message(&mut target, message, args)? {
                     next_instruction = next;
                 }
             }
@@ -602,103 +1243,161 @@ where
                 let message = &*msg;
                 let mut args = vec![];
                 for _ in 0..*count {
-                    args.push(
-                        self.memory
-                            .pop()
-                            .expect("Expected a value on the stack for native method call"),
-                    )
+                    args.push(self.pop()?)
                 }
-                if let Some(next) = self.send_message(&mut target, message, args) {
+                if let Some(next) = self.send_message(&mut target, message, args)? {
                     next_instruction = next;
                 }
             }
             &Asm::Pack(len) => {
                 let mut vector = VecDeque::with_capacity(len);
                 for _ in 0..len {
-                    vector.push_front(
-                        self.memory
-                            .pop()
-                            .expect("Tried to pop more values than available"),
-                    );
+                    vector.push_front(self.pop()?);
                 }
                 let vector = Vec::from(vector);
                 self.memory.push(Value::Array(vector));
             }
             boolean_asm @ (Asm::BooleanAnd | Asm::BooleanOr | Asm::BooleanXor) => {
-                let left = self.memory.pop().expect("couldn't pop");
-                let right = self.memory.pop().expect("couldn't pop");
-                if let (Value::Byte(left), Value::Byte(right)) = (left, right) {
-                    let left = left != 0;
-                    let right = right != 0;
-                    info!("Comparing {left} and {right} with op {boolean_asm:?}");
-                    let output = match boolean_asm {
-                        Asm::BooleanAnd => Value::from(left && right),
-                        Asm::BooleanOr => Value::from(left || right),
-                        Asm::BooleanXor => Value::from(left ^ right),
-                        _ => unreachable!(),
-                    };
-                    self.memory.push(output);
-                } else {
-                    return Err(anyhow!("Can only use two booleans for bi-boolean ops").into());
+                let left = self.pop()?;
+                let right = self.pop()?;
+                match (left, right) {
+                    (Value::Byte(left), Value::Byte(right)) => {
+                        let left = left != 0;
+                        let right = right != 0;
+                        info!("Comparing {left} and {right} with op {boolean_asm:?}");
+                        let output = match boolean_asm {
+                            Asm::BooleanAnd => Value::from(left && right),
+                            Asm::BooleanOr => Value::from(left || right),
+                            Asm::BooleanXor => Value::from(left ^ right),
+                            _ => unreachable!(),
+                        };
+                        self.memory.push(output);
+                    }
+                    (Value::Byte(_), v) | (v, _) => {
+                        return Err(self.type_mismatch(v, "boolean"));
+                    }
                 }
             }
-            asm @ (Asm::Subtract | Asm::Add | Asm::Multiply | Asm::Gt) => {
-                let left = self.memory.pop().expect("couldn't pop");
-                let right = self.memory.pop().expect("couldn't pop");
-                let output = match asm {
-                    Asm::Subtract => self.alu.sub(left, right),
-                    Asm::Add => self.alu.add(left, right),
-                    Asm::Multiply => self.alu.mult(left, right),
-                    Asm::Gt => self.alu.greater_than(left, right),
+            Asm::BooleanNot => match self.pop()? {
+                Value::Byte(b) => self.memory.push(Value::from(b == 0)),
+                v => return Err(self.type_mismatch(v, "boolean")),
+            },
+            asm @ (Asm::Subtract
+            | Asm::Add
+            | Asm::Multiply
+            | Asm::Divide
+            | Asm::Remainder
+            | Asm::And
+            | Asm::Or
+            | Asm::Xor
+            | Asm::ShiftLeft
+            | Asm::ShiftRight
+            | Asm::Gt) => {
+                let left = self.pop()?;
+                let right = self.pop()?;
+                let output = self.binary_arithmetic(left, right, |alu, left, right| match asm {
+                    Asm::Subtract => alu.sub(left, right),
+                    Asm::Add => alu.add(left, right),
+                    Asm::Multiply => alu.mult(left, right),
+                    Asm::Divide => alu.div(left, right),
+                    Asm::Remainder => alu.rem(left, right),
+                    Asm::And => alu.and(left, right),
+                    Asm::Or => alu.or(left, right),
+                    Asm::Xor => alu.xor(left, right),
+                    Asm::ShiftLeft => alu.shift_left(left, right),
+                    Asm::ShiftRight => alu.shift_right(left, right),
+                    Asm::Gt => alu.greater_than(left, right),
                     _ => unreachable!(),
-                };
+                })?;
                 self.memory.push(output);
             }
             Asm::Not => {
-                let v = self.memory.pop().unwrap();
-                let next = self.alu.not(v);
+                let v = self.pop()?;
+                let next = self.arithmetic(|alu| alu.not(v))?;
                 self.memory.push(next);
             }
-            Asm::Deref => {
-                let pop = self.memory.pop().unwrap();
-                if let Value::Reference(reference) = pop {
+            Asm::Clear => {
+                self.memory.take_stack();
+            }
+            Asm::GetRef => {
+                let value = self.pop()?;
+                let reference = match value {
+                    r @ Value::Reference(_) => r,
+                    v => self.allocate(v),
+                };
+                self.memory.push(reference);
+            }
+            &Asm::Index(index) => {
+                let array = self.pop()?;
+                let val = match array {
+                    Value::Array(mut array) if index < array.len() => array.swap_remove(index),
+                    Value::Reference(refr) => {
+                        let inner = refr.borrow();
+                        match &*inner {
+                            Value::Array(array) if index < array.len() => array[index].clone(),
+                            Value::Array(array) => {
+                                return Err(VMError::IndexOutOfBounds {
+                                    index,
+                                    len: array.len(),
+                                    location: self.error_location(),
+                                });
+                            }
+                            v => {
+                                return Err(self.type_mismatch(v.clone(), "Array"));
+                            }
+                        }
+                    }
+                    Value::Array(array) => {
+                        return Err(VMError::IndexOutOfBounds {
+                            index,
+                            len: array.len(),
+                            location: self.error_location(),
+                        });
+                    }
+                    v => {
+                        return Err(self.type_mismatch(v, "Array"));
+                    }
+                };
+                self.memory.push(val);
+            }
+            Asm::Deref => match self.pop()? {
+                Value::Reference(reference) => {
                     let derefed = reference.borrow().clone();
                     self.memory.push(derefed);
-                } else {
-                    panic!("Can only deref pointers (found: {:?})", pop)
                 }
-            }
+                v => return Err(self.bad_reference(v)),
+            },
             Asm::Boolify => {
-                let pop = self.memory.pop().unwrap();
+                let pop = self.pop()?;
                 let as_bool: bool = match pop {
                     Value::Byte(b) => b != 0,
                     Value::Integer(i) => i != 0,
                     Value::UInteger(i) => i != 0,
                     Value::Reference(r) => !r.borrow().is_null_ptr(),
-                    v => panic!("Value can not be boolified (value: {})", v),
+                    v => return Err(self.type_mismatch(v, "boolifiable value")),
                 };
                 self.memory.push(Value::Byte(as_bool as u8));
             }
             Asm::GT0 => {
-                let pop = self.memory.pop().unwrap();
+                let pop = self.pop()?;
                 let boolean = match pop {
                     Value::Byte(b) => b > 0,
                     Value::Float(f) => f > 0.0,
                     Value::Integer(i) => i > 0,
                     Value::UInteger(u) => u > 0,
-                    v => panic!("Invalid value to check if > 0 (value: {})", v),
+                    v => return Err(self.type_mismatch(v, "numeric")),
                 };
                 self.memory.push(Value::from(boolean));
             }
             Asm::SetRef => {
-                let ptr = self.memory.pop().unwrap();
-                let value = self.memory.pop().unwrap();
+                let ptr = self.pop()?;
+                let value = self.pop()?;
                 match ptr {
                     Value::Reference(r) => {
                         let mut borrowed = r.borrow_mut();
                         *borrowed = value;
                     }
-                    other => panic!("Invalid value for set ref (expected ref, found = {other})"),
+                    other => return Err(self.bad_reference(other)),
                 }
                 info!(
                     "VARS: {:#?}",
@@ -709,7 +1408,12 @@ where
                         .collect::<HashMap<usize, String>>()
                 );
             }
-            a => panic!("Invalid instruction: {:?}", a),
+            a => {
+                return Err(VMError::InvalidInstruction {
+                    asm: a.clone(),
+                    location: self.error_location(),
+                })
+            }
         }
         Ok(next_instruction)
     }
@@ -719,10 +1423,22 @@ where
     }
 
     fn load<Assembly: GetAsm>(&mut self, asm: Assembly) {
+        if let Err(error) = self.try_load(asm) {
+            panic!("{error}")
+        }
+    }
+
+    fn try_load<Assembly: GetAsm>(&mut self, asm: Assembly) -> Result<(), VMError> {
         let start_index = self.instructions.len();
         let as_asm = asm.get_asm();
+        self.check_labels(&as_asm)?;
+        if let Some(debug_info) = asm.debug_info() {
+            self.debug_info
+                .push((start_index..start_index + as_asm.len(), debug_info));
+        }
         let mut new_labels = map![];
         let mut static_instructions = set![];
+        let mut loaded = Vec::with_capacity(as_asm.len());
         for (index, asm) in as_asm.into_iter().enumerate() {
             let mut label: Option<&String> = None;
             let mut is_static = false;
@@ -743,12 +1459,9 @@ where
                 let label_index = start_index + index;
                 match self.label_to_instruction.entry(asm_label.clone()) {
                     Entry::Occupied(mut occupant) => {
-                        if occupant.key().starts_with("@@") {
-                            occupant.insert(label_index);
-                            new_labels.insert(asm_label.clone(), label_index);
-                        } else {
-                            panic!("label {:?} already registered", asm_label);
-                        }
+                        // checked to be an overridable label before anything was loaded
+                        occupant.insert(label_index);
+                        new_labels.insert(asm_label.clone(), label_index);
                     }
                     Entry::Vacant(v) => {
                         v.insert(label_index);
@@ -760,97 +1473,70 @@ where
                 static_instructions.insert(start_index + index);
             }
 
-            self.instructions.push(asm);
+            loaded.push(asm);
         }
+        Rc::make_mut(&mut self.instructions).extend(loaded);
         info!("Created new labels = {:?}", new_labels);
+        self.link(start_index);
+        for label in new_labels.keys() {
+            self.relink(label);
+        }
+        self.compile_threaded();
+        self.register_code = None;
 
         for static_instruction_index in static_instructions {
             info!("Running static code at {static_instruction_index}");
-            self.run_from_index(static_instruction_index);
+            self.run_from_index(static_instruction_index)?;
         }
+        Ok(())
     }
 
     fn load_static<Assembly: GetAsm>(&mut self, asm: Assembly) {
+        if let Err(error) = self.try_load_static(asm) {
+            panic!("{error}")
+        }
+    }
+
+    fn try_load_static<Assembly: GetAsm>(&mut self, asm: Assembly) -> Result<(), VMError> {
         let start_index = self.instructions.len();
-        self.load(asm);
+        self.try_load(asm)?;
+        let depth = self.memory.scope_depth();
         self.memory.global_scope();
-        if self
-            .run_from_index(start_index)
-            .expect("VM Error encountered")
-            != 0
-        {
-            panic!("VM Failed")
+        self.kernel_mode = true;
+        let result = self.run_from_index(start_index);
+        self.kernel_mode = false;
+        self.memory.unwind_scopes(depth);
+        match result? {
+            0 => Ok(()),
+            code => Err(VMError::StaticCodeFailed(code)),
         }
-        self.memory.back_scope();
     }
 
     fn run(&mut self, start_label: &str) -> Result<u32, VMError> {
-        let start_counter = self.label_to_instruction[start_label];
+        let start_counter = self.label_index(start_label)?;
         self.run_from_index(start_counter)
     }
 
     fn run_from_index(&mut self, index: usize) -> Result<u32, VMError> {
-        self.cont = true;
         self.counter_stack.push(index);
-        loop {
-            while self.cont && (1..=self.instructions.len() - 1).contains(&self.program_counter()) {
-                let pc = self.program_counter();
-                let ref instruction = self.instructions[pc].clone();
-                info!(
-                    target: "virtual_machine",
-                    "[{function:^18}] 0x{pc:016X}: {asm: <24}  {top}",
-                    function=Identifier::abbreviate_identifier(self.pc_to_recent_id(pc), 18),
-                    asm=format!("{:?}", instruction),
-                    top=self.memory.stack().last().map(|s| format!("(top = {})", s)).unwrap_or(String::new())
-                );
-                let next = self.interpret_instruction(instruction, pc)?;
-                self.set_program_counter(next);
-                trace!(target: "virtual_machine", "vm: {:#?}", self);
-            }
-
-            match std::mem::replace(&mut self.handler, None) {
-                None => break,
-                Some(handle) => {
-                    self.kernel_mode = false;
-                    self.end_fault(handle);
-                }
+        if !self.observers.is_empty() {
+            let function = self.most_recent_public_label(index).cloned();
+            let depth = self.counter_stack.len();
+            for observer in &mut self.observers {
+                observer.on_call(index, function.as_deref(), depth);
             }
         }
-        let output = match self.memory.pop() {
-            None => Err(VMError::NoExitCode),
-            Some(Value::UInteger(u)) => Ok(u as u32),
-            Some(v) => Err(VMError::ExitCodeInvalidType(v)),
-        };
-        output
+        self.continue_run()
     }
 
-    fn fault(&mut self, fault: Fault) {
-        let target = self.fault_table.get_fault_jump(&fault);
-
-        let saved_counter = std::mem::replace(&mut self.counter_stack, vec![0]);
-        let saved_stack = self.memory.take_stack();
-        let handle = FaultHandle::new(saved_counter, saved_stack, fault, target.clone());
-
-        let next_pc = match &target {
-            Value::Function(AsmLocation::Label(s)) => {
-                match self.label_to_instruction.entry(s.clone()) {
-                    Entry::Occupied(v) => *v.get(),
-                    Entry::Vacant(_) => {
-                        self.fault(Fault::DoubleFault);
-                        return;
-                    }
-                }
-            }
-            Value::Native => {
-                self.kernel_mode = true;
-                self.handle_native_fault(&handle);
-                0
-            }
-            v => panic!("Invalid value for fault jump target (value = {:?})", v),
-        };
-        self.handler = Some(handle);
-        self.counter_stack.push(next_pc);
-        self.kernel_mode = true;
+    fn fault(&mut self, fault: Fault) -> Result<(), VMError> {
+        if self.in_fault() {
+            return Err(VMError::DoubleFault(Box::new(VMError::UnhandledFault {
+                fault,
+                location: self.error_location(),
+            })));
+        }
+        self.enter_fault(fault, None, vec![])
     }
 
     fn is_kernel_mode(&self) -> bool {
@@ -858,13 +1544,62 @@ where
     }
 }
 
+/// How the vm executes instructions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Engine {
+    /// Interprets each instruction as it's executed
+    Interpreter,
+    /// Compiles loaded instructions into [threaded code](threaded) ahead of time
+    Threaded,
+    /// Translates loaded instructions into a [register machine form](registers), which keeps
+    /// values in registers instead of on the stack within basic blocks
+    Register,
+}
+
+impl Default for Engine {
+    /// The interpreter, unless the `threaded` or `registers` feature is enabled
+    fn default() -> Self {
+        if cfg!(feature = "threaded") {
+            Engine::Threaded
+        } else if cfg!(feature = "registers") {
+            Engine::Register
+        } else {
+            Engine::Interpreter
+        }
+    }
+}
+
+/// What a call runs
+#[derive(Debug)]
+enum CallTarget {
+    /// A function loaded into the vm, starting at an instruction
+    Instruction(usize),
+    /// A function provided by a plugin
+    Plugin(String),
+}
+
 pub struct VMBuilder<'l, A, M> {
     arithmetic: Option<A>,
     memory: Option<M>,
-    stdin: Option<Box<dyn Read + 'l>>,
+    stdin: Option<Box<dyn BufRead + 'l>>,
     stdout: Option<Box<dyn Write + 'l>>,
     stderr: Option<Box<dyn Write + 'l>>,
     object_path: Vec<PathBuf>,
+    debugger: Option<Debugger<'l>>,
+    observers: Vec<Box<dyn VMObserver + 'l>>,
+    limits: ExecutionLimits,
+    engine: Engine,
+    collection_policy: CollectionPolicy,
+    preemption: Option<u64>,
+    tracer: Option<Tracer>,
+    natives: Vec<(
+        String,
+        Arity,
+        Option<Capability>,
+        Rc<dyn NativeFunction + 'l>,
+    )>,
+    file_root: Option<PathBuf>,
+    permissions: Permissions,
 }
 
 impl<'l, A: ArithmeticsTrait, M: MemoryTrait> VMBuilder<'l, A, M> {
@@ -876,24 +1611,62 @@ impl<'l, A: ArithmeticsTrait, M: MemoryTrait> VMBuilder<'l, A, M> {
             stdout,
             stderr,
             object_path,
+            debugger,
+            observers,
+            limits,
+            engine,
+            collection_policy,
+            preemption,
+            tracer,
+            natives,
+            file_root,
+            permissions,
         } = self;
         let mut vm = VM {
             memory: memory.expect("Memory module must be set"),
             alu: arithmetic.expect("Arithmetic module must be set"),
             cont: false,
-            instructions: vec![Asm::Nop],
+            instructions: Rc::new(vec![Asm::Nop]),
             label_to_instruction: Default::default(),
+            label_references: Default::default(),
             counter_stack: vec![],
+            debug_info: vec![],
             stdin,
             stdout,
             stderr,
             next_anonymous_function: Default::default(),
 
             handler: None,
+            fault_operands: vec![],
+            exception_handlers: vec![],
+            own_loads: vec![],
             fault_table: Default::default(),
             kernel_mode: false,
+            debugger,
+            observers,
+            limits,
+            executed_instructions: 0,
+            deadline_countdown: DEADLINE_CHECK_INTERVAL,
+            engine,
+            threaded: match engine {
+                Engine::Threaded => Some(Rc::new(vec![])),
+                _ => None,
+            },
+            register_code: None,
+            registers: vec![Value::Empty; REGISTER_COUNT],
+            heap: Heap::new(collection_policy),
+            files: FileTable::new(file_root.as_deref())?,
+            scheduler: Scheduler::new(preemption),
+            generators: Generators::default(),
+            tracer,
+            natives: builtin_natives(),
+            permissions,
             plugin_manager: Arc::new(RwLock::new(PluginManager::new())),
         };
+        for (name, arity, capability, function) in natives {
+            vm.natives
+                .insert(name, (arity, capability, Native::Function(function)));
+        }
         for obj_path in object_path {
             obj_path.try_load_into_vm(&mut vm)?;
         }
@@ -910,11 +1683,21 @@ impl<'l, A, M> VMBuilder<'l, A, M> {
             stdout: None,
             stderr: None,
             object_path: vec![],
+            debugger: None,
+            observers: vec![],
+            limits: ExecutionLimits::default(),
+            engine: Engine::default(),
+            collection_policy: CollectionPolicy::default(),
+            preemption: None,
+            tracer: None,
+            natives: vec![],
+            file_root: None,
+            permissions: Permissions::default(),
         }
     }
 
     pub fn with_stdin<R: Read + 'l>(mut self, reader: R) -> Self {
-        self.stdin = Some(Box::new(reader));
+        self.stdin = Some(Box::new(BufReader::new(reader)));
         self
     }
 
@@ -933,6 +1716,132 @@ impl<'l, A, M> VMBuilder<'l, A, M> {
         self.object_path.push(as_path);
         self
     }
+
+    /// Attaches a debugger to the built vm
+    pub fn debugger(mut self, debugger: Debugger<'l>) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
+
+    /// Sets how the built vm executes instructions
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Sets when the heap of the built vm collects cycles
+    pub fn collection_policy(mut self, policy: CollectionPolicy) -> Self {
+        self.collection_policy = policy;
+        self
+    }
+
+    /// Preempts a fiber once it has run this many instructions, if another fiber is ready to run.
+    /// Only the interpreter can preempt fibers, so it's used while this is set.
+    pub fn preemption(mut self, instructions: u64) -> Self {
+        self.preemption = Some(instructions);
+        self
+    }
+
+    /// Records the calls with outside effects the built vm makes, which can then be saved from
+    /// [VM::trace]
+    pub fn record(mut self) -> Self {
+        self.tracer = Some(Tracer::Recording(Trace::default()));
+        self
+    }
+
+    /// Answers the calls with outside effects the built vm makes from a recorded trace instead of
+    /// making them
+    pub fn replay(mut self, trace: Trace) -> Self {
+        self.tracer = Some(Tracer::Replaying { trace, next: 0 });
+        self
+    }
+
+    /// Sets every execution limit of the built vm at once
+    pub fn limits(mut self, limits: ExecutionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits the number of instructions the vm can execute
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.limits.fuel = Some(fuel);
+        self
+    }
+
+    /// Sets the deepest the counter stack can get before a stack overflow occurs. Defaults to
+    /// [MAX_CALL_DEPTH].
+    pub fn max_call_depth(mut self, depth: usize) -> Self {
+        self.limits.max_call_depth = depth;
+        self
+    }
+
+    /// Limits the number of values that can be on the operand stack
+    pub fn max_stack_size(mut self, size: usize) -> Self {
+        self.limits.max_stack_size = Some(size);
+        self
+    }
+
+    /// Sets when the vm must stop running by
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.limits.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline of the vm to be `timeout` from now
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// Lets the [file natives](crate::files) of the built vm use the files under a directory, and
+    /// nothing outside of it
+    pub fn file_root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.file_root = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Registers a [native](crate::natives) with the built vm, replacing any native with the same
+    /// name
+    pub fn native<F: NativeFunction + 'l>(
+        mut self,
+        name: impl AsRef<str>,
+        arity: Arity,
+        function: F,
+    ) -> Self {
+        self.natives
+            .push((name.as_ref().to_string(), arity, None, Rc::new(function)));
+        self
+    }
+
+    /// Registers a native like [native](Self::native), which the built vm can only call if its
+    /// [permissions](crate::permissions) allow a capability
+    pub fn native_in<F: NativeFunction + 'l>(
+        mut self,
+        capability: Capability,
+        name: impl AsRef<str>,
+        arity: Arity,
+        function: F,
+    ) -> Self {
+        self.natives.push((
+            name.as_ref().to_string(),
+            arity,
+            Some(capability),
+            Rc::new(function),
+        ));
+        self
+    }
+
+    /// Sets which [capabilities](crate::permissions) the built vm has. It has all of them unless
+    /// this is called.
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Registers an observer that is notified of events as the vm runs
+    pub fn observer<O: VMObserver + 'l>(mut self, observer: O) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
 }
 
 impl<A: ArithmeticsTrait, M> VMBuilder<'_, A, M> {
@@ -983,21 +1892,42 @@ impl<'vm, M: MemoryTrait> Stack for VMStack<'vm, M> {
 
 pub struct DefaultVmHandle<'a, 'vm, A: ArithmeticsTrait, M: MemoryTrait> {
     vm: &'a mut VM<'vm, M, A>,
+    /// The first error produced by a native method invoked through this handle
+    pub error: Option<VMError>,
 }
 
 impl<'a, 'vm, A: ArithmeticsTrait, M: MemoryTrait> VMHandle for DefaultVmHandle<'a, 'vm, A, M> {
     fn native(&mut self, method: &str, values: &[Value], output: &mut Option<Value>) {
-        self.vm.native_method(method, Vec::from(values));
+        if let Err(e) = self.vm.native_method(method, Vec::from(values)) {
+            self.error.get_or_insert(e);
+            return;
+        }
         if !method.starts_with("@") {
             *output = self.vm.memory.pop();
         }
     }
+
+    fn stack(&mut self) -> &mut dyn Stack {
+        self
+    }
+}
+
+impl<'a, 'vm, A: ArithmeticsTrait, M: MemoryTrait> Stack for DefaultVmHandle<'a, 'vm, A, M> {
+    fn empty(&self) -> bool {
+        self.vm.memory.stack().is_empty()
+    }
+
+    fn push(&mut self, value: Value) {
+        self.vm.memory.push(value);
+    }
+
+    fn pop(&mut self, output: &mut Option<Value>) {
+        *output = self.vm.memory.pop();
+    }
 }
 
 impl<'a, 'vm, A: ArithmeticsTrait, M: MemoryTrait> DefaultVmHandle<'a, 'vm, A, M> {
-    pub fn new(vm: &VM<'vm, M, A>) -> Self {
-        Self {
-            vm: unsafe { &mut *(vm as *const _ as *mut _) },
-        }
+    pub fn new(vm: &'a mut VM<'vm, M, A>) -> Self {
+        Self { vm, error: None }
     }
 }
@@ -0,0 +1,180 @@
+//! Switching between [fibers](crate::fiber).
+//!
+//! Natives only ask for a switch. It's made by the run loop before the next instruction, so the
+//! instruction that asked for it always finishes on the fiber it started on.
+
+use super::{CallTarget, VM};
+use crate::error::VMError;
+use crate::fiber::{Context, FiberId, SuspendedFiber, Switch};
+use crate::{ArithmeticsTrait, MemoryTrait};
+use jodin_common::assembly::value::Value;
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// The fiber that's running
+    pub fn current_fiber(&self) -> FiberId {
+        self.scheduler.current
+    }
+
+    /// Creates a fiber that calls a function with arguments once it's scheduled
+    pub(super) fn spawn_fiber(
+        &mut self,
+        function: Value,
+        args: Vec<Value>,
+    ) -> Result<FiberId, VMError> {
+        let context = self.new_context("@spawn", function, args)?;
+        let id = self.scheduler.next_id();
+        debug!(
+            "Spawned fiber {id} at instruction {}",
+            context.counter_stack[1]
+        );
+        self.scheduler.ready.push_back(SuspendedFiber {
+            id,
+            context,
+            resumers: vec![],
+        });
+        Ok(id)
+    }
+
+    /// Creates a context that calls a function with arguments once it's resumed
+    pub(super) fn new_context(
+        &mut self,
+        native: &str,
+        function: Value,
+        mut args: Vec<Value>,
+    ) -> Result<Context, VMError> {
+        let pc = match &function {
+            Value::Function(location) => match self.call_target(location)? {
+                CallTarget::Instruction(pc) => pc,
+                CallTarget::Plugin(_) => {
+                    return Err(self
+                        .invalid_native_arguments(native, "plugin functions can't be suspended"))
+                }
+            },
+            _ => return Err(self.type_mismatch(function, "function")),
+        };
+        // taking the scopes a second time takes the fresh scopes left by the first
+        let current = self.memory.take_scopes();
+        let scopes = self.memory.take_scopes();
+        self.memory.replace_scopes(current);
+
+        args.reverse();
+        Ok(Context {
+            counter_stack: vec![0, pc],
+            stack: args,
+            exception_handlers: vec![],
+            own_loads: vec![],
+            scopes,
+        })
+    }
+
+    /// Waits for a fiber to finish, pushing the value it returned
+    pub(super) fn join_fiber(&mut self, id: FiberId) -> Result<(), VMError> {
+        if let Some(value) = self.scheduler.results.remove(&id) {
+            self.memory.push(value);
+            return Ok(());
+        }
+        if id == self.scheduler.current {
+            return Err(self.invalid_native_arguments("@join", "a fiber can't join itself"));
+        }
+        if !self.scheduler.alive(id) {
+            return Err(self.invalid_native_arguments("@join", format!("no fiber {id} to join")));
+        }
+        if self
+            .scheduler
+            .joining
+            .values()
+            .any(|(target, _)| *target == id)
+        {
+            return Err(
+                self.invalid_native_arguments("@join", format!("fiber {id} is already joined"))
+            );
+        }
+        self.scheduler.pending = Some(Switch::Join(id));
+        Ok(())
+    }
+
+    /// Makes the switch the running fiber or generator asked for, if there is one
+    pub(super) fn switch_fibers(&mut self) -> Result<(), VMError> {
+        let switch = match self.scheduler.pending.take() {
+            Some(switch) => switch,
+            None => return Ok(()),
+        };
+        // only needed if there's a deadlock, so the trace is resolved then
+        let counter_stack = self.counter_stack.clone();
+        match switch {
+            Switch::Yield => {
+                let fiber = self.suspend_fiber();
+                self.scheduler.ready.push_back(fiber);
+            }
+            Switch::Join(target) => {
+                let fiber = self.suspend_fiber();
+                self.scheduler.joining.insert(fiber.id, (target, fiber));
+            }
+            Switch::Resume(id, resumption) => return self.resume_generator(id, resumption),
+            Switch::YieldValue(value) => return self.yield_from_generator(value),
+        }
+        self.resume_fiber(&counter_stack)
+    }
+
+    /// Ends the running fiber, which has returned, and resumes the next one
+    pub(super) fn finish_fiber(&mut self) -> Result<(), VMError> {
+        // only needed if there's a deadlock, so the trace is resolved then
+        let counter_stack = self.counter_stack.clone();
+        let value = self.memory.pop().unwrap_or(Value::Empty);
+        let id = self.scheduler.current;
+        debug!("Fiber {id} returned {value}");
+        self.scheduler.finish(id, value);
+        self.counter_stack.clear();
+        self.memory.take_stack();
+        self.exception_handlers.clear();
+        self.own_loads.clear();
+        self.resume_fiber(&counter_stack)
+    }
+
+    fn suspend_fiber(&mut self) -> SuspendedFiber {
+        SuspendedFiber {
+            id: self.scheduler.current,
+            context: self.take_context(),
+            resumers: std::mem::take(&mut self.generators.resumers),
+        }
+    }
+
+    /// Takes the state of whatever is running, leaving the vm empty
+    pub(super) fn take_context(&mut self) -> Context {
+        Context {
+            counter_stack: std::mem::take(&mut self.counter_stack),
+            stack: self.memory.take_stack(),
+            exception_handlers: std::mem::take(&mut self.exception_handlers),
+            own_loads: std::mem::take(&mut self.own_loads),
+            scopes: self.memory.take_scopes(),
+        }
+    }
+
+    pub(super) fn restore_context(&mut self, context: Context) {
+        self.counter_stack = context.counter_stack;
+        self.memory.replace_stack(context.stack);
+        self.exception_handlers = context.exception_handlers;
+        self.own_loads = context.own_loads;
+        self.memory.replace_scopes(context.scopes);
+    }
+
+    /// Resumes the next fiber that's ready to run, failing with the location at the top of
+    /// `counter_stack` if there are none
+    fn resume_fiber(&mut self, counter_stack: &[usize]) -> Result<(), VMError> {
+        let fiber = match self.scheduler.ready.pop_front() {
+            Some(fiber) => fiber,
+            None => {
+                return Err(VMError::Deadlock {
+                    location: self.location_in(counter_stack),
+                })
+            }
+        };
+        trace!("Resuming fiber {}", fiber.id);
+        self.scheduler.current = fiber.id;
+        self.scheduler.pending = None;
+        self.scheduler.slice = self.scheduler.preemption.unwrap_or(0);
+        self.restore_context(fiber.context);
+        self.generators.resumers = fiber.resumers;
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+//! The [file](crate::files) natives, which use the file table of the vm.
+
+use super::VM;
+use crate::error::VMError;
+use crate::input::{eof, failure, success};
+use crate::permissions::Capability;
+use crate::{ArithmeticsTrait, MemoryTrait};
+use jodin_common::assembly::value::Value;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{stderr, stdout, Read, Seek, SeekFrom, Write};
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// Opens a file (arg 1) in a mode (arg 2)
+    pub(super) fn open_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let path = self.str_arg(native, &mut args)?;
+        let mode = self.str_arg(native, &mut args)?;
+        let mut options = OpenOptions::new();
+        match &*mode {
+            "read" => options.read(true),
+            "write" => options.write(true).create(true).truncate(true),
+            "append" => options.append(true).create(true),
+            "create" => options.write(true).create_new(true),
+            _ => {
+                return Err(self.invalid_native_arguments(
+                    native,
+                    format!("{mode:?} is not a mode files can be opened in"),
+                ))
+            }
+        };
+        let result = self
+            .files
+            .resolve(&path)
+            .and_then(|path| options.open(path).map_err(|e| e.to_string()))
+            .map(|file| Value::UInteger(self.files.insert(file)));
+        self.answer(result);
+        Ok(())
+    }
+
+    /// Reads the rest of a file (arg 1), or up to a number of bytes (arg 2) of it
+    pub(super) fn read_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let fd = self.fd_arg(native, &mut args)?;
+        let count = match args.is_empty() {
+            true => None,
+            false => Some(self.index_arg(native, &mut args)?),
+        };
+        let file = self.files.get(fd).expect("descriptor should be open");
+        let result = match count {
+            None => {
+                let mut s = String::new();
+                match file.read_to_string(&mut s) {
+                    Ok(0) => eof(),
+                    Ok(_) => success(Value::Str(s)),
+                    Err(e) => failure(e),
+                }
+            }
+            Some(count) => {
+                let mut bytes = vec![];
+                match file.take(count as u64).read_to_end(&mut bytes) {
+                    Ok(0) if count > 0 => eof(),
+                    Ok(_) => success(Value::Array(bytes.into_iter().map(Value::Byte).collect())),
+                    Err(e) => failure(e),
+                }
+            }
+        };
+        self.memory.push(result);
+        Ok(())
+    }
+
+    /// Writes a string (arg 2) to stdout, stderr or a file, picked by a file descriptor (arg 1)
+    pub(super) fn write_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let fd = match self.native_arg(native, &mut args)? {
+            Value::UInteger(fd) => fd,
+            _ => {
+                return Err(self.invalid_native_arguments(
+                    native,
+                    "file descriptors should only be unsigned ints",
+                ))
+            }
+        };
+        let s = match self.native_arg(native, &mut args)? {
+            Value::Str(s) => s,
+            _ => {
+                return Err(self.invalid_native_arguments(
+                    native,
+                    "can only pass strings to the write function",
+                ))
+            }
+        };
+        match fd {
+            1 | 2 => self.require_capability(Capability::Console, native)?,
+            _ => self.require_capability(Capability::Filesystem, native)?,
+        }
+        match fd {
+            1 => match &mut self.stdout {
+                Some(output) => write!(output, "{}", s)?,
+                None => write!(stdout(), "{}", s)?,
+            },
+            2 => match &mut self.stderr {
+                Some(output) => write!(output, "{}", s)?,
+                None => write!(stderr(), "{}", s)?,
+            },
+            fd => {
+                let result = match self.files.get(fd) {
+                    Some(file) => write!(file, "{}", s),
+                    None => {
+                        return Err(self.invalid_native_arguments(
+                            native,
+                            format!("{} is not a valid file descriptor for writing", fd),
+                        ))
+                    }
+                };
+                self.answer(result.map(|_| Value::Empty).map_err(|e| e.to_string()));
+                return Ok(());
+            }
+        }
+        self.memory.push(Value::Empty);
+        Ok(())
+    }
+
+    /// Moves to an offset (arg 2) from the start, current offset or end (arg 3) of a file (arg 1)
+    pub(super) fn seek_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let fd = self.fd_arg(native, &mut args)?;
+        let offset = match self.native_arg(native, &mut args)? {
+            Value::Integer(offset) => offset,
+            Value::UInteger(offset) => offset as i64,
+            v => return Err(self.type_mismatch(v, "offset")),
+        };
+        let from = match args.is_empty() {
+            true => "start".to_string(),
+            false => self.str_arg(native, &mut args)?,
+        };
+        let seek = match &*from {
+            "start" if offset >= 0 => SeekFrom::Start(offset as u64),
+            "current" => SeekFrom::Current(offset),
+            "end" => SeekFrom::End(offset),
+            "start" => {
+                return Err(self
+                    .invalid_native_arguments(native, "can not seek before the start of a file"))
+            }
+            _ => {
+                return Err(
+                    self.invalid_native_arguments(native, format!("can not seek from {from:?}"))
+                )
+            }
+        };
+        let file = self.files.get(fd).expect("descriptor should be open");
+        let result = file
+            .seek(seek)
+            .map(Value::UInteger)
+            .map_err(|e| e.to_string());
+        self.answer(result);
+        Ok(())
+    }
+
+    /// Closes a file (arg 1)
+    pub(super) fn close_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let fd = self.fd_arg(native, &mut args)?;
+        self.files.close(fd);
+        self.memory.push(Value::Empty);
+        Ok(())
+    }
+
+    /// Describes a file or directory (arg 1)
+    pub(super) fn stat_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let path = self.str_arg(native, &mut args)?;
+        let result = self.files.resolve(&path).and_then(|path| {
+            let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+            Ok(Value::from([
+                ("size", Value::UInteger(metadata.len())),
+                ("is_file", Value::from(metadata.is_file())),
+                ("is_dir", Value::from(metadata.is_dir())),
+            ]))
+        });
+        self.answer(result);
+        Ok(())
+    }
+
+    /// Lists the names of the entries of a directory (arg 1)
+    pub(super) fn list_dir_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let path = self.str_arg(native, &mut args)?;
+        let result = self.files.resolve(&path).and_then(|path| {
+            let mut names = fs::read_dir(path)
+                .and_then(|entries| {
+                    entries
+                        .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+                        .collect::<Result<Vec<_>, std::io::Error>>()
+                })
+                .map_err(|e| e.to_string())?;
+            names.sort();
+            Ok(Value::Array(names.into_iter().map(Value::Str).collect()))
+        });
+        self.answer(result);
+        Ok(())
+    }
+
+    /// Removes a file or an empty directory (arg 1)
+    pub(super) fn remove_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let path = self.str_arg(native, &mut args)?;
+        let root = self.files.root().map(|root| root.to_path_buf());
+        let result = self.files.resolve(&path).and_then(|path| {
+            if Some(&path) == root.as_ref() {
+                return Err("can not remove the file root".to_string());
+            }
+            match path.is_dir() {
+                true => fs::remove_dir(path),
+                false => fs::remove_file(path),
+            }
+            .map(|_| Value::Empty)
+            .map_err(|e| e.to_string())
+        });
+        self.answer(result);
+        Ok(())
+    }
+
+    /// Takes the next argument of a native as the descriptor of an open file
+    fn fd_arg(&self, native: &str, args: &mut Vec<Value>) -> Result<u64, VMError> {
+        match self.native_arg(native, args)? {
+            Value::UInteger(fd) if self.files.is_open(fd) => Ok(fd),
+            Value::UInteger(fd) => Err(self.invalid_native_arguments(
+                native,
+                format!("{fd} is not the descriptor of an open file"),
+            )),
+            v => Err(self.type_mismatch(v, "file descriptor")),
+        }
+    }
+
+    /// Pushes the result of a file native
+    fn answer(&mut self, result: Result<Value, String>) {
+        self.memory.push(match result {
+            Ok(value) => success(value),
+            Err(reason) => failure(reason),
+        });
+    }
+}
@@ -0,0 +1,153 @@
+//! Resuming and suspending [generators](crate::generator).
+//!
+//! Generators are resumed and yield through the same pending switch as fibers, so the instruction
+//! that resumed a generator finishes before it starts running.
+
+use super::VM;
+use crate::error::VMError;
+use crate::fiber::Switch;
+use crate::generator::{Generator, GeneratorId, Resumer, Resumption, GENERATOR_ID};
+use crate::{ArithmeticsTrait, MemoryTrait};
+use jodin_common::assembly::value::Value;
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// Creates a generator that calls a function with arguments once it's first resumed, returning
+    /// its object
+    pub(super) fn create_generator(
+        &mut self,
+        function: Value,
+        args: Vec<Value>,
+    ) -> Result<Value, VMError> {
+        let context = self.new_context("@generator", function, args)?;
+        let id = self.generators.next_id();
+        let object = self.allocate(Value::from([(GENERATOR_ID, Value::UInteger(id))]));
+        if let Value::Reference(reference) = &object {
+            self.generators
+                .insert(id, Generator::new(reference, context));
+        }
+        debug!("Created generator {id}");
+        Ok(object)
+    }
+
+    /// Answers a message sent to a generator object
+    pub(super) fn generator_message(
+        &mut self,
+        id: GeneratorId,
+        message: &str,
+    ) -> Result<(), VMError> {
+        let resumption = match message {
+            "next" => Resumption::Next,
+            "has_next" => Resumption::HasNext,
+            m => return Err(self.invalid_message(m, "generator")),
+        };
+        let in_fault = self.in_fault();
+        let generator = match self.generators.get_mut(id) {
+            Some(generator) => generator,
+            None => return Err(self.invalid_message(message, "freed generator")),
+        };
+        if let Some(value) = generator.peeked.take() {
+            match resumption {
+                Resumption::Next => self.memory.push(value),
+                Resumption::HasNext => {
+                    generator.peeked = Some(value);
+                    self.memory.push(Value::from(true));
+                }
+            }
+        } else if generator.finished {
+            match resumption {
+                Resumption::Next => {
+                    return Err(VMError::GeneratorFinished {
+                        location: self.error_location(),
+                    })
+                }
+                Resumption::HasNext => self.memory.push(Value::from(false)),
+            }
+        } else if generator.running() {
+            return Err(self.invalid_message(message, "running generator"));
+        } else if in_fault {
+            return Err(self.invalid_message(message, "generator while handling a fault"));
+        } else {
+            self.scheduler.pending = Some(Switch::Resume(id, resumption));
+        }
+        Ok(())
+    }
+
+    /// Suspends whatever is running and runs a generator in its place
+    pub(super) fn resume_generator(
+        &mut self,
+        id: GeneratorId,
+        resumption: Resumption,
+    ) -> Result<(), VMError> {
+        let context = self
+            .generators
+            .get_mut(id)
+            .and_then(|generator| generator.context.take())
+            .expect("only suspended generators are resumed");
+        trace!("Resuming generator {id}");
+        let resumer = Resumer {
+            generator: id,
+            resumption,
+            context: self.take_context(),
+        };
+        self.generators.resumers.push(resumer);
+        self.restore_context(context);
+        Ok(())
+    }
+
+    /// Suspends the running generator, handing a value back to whatever resumed it
+    pub(super) fn yield_from_generator(&mut self, value: Value) -> Result<(), VMError> {
+        let resumer = self
+            .generators
+            .resumers
+            .pop()
+            .expect("only running generators yield");
+        let context = self.take_context();
+        let generator = self
+            .generators
+            .get_mut(resumer.generator)
+            .expect("running generators are never freed");
+        generator.context = Some(context);
+        let answer = match resumer.resumption {
+            Resumption::Next => value,
+            Resumption::HasNext => {
+                generator.peeked = Some(value);
+                Value::from(true)
+            }
+        };
+        self.restore_context(resumer.context);
+        self.memory.push(answer);
+        Ok(())
+    }
+
+    /// Ends the running generator, which has returned, and goes back to whatever resumed it
+    pub(super) fn finish_generator(&mut self) -> Result<(), VMError> {
+        // the value a generator returns isn't used
+        self.memory.pop();
+        match self.leave_generator() {
+            Resumption::Next => Err(VMError::GeneratorFinished {
+                location: self.error_location(),
+            }),
+            Resumption::HasNext => {
+                self.memory.push(Value::from(false));
+                Ok(())
+            }
+        }
+    }
+
+    /// Ends the running generator without it returning, going back to whatever resumed it.
+    /// Returns the message it was resumed to answer.
+    pub(super) fn leave_generator(&mut self) -> Resumption {
+        let resumer = self
+            .generators
+            .resumers
+            .pop()
+            .expect("not running a generator");
+        drop(self.take_context());
+        if let Some(generator) = self.generators.get_mut(resumer.generator) {
+            generator.finished = true;
+        }
+        trace!("Left generator {}", resumer.generator);
+        self.restore_context(resumer.context);
+        resumer.resumption
+    }
+}
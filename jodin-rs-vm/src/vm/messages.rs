@@ -0,0 +1,257 @@
+//! Answering the [built-in messages](crate::protocol) of values.
+
+use super::VM;
+use crate::error::VMError;
+use crate::protocol::{ITERATED_ARRAY, ITERATOR_INDEX};
+use crate::{ArithmeticsTrait, MemoryTrait};
+use jodin_common::assembly::value::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// Answers a message sent to a string
+    pub(super) fn string_message(
+        &mut self,
+        string: &str,
+        message: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Value, VMError> {
+        let len = string.chars().count();
+        Ok(match message {
+            "length" => Value::UInteger(len as u64),
+            "index" => {
+                let index = self.index_arg(message, &mut args)?;
+                match string.chars().nth(index) {
+                    Some(c) => Value::Str(c.to_string()),
+                    None => return Err(self.index_out_of_bounds(index, len)),
+                }
+            }
+            "slice" => {
+                let start = self.index_arg(message, &mut args)?;
+                let end = self.index_arg(message, &mut args)?;
+                if end > len {
+                    return Err(self.index_out_of_bounds(end, len));
+                }
+                if start > end {
+                    return Err(self.invalid_native_arguments(
+                        message,
+                        format!("start {start} is after end {end}"),
+                    ));
+                }
+                Value::Str(string.chars().skip(start).take(end - start).collect())
+            }
+            "concat" => {
+                let other = self.str_arg(message, &mut args)?;
+                Value::Str(format!("{string}{other}"))
+            }
+            "find" => {
+                let pattern = self.str_arg(message, &mut args)?;
+                match string.find(&*pattern) {
+                    Some(offset) => Value::Integer(string[..offset].chars().count() as i64),
+                    None => Value::Integer(-1),
+                }
+            }
+            "split" => {
+                let separator = self.str_arg(message, &mut args)?;
+                Value::Array(
+                    string
+                        .split(&*separator)
+                        .map(|part| Value::Str(part.to_string()))
+                        .collect(),
+                )
+            }
+            "to_upper" => Value::Str(string.to_uppercase()),
+            m => return Err(self.invalid_message(m, "string")),
+        })
+    }
+
+    /// Answers a message sent to an array
+    pub(super) fn array_message(
+        &mut self,
+        array: &mut Vec<Value>,
+        message: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Value, VMError> {
+        Ok(match message {
+            "push" => {
+                let value = self.native_arg(message, &mut args)?;
+                array.push(value);
+                Value::Empty
+            }
+            "pop" => match array.pop() {
+                Some(value) => value,
+                None => return Err(self.index_out_of_bounds(0, 0)),
+            },
+            "insert" => {
+                let index = self.index_arg(message, &mut args)?;
+                let value = self.native_arg(message, &mut args)?;
+                if index > array.len() {
+                    return Err(self.index_out_of_bounds(index, array.len()));
+                }
+                array.insert(index, value);
+                Value::Empty
+            }
+            "remove" => {
+                let index = self.index_arg(message, &mut args)?;
+                if index >= array.len() {
+                    return Err(self.index_out_of_bounds(index, array.len()));
+                }
+                array.remove(index)
+            }
+            "len" => Value::UInteger(array.len() as u64),
+            "iterate" => self.allocate(Value::from([
+                (ITERATED_ARRAY, Value::Array(array.clone())),
+                (ITERATOR_INDEX, Value::UInteger(0)),
+            ])),
+            m => return Err(self.invalid_message(m, "array")),
+        })
+    }
+
+    /// Answers a message sent to an iterator created by an array's `iterate`
+    pub(super) fn iterator_message(
+        &mut self,
+        iterator: &mut HashMap<String, Value>,
+        message: &str,
+    ) -> Result<Value, VMError> {
+        let index = match iterator.get(ITERATOR_INDEX) {
+            Some(&Value::UInteger(index)) => index as usize,
+            _ => 0,
+        };
+        let array = match iterator.get(ITERATED_ARRAY) {
+            Some(Value::Array(array)) => array,
+            _ => return Err(self.invalid_message(message, "array iterator")),
+        };
+        Ok(match message {
+            "has_next" => Value::from(index < array.len()),
+            "next" => {
+                let value = match array.get(index) {
+                    Some(value) => value.clone(),
+                    None => return Err(self.index_out_of_bounds(index, array.len())),
+                };
+                iterator.insert(
+                    ITERATOR_INDEX.to_string(),
+                    Value::UInteger(index as u64 + 1),
+                );
+                value
+            }
+            m => return Err(self.invalid_message(m, "array iterator")),
+        })
+    }
+
+    /// Answers a message sent to a dictionary
+    pub(super) fn dictionary_message(
+        &mut self,
+        dict: &mut HashMap<String, Value>,
+        message: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Value, VMError> {
+        Ok(match message {
+            "get" => {
+                let name = self.str_arg(message, &mut args)?;
+                match dict.get(&*name) {
+                    Some(value) => value.clone(),
+                    None => return Err(self.missing_attribute(name)),
+                }
+            }
+            "put" => {
+                let name = self.str_arg(message, &mut args)?;
+                let value = self.native_arg(message, &mut args)?;
+                dict.insert(name, value);
+                Value::Empty
+            }
+            "keys" => {
+                let mut keys = dict.keys().cloned().collect::<Vec<_>>();
+                keys.sort();
+                Value::Array(keys.into_iter().map(Value::Str).collect())
+            }
+            "values" => {
+                let mut entries = dict.iter().collect::<Vec<_>>();
+                entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+                Value::Array(entries.into_iter().map(|(_, v)| v.clone()).collect())
+            }
+            "contains" => {
+                let name = self.str_arg(message, &mut args)?;
+                Value::from(dict.contains_key(&*name))
+            }
+            "remove" => {
+                let name = self.str_arg(message, &mut args)?;
+                match dict.remove(&*name) {
+                    Some(value) => value,
+                    None => return Err(self.missing_attribute(name)),
+                }
+            }
+            "len" => Value::UInteger(dict.len() as u64),
+            m => return Err(self.invalid_message(m, "dictionary")),
+        })
+    }
+
+    /// Answers a message sent to a byte, integer, unsigned integer or float
+    pub(super) fn number_message(
+        &mut self,
+        number: Value,
+        message: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Value, VMError> {
+        if message == "to_string" {
+            return Ok(Value::Str(format!("{number:#}")));
+        }
+        if !matches!(message, "eq" | "ne" | "lt" | "le" | "gt" | "ge" | "compare") {
+            return Err(self.invalid_message(message, "number"));
+        }
+        let other = self.native_arg(message, &mut args)?;
+        let ordering = self.compare(number, other)?;
+        Ok(match message {
+            "eq" => Value::from(ordering == Ordering::Equal),
+            "ne" => Value::from(ordering != Ordering::Equal),
+            "lt" => Value::from(ordering == Ordering::Less),
+            "le" => Value::from(ordering != Ordering::Greater),
+            "gt" => Value::from(ordering == Ordering::Greater),
+            "ge" => Value::from(ordering != Ordering::Less),
+            _ => Value::Integer(ordering as i64),
+        })
+    }
+
+    /// Compares two numbers with the alu, so they're promoted like they are for arithmetic
+    fn compare(&self, a: Value, b: Value) -> Result<Ordering, VMError> {
+        let greater = self.arithmetic(|alu| alu.greater_than(a.clone(), b.clone()))?;
+        let less = self.arithmetic(|alu| alu.greater_than(b, a))?;
+        Ok(match (greater, less) {
+            (Value::Byte(g), _) if g != 0 => Ordering::Greater,
+            (_, Value::Byte(l)) if l != 0 => Ordering::Less,
+            _ => Ordering::Equal,
+        })
+    }
+
+    /// Takes the next argument of a message as a string
+    pub(super) fn str_arg(&self, message: &str, args: &mut Vec<Value>) -> Result<String, VMError> {
+        match self.native_arg(message, args)? {
+            Value::Str(string) => Ok(string),
+            v => Err(self.type_mismatch(v, "Str")),
+        }
+    }
+
+    /// Takes the next argument of a message as an index, which can be any non-negative integer
+    pub(super) fn index_arg(&self, message: &str, args: &mut Vec<Value>) -> Result<usize, VMError> {
+        match self.native_arg(message, args)? {
+            Value::Byte(index) => Ok(index as usize),
+            Value::UInteger(index) => Ok(index as usize),
+            Value::Integer(index) if index >= 0 => Ok(index as usize),
+            v => Err(self.type_mismatch(v, "index")),
+        }
+    }
+
+    fn index_out_of_bounds(&self, index: usize, len: usize) -> VMError {
+        VMError::IndexOutOfBounds {
+            index,
+            len,
+            location: self.error_location(),
+        }
+    }
+
+    fn missing_attribute(&self, attribute: String) -> VMError {
+        VMError::MissingAttribute {
+            attribute,
+            location: self.error_location(),
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! The registry of [natives](crate::natives) and the built-in natives.
+//!
+//! Built-in natives that only need their arguments and the stdio of the vm are registered as
+//! [NativeFunction]s like any other native. The rest work on the internals of the vm, so they're
+//! registered as functions of the vm instead.
+
+use super::VM;
+use crate::error::VMError;
+use crate::fiber::Switch;
+use crate::input;
+use crate::natives::{Arity, NativeCall, NativeFunction};
+use crate::permissions::Capability;
+use crate::{ArithmeticsTrait, MemoryTrait};
+use jodin_common::assembly::value::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::rc::Rc;
+
+/// A native that works on the internals of the vm
+pub(super) type Builtin<'l, M, A> = fn(&mut VM<'l, M, A>, &str, Vec<Value>) -> Result<(), VMError>;
+
+/// A registered native
+pub(super) enum Native<'l, M: MemoryTrait, A: ArithmeticsTrait> {
+    Builtin(Builtin<'l, M, A>),
+    Function(Rc<dyn NativeFunction + 'l>),
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> Clone for Native<'l, M, A> {
+    fn clone(&self) -> Self {
+        match self {
+            Native::Builtin(builtin) => Native::Builtin(*builtin),
+            Native::Function(function) => Native::Function(function.clone()),
+        }
+    }
+}
+
+/// The natives of a vm by name, with their arities and the capabilities they need
+pub(super) type Natives<'l, M, A> = HashMap<String, (Arity, Option<Capability>, Native<'l, M, A>)>;
+
+/// The natives every vm starts with
+pub(super) fn builtin_natives<'l, M: MemoryTrait, A: ArithmeticsTrait>() -> Natives<'l, M, A> {
+    let functions: [(&str, Arity, Rc<dyn NativeFunction>); 6] = [
+        ("print", Arity::Exactly(1), Rc::new(print)),
+        ("read_line", Arity::Exactly(0), Rc::new(input::read_line)),
+        ("read_bytes", Arity::Exactly(1), Rc::new(input::read_bytes)),
+        ("read_all", Arity::Exactly(0), Rc::new(input::read_all)),
+        ("read_int", Arity::Exactly(0), Rc::new(input::read_int)),
+        ("read_float", Arity::Exactly(0), Rc::new(input::read_float)),
+    ];
+    let builtins: [(&str, Arity, Builtin<'l, M, A>); 28] = [
+        ("write", Arity::Exactly(2), VM::write_native),
+        ("open", Arity::Exactly(2), VM::open_native),
+        ("read", Arity::AtLeast(1), VM::read_native),
+        ("seek", Arity::AtLeast(2), VM::seek_native),
+        ("close", Arity::Exactly(1), VM::close_native),
+        ("stat", Arity::Exactly(1), VM::stat_native),
+        ("list_dir", Arity::Exactly(1), VM::list_dir_native),
+        ("remove", Arity::Exactly(1), VM::remove_native),
+        ("invoke", Arity::AtLeast(3), VM::invoke_native),
+        ("ref", Arity::Exactly(1), VM::ref_native),
+        ("copy", Arity::Exactly(1), VM::copy_native),
+        ("dynamic_call", Arity::Exactly(1), VM::dynamic_call_native),
+        ("@load_scope", Arity::Exactly(1), VM::load_scope_native),
+        ("@save_scope", Arity::Exactly(1), |vm, native, args| {
+            let hashed = vm.scope_hash(native, args)?;
+            vm.memory.save_current_scope(hashed);
+            Ok(())
+        }),
+        ("@spawn", Arity::AtLeast(1), |vm, native, mut args| {
+            let function = vm.native_arg(native, &mut args)?;
+            let id = vm.spawn_fiber(function, args)?;
+            vm.memory.push(Value::UInteger(id));
+            Ok(())
+        }),
+        ("@yield", Arity::Exactly(0), |vm, _, _| {
+            vm.scheduler.pending = Some(Switch::Yield);
+            Ok(())
+        }),
+        ("@join", Arity::Exactly(1), |vm, native, mut args| match vm
+            .native_arg(native, &mut args)?
+        {
+            Value::UInteger(id) => vm.join_fiber(id),
+            v => Err(vm.type_mismatch(v, "fiber id")),
+        }),
+        ("@generator", Arity::AtLeast(1), |vm, native, mut args| {
+            let function = vm.native_arg(native, &mut args)?;
+            let generator = vm.create_generator(function, args)?;
+            vm.memory.push(generator);
+            Ok(())
+        }),
+        ("@yield_value", Arity::Exactly(1), VM::yield_value_native),
+        ("@collect_garbage", Arity::Exactly(0), |vm, _, _| {
+            vm.heap.collect();
+            Ok(())
+        }),
+        ("@push_scope", Arity::Exactly(0), |vm, _, _| {
+            vm.memory.push_scope();
+            Ok(())
+        }),
+        (
+            "@reserve_vars",
+            Arity::Exactly(1),
+            |vm, native, mut args| match vm.native_arg(native, &mut args)? {
+                Value::UInteger(count) => {
+                    vm.memory.reserve_vars(count as usize);
+                    Ok(())
+                }
+                v => Err(vm.invalid_native_arguments(
+                    native,
+                    format!("expected a variable count, found {v}"),
+                )),
+            },
+        ),
+        ("@pop_scope", Arity::Exactly(0), |vm, native, _| {
+            vm.memory
+                .pop_scope()
+                .map_err(|_| vm.scope_underflow(native))
+        }),
+        ("@global_scope", Arity::Exactly(0), |vm, _, _| {
+            vm.memory.global_scope();
+            Ok(())
+        }),
+        ("@back_scope", Arity::Exactly(0), |vm, native, _| {
+            vm.back_scope_native(native)
+        }),
+        ("@print_stack", Arity::Exactly(0), |vm, _, _| {
+            println!("memory: {:#?}", vm.memory);
+            Ok(())
+        }),
+        (
+            "@set_fault_handler",
+            Arity::Exactly(2),
+            VM::set_fault_handler_native,
+        ),
+        ("@call", Arity::AtLeast(1), |vm, native, mut args| match vm
+            .native_arg(native, &mut args)?
+        {
+            Value::Str(method) => vm.native_method(&method, args),
+            v => Err(vm.type_mismatch(v, "Str")),
+        }),
+    ];
+    functions
+        .into_iter()
+        .map(|(name, arity, function)| (name, arity, Native::Function(function)))
+        .chain(
+            builtins
+                .into_iter()
+                .map(|(name, arity, builtin)| (name, arity, Native::Builtin(builtin))),
+        )
+        .map(|(name, arity, native)| {
+            // a function can enter and leave its own scope without the capability, so these
+            // natives check it themselves
+            let capability = Capability::of_builtin(name)
+                .filter(|_| !matches!(name, "@load_scope" | "@back_scope"));
+            (name.to_string(), (arity, capability, native))
+        })
+        .collect()
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// Registers a native, replacing any native with the same name, including built-in ones
+    pub fn register_native<F: NativeFunction + 'l>(
+        &mut self,
+        name: impl AsRef<str>,
+        arity: Arity,
+        function: F,
+    ) {
+        self.natives.insert(
+            name.as_ref().to_string(),
+            (arity, None, Native::Function(Rc::new(function))),
+        );
+    }
+
+    /// Registers a native like [register_native](Self::register_native), which can only be called
+    /// if the [permissions](crate::permissions) of the vm allow a capability
+    pub fn register_native_in<F: NativeFunction + 'l>(
+        &mut self,
+        capability: Capability,
+        name: impl AsRef<str>,
+        arity: Arity,
+        function: F,
+    ) {
+        self.natives.insert(
+            name.as_ref().to_string(),
+            (arity, Some(capability), Native::Function(Rc::new(function))),
+        );
+    }
+
+    /// Finds a native, checking it can be called with a number of arguments and that the vm has
+    /// the capability it needs
+    pub(super) fn find_native(
+        &self,
+        native: &str,
+        count: usize,
+    ) -> Result<Native<'l, M, A>, VMError> {
+        match self.natives.get(native) {
+            None => Err(VMError::InvalidNative {
+                native: native.to_string(),
+                location: self.error_location(),
+            }),
+            Some((arity, _, _)) if !arity.accepts(count) => Err(self.invalid_native_arguments(
+                native,
+                format!("expected {arity} arguments, found {count}"),
+            )),
+            Some((_, capability, found)) => {
+                if let Some(capability) = capability {
+                    self.require_capability(*capability, native)?;
+                }
+                Ok(found.clone())
+            }
+        }
+    }
+
+    /// Runs a native that has been found
+    pub(super) fn run_native(
+        &mut self,
+        native: &str,
+        found: Native<'l, M, A>,
+        args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let function = match found {
+            Native::Builtin(builtin) => return builtin(self, native, args),
+            Native::Function(function) => function,
+        };
+        // the stdio is taken out of the vm while the native runs, so errors can still be located
+        let mut stdin = self.stdin.take();
+        let mut stdout = self.stdout.take();
+        let mut stderr = self.stderr.take();
+        let result = {
+            let locate = || self.error_location();
+            let mut call =
+                NativeCall::new(native, args, &locate, &mut stdin, &mut stdout, &mut stderr);
+            function.call(&mut call)
+        };
+        self.stdin = stdin;
+        self.stdout = stdout;
+        self.stderr = stderr;
+        if let Some(value) = result? {
+            self.memory.push(value);
+        }
+        Ok(())
+    }
+
+    /// Invokes the message (arg 2) on the target (arg 1) with args (arg 3..)
+    fn invoke_native(&mut self, _: &str, mut args: Vec<Value>) -> Result<(), VMError> {
+        let mut target = args.pop().unwrap();
+        let msg = match args.pop().unwrap() {
+            Value::Str(msg) => msg,
+            v => return Err(self.type_mismatch(v, "Str")),
+        };
+        match args.pop().unwrap() {
+            Value::Array(args) => {
+                self.send_message(&mut target, &msg, args)?;
+                Ok(())
+            }
+            v => Err(self.type_mismatch(v, "Array")),
+        }
+    }
+
+    fn ref_native(&mut self, native: &str, mut args: Vec<Value>) -> Result<(), VMError> {
+        let target = self.native_arg(native, &mut args)?;
+        let as_ref = self.allocate(target);
+        self.memory.push(as_ref);
+        Ok(())
+    }
+
+    fn copy_native(&mut self, native: &str, mut args: Vec<Value>) -> Result<(), VMError> {
+        let target = self.native_arg(native, &mut args)?;
+        let cloned = target.clone();
+        self.memory.push(target);
+        self.memory.push(cloned);
+        Ok(())
+    }
+
+    fn dynamic_call_native(&mut self, native: &str, mut args: Vec<Value>) -> Result<(), VMError> {
+        match self.native_arg(native, &mut args)? {
+            Value::Str(function) => {
+                let result = self.call_plugin(&function)?;
+                self.memory.push(result);
+                Ok(())
+            }
+            v => Err(self.type_mismatch(v, "Str")),
+        }
+    }
+
+    fn yield_value_native(&mut self, native: &str, mut args: Vec<Value>) -> Result<(), VMError> {
+        let value = self.native_arg(native, &mut args)?;
+        if self.generators.resumers.is_empty() {
+            return Err(self.invalid_native_arguments(native, "not running a generator"));
+        }
+        self.scheduler.pending = Some(Switch::YieldValue(value));
+        Ok(())
+    }
+
+    fn set_fault_handler_native(
+        &mut self,
+        native: &str,
+        mut args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        self.require_kernel_mode()?;
+        let fault = match self.native_arg(native, &mut args)? {
+            Value::Str(fault) => fault,
+            v => return Err(self.type_mismatch(v, "Str")),
+        };
+        let handler = match self.native_arg(native, &mut args)? {
+            f @ Value::Function(_) => f,
+            v => return Err(self.type_mismatch(v, "function")),
+        };
+        if !self.fault_table.set_fault_jump(&fault, handler) {
+            return Err(self.invalid_native_arguments(
+                native,
+                format!("{fault:?} is not a fault that can be handled"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Hashes the scope argument of a scope native
+    /// Loads a saved scope. Without [Scopes](Capability::Scopes), the only scope that can be loaded
+    /// is the one of the running function, which is saved under its label.
+    fn load_scope_native(&mut self, native: &str, args: Vec<Value>) -> Result<(), VMError> {
+        let restricted = !self.permissions.allows(Capability::Scopes);
+        if restricted {
+            let function = self.most_recent_public_label(self.program_counter());
+            match (args.first(), function) {
+                (Some(Value::Str(scope)), Some(function)) if scope == function => {}
+                _ => self.require_capability(Capability::Scopes, native)?,
+            }
+        }
+        let hashed = self.scope_hash(native, args)?;
+        self.memory.load_scope(hashed);
+        if restricted {
+            self.own_loads
+                .push((self.counter_stack.len(), self.memory.scope_depth().loads));
+        }
+        Ok(())
+    }
+
+    /// Backs out of the loaded scope. Without [Scopes](Capability::Scopes), the only load that can
+    /// be backed out of is one the running call made of its own scope.
+    fn back_scope_native(&mut self, native: &str) -> Result<(), VMError> {
+        if !self.permissions.allows(Capability::Scopes) {
+            let load = (self.counter_stack.len(), self.memory.scope_depth().loads);
+            if self.own_loads.last() != Some(&load) {
+                self.require_capability(Capability::Scopes, native)?;
+            }
+            self.own_loads.pop();
+        }
+        self.memory
+            .back_scope()
+            .map_err(|_| self.scope_underflow(native))
+    }
+
+    fn scope_hash(&self, native: &str, mut args: Vec<Value>) -> Result<u64, VMError> {
+        let scope = self.native_arg(native, &mut args)?;
+        let mut hasher = DefaultHasher::default();
+        scope.try_hash(&mut hasher)?;
+        Ok(hasher.finish())
+    }
+}
+
+/// Prints its argument
+fn print(call: &mut NativeCall<'_, '_>) -> Result<Option<Value>, VMError> {
+    let value = call.args().value()?;
+    write!(call.stdout(), "{:#}", value)?;
+    Ok(Some(Value::Empty))
+}
@@ -0,0 +1,199 @@
+//! The register engine.
+//!
+//! Loaded instructions are translated into their
+//! [register form](jodin_common::assembly::registers), which is run by this module. Within a
+//! basic block, values are kept in the vm's register file instead of being pushed and popped
+//! through the [memory](crate::MemoryTrait). Instructions the register form doesn't have are run
+//! by the interpreter, after the registers holding values have been spilled onto the stack.
+//!
+//! Execution can only begin in the register form at stack instructions that are reached with no
+//! values in registers. Anywhere else, the interpreter runs instructions until one is reached. As
+//! with the threaded engine, the vm falls back to the interpreter loop while a debugger or
+//! observer is attached or instructions are being logged, and also while a fuel limit is set.
+
+use super::threaded::is_true;
+use super::VM;
+use crate::error::{ArithmeticError, VMError};
+use crate::{ArithmeticsTrait, MemoryTrait, VirtualMachine};
+use jodin_common::assembly::registers::{
+    translate, BinaryOp, Register, RegisterAsm, RegisterAssembly, RegisterInstruction, UnaryOp,
+};
+use jodin_common::assembly::value::{JRef, Value};
+use std::rc::Rc;
+
+/// Where execution continues after a register instruction
+enum Flow {
+    /// The next register instruction
+    Next,
+    /// A stack instruction
+    Jump(usize),
+}
+
+fn binary<A: ArithmeticsTrait>(
+    alu: &A,
+    op: BinaryOp,
+    left: Value,
+    right: Value,
+) -> Result<Value, ArithmeticError> {
+    match op {
+        BinaryOp::Add => alu.add(left, right),
+        BinaryOp::Subtract => alu.sub(left, right),
+        BinaryOp::Multiply => alu.mult(left, right),
+        BinaryOp::Divide => alu.div(left, right),
+        BinaryOp::Remainder => alu.rem(left, right),
+        BinaryOp::And => alu.and(left, right),
+        BinaryOp::Or => alu.or(left, right),
+        BinaryOp::Xor => alu.xor(left, right),
+        BinaryOp::ShiftLeft => alu.shift_left(left, right),
+        BinaryOp::ShiftRight => alu.shift_right(left, right),
+        BinaryOp::Gt => alu.greater_than(left, right),
+    }
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// Gets the register form of the loaded instructions, translating it if it's out of date
+    fn register_code(&mut self) -> Rc<RegisterAssembly> {
+        match &self.register_code {
+            Some(code) => code.clone(),
+            None => {
+                let code = Rc::new(translate(&self.instructions));
+                self.register_code = Some(code.clone());
+                code
+            }
+        }
+    }
+
+    /// Takes the value out of a register
+    fn take_register(&mut self, reg: Register) -> Value {
+        std::mem::replace(&mut self.registers[reg as usize], Value::Empty)
+    }
+
+    /// Pushes the values of the first `n` registers onto the stack
+    fn spill_registers(&mut self, n: Register) {
+        for reg in 0..n {
+            let value = self.take_register(reg);
+            self.memory.push(value);
+        }
+    }
+
+    /// Runs register instructions until the vm stops, the program counter leaves the loaded
+    /// instructions or reaches an instruction the register form can't begin at. Returns whether
+    /// any instruction was run.
+    pub(super) fn run_registers(&mut self) -> Result<bool, VMError> {
+        let mut code = self.register_code();
+        let mut index = match code.entry(self.program_counter()) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        while self.cont {
+            let instruction = match code.instructions.get(index) {
+                Some(instruction) => instruction,
+                None => break,
+            };
+            self.set_program_counter(instruction.pc);
+            if instruction.width > 0 {
+                self.check_limits()?;
+                self.executed_instructions += instruction.width as u64 - 1;
+            }
+            let next = match self.execute_register(instruction) {
+                Ok(Flow::Next) => {
+                    index += 1;
+                    self.set_program_counter(instruction.next);
+                    continue;
+                }
+                Ok(Flow::Jump(next)) => next,
+                Err(error) => {
+                    if let Some(n) = instruction.asm.spilled_on_failure() {
+                        self.spill_registers(n);
+                    }
+                    self.raise_fault(error)?;
+                    self.program_counter()
+                }
+            };
+            self.set_program_counter(next);
+            if self.scheduler.pending.is_some() {
+                // the run loop switches fibers
+                break;
+            }
+            if self.register_code.is_none() {
+                // the interpreter loaded or relinked instructions
+                code = self.register_code();
+            }
+            index = match code.entry(next) {
+                Some(index) if next > 0 => index,
+                _ => break,
+            };
+        }
+        Ok(true)
+    }
+
+    fn execute_register(&mut self, instruction: &RegisterInstruction) -> Result<Flow, VMError> {
+        match &instruction.asm {
+            RegisterAsm::Const { dst, value } => {
+                self.registers[*dst as usize] = value.clone();
+            }
+            &RegisterAsm::LoadVar { dst, var } => {
+                self.registers[dst as usize] = self.get_var_value(var)?;
+            }
+            &RegisterAsm::LoadRef { dst, var } => {
+                let value = match self.memory.get_var(var as usize) {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return Err(VMError::VariableNotSet {
+                            var: var as usize,
+                            location: self.error_location(),
+                        })
+                    }
+                };
+                self.registers[dst as usize] = Value::Reference(JRef::from(value));
+            }
+            &RegisterAsm::Deref(reg) => match self.take_register(reg) {
+                Value::Reference(reference) => {
+                    self.registers[reg as usize] = reference.borrow().clone();
+                }
+                v => return Err(self.bad_reference(v)),
+            },
+            &RegisterAsm::StoreVar { var, src } => {
+                let value = self.take_register(src);
+                self.memory.set_var(var as usize, value);
+            }
+            &RegisterAsm::Binary {
+                op,
+                dst,
+                left,
+                right,
+            } => {
+                let left = self.take_register(left);
+                let right = self.take_register(right);
+                self.registers[dst as usize] =
+                    self.binary_arithmetic(left, right, |alu, l, r| binary(alu, op, l, r))?;
+            }
+            &RegisterAsm::Unary { op, reg } => {
+                let value = self.take_register(reg);
+                self.registers[reg as usize] = match op {
+                    UnaryOp::Not => self.arithmetic(|alu| alu.not(value))?,
+                    UnaryOp::GT0 => Value::from(match value {
+                        Value::Byte(b) => b > 0,
+                        Value::Float(f) => f > 0.0,
+                        Value::Integer(i) => i > 0,
+                        Value::UInteger(u) => u > 0,
+                        v => return Err(self.type_mismatch(v, "numeric")),
+                    }),
+                };
+            }
+            &RegisterAsm::Jump(target) => return Ok(Flow::Jump(target)),
+            &RegisterAsm::JumpIf { cond, target } => {
+                if is_true(&self.take_register(cond)) {
+                    return Ok(Flow::Jump(target));
+                }
+            }
+            &RegisterAsm::Spill(n) => self.spill_registers(n),
+            RegisterAsm::Stack(asm) => {
+                return self
+                    .interpret_instruction(asm, instruction.pc)
+                    .map(Flow::Jump)
+            }
+        }
+        Ok(Flow::Next)
+    }
+}
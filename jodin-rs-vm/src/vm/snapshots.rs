@@ -0,0 +1,102 @@
+//! Saving the state of a vm in a [snapshot](crate::snapshot) and restoring it.
+
+use super::{Engine, VM};
+use crate::error::VMError;
+use crate::fault::FaultHandle;
+use crate::fiber::Scheduler;
+use crate::generator::Generators;
+use crate::heap::Heap;
+use crate::snapshot::Snapshot;
+use crate::{ArithmeticsTrait, MemoryTrait};
+use jodin_common::assembly::value::with_aliases;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// Writes the state of the vm as a snapshot, which can be read back by [restore](VM::restore).
+    /// Fails if other fibers or generators are alive.
+    pub fn snapshot<W: Write>(&self, writer: W) -> Result<(), VMError>
+    where
+        M: Serialize,
+    {
+        if !self.scheduler.alone() {
+            return Err(VMError::SnapshotUnavailable(
+                "other fibers are alive".to_string(),
+            ));
+        }
+        if self.generators.any_alive() {
+            return Err(VMError::SnapshotUnavailable(
+                "generators are alive".to_string(),
+            ));
+        }
+        let snapshot = Snapshot {
+            instructions: (*self.instructions).clone(),
+            label_to_instruction: self.label_to_instruction.clone(),
+            label_references: self.label_references.clone(),
+            counter_stack: self.counter_stack.clone(),
+            debug_info: self.debug_info.clone(),
+            memory: &self.memory,
+            heap: self.heap.live().collect(),
+            exception_handlers: self.exception_handlers.clone(),
+            own_loads: self.own_loads.clone(),
+            handler: self.handler.as_ref(),
+            fault_table: self.fault_table.clone(),
+            kernel_mode: self.kernel_mode,
+            next_anonymous_function: self.next_anonymous_function.load(Ordering::Relaxed),
+            executed_instructions: self.executed_instructions,
+        };
+        with_aliases(|| bincode::serialize_into(writer, &snapshot))?;
+        Ok(())
+    }
+
+    /// Replaces the state of the vm with a snapshot written by [snapshot](VM::snapshot) from a vm
+    /// with the same kind of memory. A run that was stopped when the snapshot was taken can then
+    /// be continued with [resume](VM::resume).
+    pub fn restore<R: Read>(&mut self, reader: R) -> Result<(), VMError>
+    where
+        M: DeserializeOwned,
+    {
+        let snapshot: Snapshot<M, FaultHandle> =
+            with_aliases(|| bincode::deserialize_from(reader))?;
+        self.instructions = Rc::new(snapshot.instructions);
+        self.label_to_instruction = snapshot.label_to_instruction;
+        self.label_references = snapshot.label_references;
+        self.counter_stack = snapshot.counter_stack;
+        self.debug_info = snapshot.debug_info;
+        self.memory = snapshot.memory;
+        self.heap = Heap::new(self.heap.policy());
+        for reference in &snapshot.heap {
+            self.heap.track(reference);
+        }
+        self.exception_handlers = snapshot.exception_handlers;
+        self.own_loads = snapshot.own_loads;
+        self.handler = snapshot.handler;
+        self.fault_table = snapshot.fault_table;
+        self.kernel_mode = snapshot.kernel_mode;
+        self.next_anonymous_function = AtomicU64::new(snapshot.next_anonymous_function);
+        self.executed_instructions = snapshot.executed_instructions;
+
+        self.scheduler = Scheduler::new(self.scheduler.preemption);
+        self.generators = Generators::default();
+        self.threaded = match self.engine {
+            Engine::Threaded => Some(Rc::new(vec![])),
+            _ => None,
+        };
+        self.compile_threaded();
+        self.register_code = None;
+        Ok(())
+    }
+
+    /// Continues a run that stopped before it finished, such as one that ran out of fuel or was
+    /// restored from a snapshot, returning its exit code
+    pub fn resume(&mut self) -> Result<u32, VMError> {
+        // a finished run leaves the counter at 0, where no instruction is run
+        if self.program_counter() == 0 && !self.in_fault() {
+            return Err(VMError::NothingToResume);
+        }
+        self.continue_run()
+    }
+}
@@ -0,0 +1,676 @@
+//! The threaded code engine.
+//!
+//! Loaded instructions are compiled into closures with their operands decoded ahead of time, so
+//! executing an instruction is a single indirect call instead of a walk through
+//! [interpret_instruction](crate::core_traits::VirtualMachine::interpret_instruction). Instructions
+//! that aren't hot enough to be worth specializing fall back to the interpreter.
+//!
+//! Compiled instructions are run by their own loop, which skips the work the interpreter loop does
+//! around every instruction. While a debugger or observer is attached, or while instructions are
+//! being logged, the vm falls back to the interpreter loop.
+//!
+//! Common sequences of instructions are also fused into a single superinstruction, which reads
+//! the operands of a binary operation straight from variables and constants, and puts the result
+//! straight into a variable or a conditional jump, instead of moving every value through the
+//! stack. A superinstruction doesn't change anything until it has succeeded. If it would fail, the
+//! first of its instructions is run on its own instead, so faults happen at the same instruction
+//! as in the interpreter. Fused instructions are counted as the instructions they stand for, and
+//! aren't used while any limit is checked per instruction, so that limits stop the vm at the same
+//! instruction as the interpreter.
+
+use super::VM;
+use crate::error::VMError;
+use crate::{ArithmeticsTrait, MemoryTrait, VirtualMachine};
+use jodin_common::assembly::instructions::Asm;
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::{JRef, Value};
+
+/// Executes an instruction at some index with its decoded operand, returning the index of the
+/// next instruction
+type Handler<'l, M, A> = fn(&mut VM<'l, M, A>, &Operand<A>, usize) -> Result<usize, VMError>;
+
+/// Executes a superinstruction at some index with its decoded operand, returning the index of the
+/// next instruction, or nothing without changing the vm if it can't be executed
+type FusedHandler<'l, M, A> = fn(&mut VM<'l, M, A>, &Operand<A>, usize) -> Option<usize>;
+
+/// The most instructions a superinstruction stands for
+pub(super) const LONGEST_FUSION: usize = 6;
+
+/// An instruction decoded ahead of time
+pub(super) struct Compiled<'l, M: MemoryTrait, A: ArithmeticsTrait> {
+    handler: Handler<'l, M, A>,
+    operand: Operand<A>,
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> Clone for Compiled<'l, M, A> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler,
+            operand: self.operand.clone(),
+        }
+    }
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> Compiled<'l, M, A> {
+    fn new(handler: Handler<'l, M, A>, operand: Operand<A>) -> Self {
+        Self { handler, operand }
+    }
+
+    fn execute(&self, vm: &mut VM<'l, M, A>, pc: usize) -> Result<usize, VMError> {
+        (self.handler)(vm, &self.operand, pc)
+    }
+
+    /// Whether this instruction is run through the interpreter
+    fn is_fallback(&self) -> bool {
+        matches!(self.operand, Operand::Asm(_))
+    }
+}
+
+/// A superinstruction decoded ahead of time
+pub(super) struct Fused<'l, M: MemoryTrait, A: ArithmeticsTrait> {
+    handler: FusedHandler<'l, M, A>,
+    operand: Operand<A>,
+    /// The number of instructions it stands for
+    width: usize,
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> Clone for Fused<'l, M, A> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler,
+            operand: self.operand.clone(),
+            width: self.width,
+        }
+    }
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> Fused<'l, M, A> {
+    fn execute(&self, vm: &mut VM<'l, M, A>, pc: usize) -> Option<usize> {
+        (self.handler)(vm, &self.operand, pc)
+    }
+}
+
+/// The operand of a compiled instruction
+pub(super) enum Operand<A: ArithmeticsTrait> {
+    None,
+    Value(Value),
+    Scalar(Scalar),
+    Var(u64),
+    Target(usize),
+    Binary(BinaryOp<A>),
+    FusedBinary(FusedBinary<A>),
+    /// An instruction that is run through the interpreter
+    Asm(Asm),
+}
+
+impl<A: ArithmeticsTrait> Clone for Operand<A> {
+    fn clone(&self) -> Self {
+        match self {
+            Operand::None => Operand::None,
+            Operand::Value(value) => Operand::Value(value.clone()),
+            Operand::Scalar(scalar) => Operand::Scalar(*scalar),
+            Operand::Var(var) => Operand::Var(*var),
+            Operand::Target(target) => Operand::Target(*target),
+            Operand::Binary(op) => Operand::Binary(*op),
+            Operand::FusedBinary(fused) => Operand::FusedBinary(*fused),
+            Operand::Asm(asm) => Operand::Asm(asm.clone()),
+        }
+    }
+}
+
+/// A pushed value that's copied onto the stack instead of cloned
+#[derive(Debug, Copy, Clone)]
+pub(super) enum Scalar {
+    Byte(u8),
+    Float(f64),
+    Integer(i64),
+    UInteger(u64),
+}
+
+impl Scalar {
+    fn of(value: &Value) -> Option<Self> {
+        match *value {
+            Value::Byte(b) => Some(Scalar::Byte(b)),
+            Value::Float(f) => Some(Scalar::Float(f)),
+            Value::Integer(i) => Some(Scalar::Integer(i)),
+            Value::UInteger(u) => Some(Scalar::UInteger(u)),
+            _ => None,
+        }
+    }
+
+    fn value(self) -> Value {
+        match self {
+            Scalar::Byte(b) => Value::Byte(b),
+            Scalar::Float(f) => Value::Float(f),
+            Scalar::Integer(i) => Value::Integer(i),
+            Scalar::UInteger(u) => Value::UInteger(u),
+        }
+    }
+}
+
+/// Where a fused binary operation gets one of its operands from
+#[derive(Debug, Copy, Clone)]
+pub(super) enum Input {
+    /// The stack, which the operand is only popped from once the operation succeeded
+    Stack,
+    /// A variable, standing for a `GetVar` and a `Deref`
+    Var(usize),
+    /// A pushed constant
+    Const(Scalar),
+}
+
+impl Input {
+    /// Reads the operand, where `depth` is how far from the top of the stack it is if it's on the
+    /// stack
+    fn read<M: MemoryTrait>(self, memory: &M, depth: usize) -> Option<Value> {
+        match self {
+            Input::Stack => memory.stack().iter().rev().nth(depth).cloned(),
+            Input::Var(var) => memory.var_value(var).ok(),
+            Input::Const(scalar) => Some(scalar.value()),
+        }
+    }
+
+    /// Parses the instructions an operand is read with from the start of some instructions,
+    /// returning it with the number of instructions it stands for
+    fn parse(instructions: &[Asm]) -> Option<(Input, usize)> {
+        match instructions {
+            [Asm::GetVar(var), Asm::Deref, ..] => Some((Input::Var(*var as usize), 2)),
+            [Asm::Push(value), ..] => Some((Input::Const(Scalar::of(value)?), 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Where a fused binary operation puts its result
+#[derive(Debug, Copy, Clone)]
+pub(super) enum Output {
+    Stack,
+    /// A variable, standing for a `SetVar`
+    Var(usize),
+    /// A conditional jump to an index
+    Branch(usize),
+}
+
+/// A binary operation fused with the instructions around it
+pub(super) struct FusedBinary<A: ArithmeticsTrait> {
+    op: BinaryOp<A>,
+    integer_op: Option<IntegerOp>,
+    /// The operand that's pushed last, and so popped first
+    left: Input,
+    right: Input,
+    output: Output,
+    width: usize,
+}
+
+impl<A: ArithmeticsTrait> Clone for FusedBinary<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: ArithmeticsTrait> Copy for FusedBinary<A> {}
+
+/// A compiled instruction
+pub(super) struct ThreadedOp<'l, M: MemoryTrait, A: ArithmeticsTrait> {
+    /// Executes just this instruction
+    pub(super) single: Compiled<'l, M, A>,
+    /// Executes this instruction along with the ones after it
+    pub(super) fused: Option<Fused<'l, M, A>>,
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> Clone for ThreadedOp<'l, M, A> {
+    fn clone(&self) -> Self {
+        Self {
+            single: self.single.clone(),
+            fused: self.fused.clone(),
+        }
+    }
+}
+
+/// A binary operation of the alu
+type BinaryOp<A> = fn(&A, Value, Value) -> Result<Value, crate::error::ArithmeticError>;
+
+/// A binary operation that can be done on two integers of the same type without the alu, if the
+/// alu [wraps integers](ArithmeticsTrait::wrapping_integers)
+#[derive(Debug, Copy, Clone)]
+pub(super) enum IntegerOp {
+    Add,
+    Subtract,
+    Multiply,
+    Gt,
+}
+
+impl IntegerOp {
+    fn of(asm: &Asm) -> Option<Self> {
+        match asm {
+            Asm::Add => Some(IntegerOp::Add),
+            Asm::Subtract => Some(IntegerOp::Subtract),
+            Asm::Multiply => Some(IntegerOp::Multiply),
+            Asm::Gt => Some(IntegerOp::Gt),
+            _ => None,
+        }
+    }
+
+    /// Applies the operation if both values are unsigned or both are signed integers
+    fn apply(self, left: &Value, right: &Value) -> Option<Value> {
+        Some(match (self, left, right) {
+            (IntegerOp::Add, &Value::UInteger(l), &Value::UInteger(r)) => {
+                Value::UInteger(l.wrapping_add(r))
+            }
+            (IntegerOp::Add, &Value::Integer(l), &Value::Integer(r)) => {
+                Value::Integer(l.wrapping_add(r))
+            }
+            (IntegerOp::Subtract, &Value::UInteger(l), &Value::UInteger(r)) => {
+                Value::UInteger(l.wrapping_sub(r))
+            }
+            (IntegerOp::Subtract, &Value::Integer(l), &Value::Integer(r)) => {
+                Value::Integer(l.wrapping_sub(r))
+            }
+            (IntegerOp::Multiply, &Value::UInteger(l), &Value::UInteger(r)) => {
+                Value::UInteger(l.wrapping_mul(r))
+            }
+            (IntegerOp::Multiply, &Value::Integer(l), &Value::Integer(r)) => {
+                Value::Integer(l.wrapping_mul(r))
+            }
+            (IntegerOp::Gt, &Value::UInteger(l), &Value::UInteger(r)) => Value::from(l > r),
+            (IntegerOp::Gt, &Value::Integer(l), &Value::Integer(r)) => Value::from(l > r),
+            _ => return None,
+        })
+    }
+}
+
+fn binary_op<A: ArithmeticsTrait>(asm: &Asm) -> Option<BinaryOp<A>> {
+    let op: BinaryOp<A> = match asm {
+        Asm::Add => |alu, l, r| alu.add(l, r),
+        Asm::Subtract => |alu, l, r| alu.sub(l, r),
+        Asm::Multiply => |alu, l, r| alu.mult(l, r),
+        Asm::Divide => |alu, l, r| alu.div(l, r),
+        Asm::Remainder => |alu, l, r| alu.rem(l, r),
+        Asm::And => |alu, l, r| alu.and(l, r),
+        Asm::Or => |alu, l, r| alu.or(l, r),
+        Asm::Xor => |alu, l, r| alu.xor(l, r),
+        Asm::ShiftLeft => |alu, l, r| alu.shift_left(l, r),
+        Asm::ShiftRight => |alu, l, r| alu.shift_right(l, r),
+        Asm::Gt => |alu, l, r| alu.greater_than(l, r),
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Gets the index a jump from `pc` to a location lands on, if it can be known ahead of time.
+/// Jumps before the first instruction are left to the interpreter, which fails with
+/// [JumpOutOfBounds](VMError::JumpOutOfBounds) if they're taken.
+fn static_target(location: &AsmLocation, pc: usize) -> Option<usize> {
+    match location {
+        &AsmLocation::ByteIndex(i) => Some(i),
+        &AsmLocation::InstructionDiff(diff) => pc.checked_add_signed(diff),
+        AsmLocation::Label(_) => None,
+    }
+}
+
+/// Whether a value popped by a conditional jump causes the jump
+pub(super) fn is_true(value: &Value) -> bool {
+    match value {
+        Value::Byte(b) => *b != 0,
+        r @ Value::Reference(_) => !r.is_null_ptr(),
+        _ => false,
+    }
+}
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    pub(super) fn get_var_value(&self, var: u64) -> Result<Value, VMError> {
+        self.memory
+            .var_value(var as usize)
+            .map_err(|_| VMError::VariableNotSet {
+                var: var as usize,
+                location: self.error_location(),
+            })
+    }
+
+    /// Runs compiled instructions until the vm stops or the program counter leaves the loaded
+    /// instructions. Returns whether any instruction was run.
+    pub(super) fn run_threaded(&mut self) -> Result<bool, VMError> {
+        let mut ops = self.threaded.clone().expect("threaded engine not in use");
+        // without limits, only the executed instructions have to be counted
+        let limited = self.limits.checked_per_instruction();
+        while self.cont {
+            let pc = self.program_counter();
+            let op = match ops.get(pc) {
+                Some(op) if pc > 0 => op,
+                _ => break,
+            };
+            if let (Some(fused), false) = (&op.fused, limited) {
+                if let Some(next) = fused.execute(self, pc) {
+                    self.executed_instructions += fused.width as u64;
+                    self.set_program_counter(next);
+                    continue;
+                }
+            }
+            if limited {
+                self.check_limits()?;
+            } else {
+                self.executed_instructions += 1;
+            }
+            let result = op.single.execute(self, pc);
+            let next = match result {
+                Ok(next) => next,
+                Err(error) => {
+                    self.raise_fault(error)?;
+                    self.program_counter()
+                }
+            };
+            self.set_program_counter(next);
+            // the interpreter may have loaded or relinked instructions
+            if op.single.is_fallback() {
+                ops = self.threaded.clone().expect("threaded engine not in use");
+                if self.scheduler.pending.is_some() {
+                    // the run loop switches fibers
+                    break;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Applies a binary operation to a left value and the value popped from the stack
+    fn apply_binary(&mut self, op: BinaryOp<A>, left: Value) -> Result<(), VMError> {
+        let right = self.pop()?;
+        let output = self.binary_arithmetic(left, right, op)?;
+        self.memory.push(output);
+        Ok(())
+    }
+}
+
+/// Compiles the instruction at `index`, fusing it with the instructions after it if possible
+pub(super) fn compile<'l, M: MemoryTrait, A: ArithmeticsTrait>(
+    instructions: &[Asm],
+    index: usize,
+) -> ThreadedOp<'l, M, A> {
+    ThreadedOp {
+        single: compile_single(&instructions[index], index),
+        fused: compile_fused(instructions, index),
+    }
+}
+
+fn compile_single<'l, M: MemoryTrait, A: ArithmeticsTrait>(
+    asm: &Asm,
+    index: usize,
+) -> Compiled<'l, M, A> {
+    if let Some(op) = binary_op::<A>(asm) {
+        return Compiled::new(binary, Operand::Binary(op));
+    }
+    match asm {
+        Asm::Label(_) | Asm::PublicLabel(_) | Asm::Static | Asm::Nop => {
+            Compiled::new(|_, _, pc| Ok(pc + 1), Operand::None)
+        }
+        Asm::Push(value) => match Scalar::of(value) {
+            Some(scalar) => Compiled::new(push_scalar, Operand::Scalar(scalar)),
+            None => Compiled::new(push, Operand::Value(value.clone())),
+        },
+        Asm::Pop => Compiled::new(
+            |vm, _, pc| {
+                vm.pop()?;
+                Ok(pc + 1)
+            },
+            Operand::None,
+        ),
+        &Asm::SetVar(var) => Compiled::new(set_var, Operand::Var(var)),
+        &Asm::GetVar(var) => Compiled::new(get_var, Operand::Var(var)),
+        Asm::Deref => Compiled::new(deref, Operand::None),
+        Asm::Goto(location) => match static_target(location, index) {
+            Some(target) => Compiled::new(goto, Operand::Target(target)),
+            None => fallback(asm),
+        },
+        Asm::CondGoto(location) => match static_target(location, index) {
+            Some(target) => Compiled::new(cond_goto, Operand::Target(target)),
+            None => fallback(asm),
+        },
+        Asm::GT0 => Compiled::new(gt0, Operand::None),
+        _ => fallback(asm),
+    }
+}
+
+/// Runs an instruction through the interpreter
+fn fallback<'l, M: MemoryTrait, A: ArithmeticsTrait>(asm: &Asm) -> Compiled<'l, M, A> {
+    Compiled::new(
+        |vm, operand, pc| match operand {
+            Operand::Asm(asm) => vm.interpret_instruction(asm, pc),
+            _ => unreachable!(),
+        },
+        Operand::Asm(asm.clone()),
+    )
+}
+
+/// Compiles the instructions starting at `index` into a superinstruction, if they start with a
+/// sequence that can be fused
+fn compile_fused<'l, M: MemoryTrait, A: ArithmeticsTrait>(
+    instructions: &[Asm],
+    index: usize,
+) -> Option<Fused<'l, M, A>> {
+    let rest = &instructions[index..];
+    let mut inputs = vec![];
+    let mut width = 0;
+    while inputs.len() < 2 {
+        match Input::parse(&rest[width..]) {
+            Some((input, input_width)) => {
+                inputs.push(input);
+                width += input_width;
+            }
+            None => break,
+        }
+    }
+    let op = match rest.get(width).and_then(binary_op::<A>) {
+        Some(op) => op,
+        None => {
+            // a variable is still read without a reference to it
+            return match inputs.first() {
+                Some(&Input::Var(var)) => Some(Fused {
+                    handler: get_var_deref,
+                    operand: Operand::Var(var as u64),
+                    width: 2,
+                }),
+                _ => None,
+            };
+        }
+    };
+    let integer_op = IntegerOp::of(&rest[width]);
+    width += 1;
+    let (left, right) = match inputs[..] {
+        [right, left] => (left, right),
+        [left] => (left, Input::Stack),
+        _ => (Input::Stack, Input::Stack),
+    };
+    let output = match rest.get(width) {
+        Some(&Asm::SetVar(var)) => Output::Var(var as usize),
+        Some(Asm::CondGoto(location)) => match static_target(location, index + width) {
+            Some(target) => Output::Branch(target),
+            None => Output::Stack,
+        },
+        _ => Output::Stack,
+    };
+    if !matches!(output, Output::Stack) {
+        width += 1;
+    } else if inputs.is_empty() {
+        // a lone operation has nothing to fuse with
+        return None;
+    }
+    Some(Fused {
+        handler: fused_binary,
+        operand: Operand::FusedBinary(FusedBinary {
+            op,
+            integer_op,
+            left,
+            right,
+            output,
+            width,
+        }),
+        width,
+    })
+}
+
+fn binary<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    let op = match operand {
+        Operand::Binary(op) => *op,
+        _ => unreachable!(),
+    };
+    let left = vm.pop()?;
+    vm.apply_binary(op, left)?;
+    Ok(pc + 1)
+}
+
+fn push<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    if let Operand::Value(value) = operand {
+        vm.memory.push(value.clone());
+    }
+    Ok(pc + 1)
+}
+
+fn push_scalar<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    if let &Operand::Scalar(scalar) = operand {
+        vm.memory.push(scalar.value());
+    }
+    Ok(pc + 1)
+}
+
+fn set_var<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    if let &Operand::Var(var) = operand {
+        let value = vm.pop()?;
+        vm.memory.set_var(var as usize, value);
+    }
+    Ok(pc + 1)
+}
+
+fn get_var<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    if let &Operand::Var(var) = operand {
+        let value = match vm.memory.get_var(var as usize) {
+            Ok(value) => value,
+            Err(_) => {
+                return Err(VMError::VariableNotSet {
+                    var: var as usize,
+                    location: vm.error_location(),
+                })
+            }
+        };
+        vm.memory.push(Value::Reference(JRef::from(value)));
+    }
+    Ok(pc + 1)
+}
+
+fn deref<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    _: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    match vm.pop()? {
+        Value::Reference(reference) => {
+            let value = reference.borrow().clone();
+            vm.memory.push(value);
+            Ok(pc + 1)
+        }
+        v => Err(vm.bad_reference(v)),
+    }
+}
+
+fn goto<M: MemoryTrait, A: ArithmeticsTrait>(
+    _: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    match operand {
+        &Operand::Target(target) => Ok(target),
+        _ => Ok(pc + 1),
+    }
+}
+
+fn cond_goto<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    match operand {
+        &Operand::Target(target) if is_true(&vm.pop()?) => Ok(target),
+        _ => Ok(pc + 1),
+    }
+}
+
+fn gt0<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    _: &Operand<A>,
+    pc: usize,
+) -> Result<usize, VMError> {
+    let boolean = match vm.pop()? {
+        Value::Byte(b) => b > 0,
+        Value::Float(f) => f > 0.0,
+        Value::Integer(i) => i > 0,
+        Value::UInteger(u) => u > 0,
+        v => return Err(vm.type_mismatch(v, "numeric")),
+    };
+    vm.memory.push(Value::from(boolean));
+    Ok(pc + 1)
+}
+
+fn get_var_deref<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Option<usize> {
+    let value = match operand {
+        &Operand::Var(var) => vm.memory.var_value(var as usize).ok()?,
+        _ => unreachable!(),
+    };
+    vm.memory.push(value);
+    Some(pc + 2)
+}
+
+fn fused_binary<M: MemoryTrait, A: ArithmeticsTrait>(
+    vm: &mut VM<M, A>,
+    operand: &Operand<A>,
+    pc: usize,
+) -> Option<usize> {
+    let fused = match operand {
+        Operand::FusedBinary(fused) => fused,
+        _ => unreachable!(),
+    };
+    let left = fused.left.read(&vm.memory, 0)?;
+    let popped = matches!(fused.left, Input::Stack) as usize;
+    let right = fused.right.read(&vm.memory, popped)?;
+    let integer = fused
+        .integer_op
+        .filter(|_| vm.alu.wrapping_integers())
+        .and_then(|op| op.apply(&left, &right));
+    let output = match integer {
+        Some(output) => output,
+        None => (fused.op)(&vm.alu, left, right).ok()?,
+    };
+    let popped = popped + matches!(fused.right, Input::Stack) as usize;
+    for _ in 0..popped {
+        vm.memory.pop();
+    }
+    match fused.output {
+        Output::Stack => vm.memory.push(output),
+        Output::Var(var) => vm.memory.set_var(var, output),
+        Output::Branch(target) if is_true(&output) => return Some(target),
+        Output::Branch(_) => {}
+    }
+    Some(pc + fused.width)
+}
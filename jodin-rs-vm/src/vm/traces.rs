@@ -0,0 +1,112 @@
+//! Recording and replaying the calls with [outside effects](crate::replay).
+//!
+//! The tracer is taken out of the vm while a recorded call runs, so natives called by a plugin
+//! function run as part of its call without being recorded themselves.
+
+use super::natives::Native;
+use super::VM;
+use crate::error::VMError;
+use crate::replay::{Trace, TraceCall, TraceEvent, Tracer};
+use crate::{ArithmeticsTrait, MemoryTrait};
+use jodin_common::assembly::value::Value;
+
+impl<'l, M: MemoryTrait, A: ArithmeticsTrait> VM<'l, M, A> {
+    /// The calls with outside effects the vm has recorded or is replaying, if it was built to do
+    /// either
+    pub fn trace(&self) -> Option<&Trace> {
+        self.tracer.as_ref().map(Tracer::trace)
+    }
+
+    /// Runs a native with outside effects, recording it or answering it from the trace
+    pub(super) fn trace_native(
+        &mut self,
+        native: &str,
+        found: Native<'l, M, A>,
+        args: Vec<Value>,
+    ) -> Result<(), VMError> {
+        let call = TraceCall::Native(native.to_string());
+        if let Some(Tracer::Replaying { .. }) = self.tracer {
+            let event = self.next_event(&call, &args)?;
+            if let Some(result) = event.result {
+                self.memory.push(result);
+            }
+            return Ok(());
+        }
+        let tracer = self.tracer.take();
+        let len = self.memory.stack().len();
+        let output = self.run_native(native, found, args.clone());
+        self.tracer = tracer;
+        output?;
+        let result = match self.memory.stack() {
+            stack if stack.len() > len => stack.last().cloned(),
+            _ => None,
+        };
+        self.record(TraceEvent { call, args, result });
+        Ok(())
+    }
+
+    /// Calls a plugin function, recording it or answering it from the trace
+    pub(super) fn trace_plugin(&mut self, function: &str) -> Result<Value, VMError> {
+        let call = TraceCall::Plugin(function.to_string());
+        if let Some(Tracer::Replaying { trace, next }) = &self.tracer {
+            // how many values the function pops is only known from the recorded call
+            let popped = trace
+                .events
+                .get(*next)
+                .filter(|event| event.call == call)
+                .map_or(0, |event| event.args.len());
+            let stack = self.memory.stack();
+            let args = stack[stack.len().saturating_sub(popped)..].to_vec();
+            let event = self.next_event(&call, &args)?;
+            for _ in 0..args.len() {
+                self.memory.pop();
+            }
+            return Ok(event.result.unwrap_or(Value::Empty));
+        }
+        let tracer = self.tracer.take();
+        let stack = self.memory.stack().to_vec();
+        let output = self.call_plugin(function);
+        self.tracer = tracer;
+        let result = output?;
+        let remaining = self.memory.stack().len().min(stack.len());
+        self.record(TraceEvent {
+            call,
+            args: stack[remaining..].to_vec(),
+            result: Some(result.clone()),
+        });
+        Ok(result)
+    }
+
+    fn record(&mut self, event: TraceEvent) {
+        if let Some(Tracer::Recording(trace)) = &mut self.tracer {
+            trace.events.push(event);
+        }
+    }
+
+    /// Takes the next event of the trace being replayed, failing unless it's a call to `call`
+    /// with `args`
+    fn next_event(&mut self, call: &TraceCall, args: &[Value]) -> Result<TraceEvent, VMError> {
+        let found = TraceEvent {
+            call: call.clone(),
+            args: args.to_vec(),
+            result: None,
+        };
+        let expected = match &mut self.tracer {
+            Some(Tracer::Replaying { trace, next }) => match trace.events.get(*next) {
+                Some(event) if event.call == found.call && event.args == found.args => {
+                    *next += 1;
+                    return Ok(event.clone());
+                }
+                expected => expected
+                    .map(TraceEvent::to_string)
+                    .unwrap_or_else(|| "the end of the trace".to_string()),
+            },
+            _ => unreachable!("not replaying a trace"),
+        };
+        Err(VMError::ReplayDiverged {
+            expected,
+            found: found.to_string(),
+            location: self.error_location(),
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::MinimumMemory;
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::vm_fixture::run_main;
+
+/// `subtract(a, b)` binds its parameters in order, then returns `a - b`
+fn subtract() -> Assembly {
+    vec![
+        Asm::pub_label("subtract"),
+        Asm::SetVar(0),
+        Asm::SetVar(1),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Subtract,
+        Asm::Return,
+    ]
+}
+
+fn run(main: Assembly) -> Result<u32, VMError> {
+    let mut program = vec![Asm::pub_label("main")];
+    program.extend(main);
+    run_main::<MinimumMemory>(VMBuilder::new(), [subtract(), program]).0
+}
+
+#[test]
+fn direct_call() {
+    let result = run(vec![
+        Asm::push(3u64),
+        Asm::push(10u64),
+        Asm::call("subtract", 2),
+        Asm::Return,
+    ]);
+    assert_eq!(result.unwrap(), 7);
+}
+
+#[test]
+fn direct_call_by_index() {
+    let result = run(vec![
+        Asm::push(3u64),
+        Asm::push(10u64),
+        Asm::Call(AsmLocation::ByteIndex(1), 2),
+        Asm::Return,
+    ]);
+    assert_eq!(result.unwrap(), 7);
+}
+
+#[test]
+fn matches_send_message() {
+    let result = run(vec![
+        Asm::push(10u64),
+        Asm::push(3u64),
+        Asm::Pack(2),
+        Asm::push(jodin_common::core::function_names::CALL),
+        Asm::push(Value::Function(AsmLocation::Label("subtract".to_string()))),
+        Asm::SendMessage,
+        Asm::Return,
+    ]);
+    assert_eq!(result.unwrap(), 7);
+}
+
+#[test]
+fn missing_arguments() {
+    let error = run(vec![Asm::push(3u64), Asm::call("subtract", 2), Asm::Return])
+        .expect_err("call should underflow");
+    assert!(matches!(error, VMError::StackUnderflow { .. }), "{error}");
+}
+
+#[test]
+fn unknown_function() {
+    let error =
+        run(vec![Asm::call("nowhere", 0), Asm::Return]).expect_err("function doesn't exist");
+    match error {
+        VMError::UnknownLabel { label, .. } => assert_eq!(label, "nowhere"),
+        e => panic!("wrong error: {e}"),
+    }
+}
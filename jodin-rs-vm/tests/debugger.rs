@@ -0,0 +1,165 @@
+use jodin_common::assembly::debug_info::{DebugInfo, SourcePosition};
+use jodin_common::assembly::instructions::{Asm, Assembly, GetAsm};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_common::core::function_names::CALL;
+use jodin_common::identifier::Identifier;
+use jodin_common::unit::CompilationObject;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::debugger::{Breakpoint, Debugger};
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::VMBuilder;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+fn program() -> Assembly {
+    vec![
+        Asm::pub_label("main"),
+        Asm::push(5u64),
+        Asm::SetVar(0),
+        Asm::push(2u64),
+        Asm::Pack(1),
+        Asm::push(CALL),
+        Asm::push(Value::Function(AsmLocation::Label("double".to_string()))),
+        Asm::SendMessage,
+        Asm::Return,
+        Asm::pub_label("double"),
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Add,
+        Asm::Return,
+    ]
+}
+
+/// Runs the program with a debugger reading the given script, returning the result and everything
+/// the debugger wrote
+fn debug(script: &str, breakpoints: &[Breakpoint]) -> (Result<u32, VMError>, String) {
+    debug_asm(program(), script, breakpoints)
+}
+
+fn debug_asm<G: GetAsm>(
+    asm: G,
+    script: &str,
+    breakpoints: &[Breakpoint],
+) -> (Result<u32, VMError>, String) {
+    let mut output: Vec<u8> = Vec::new();
+    let result = {
+        let mut debugger = Debugger::new(script.as_bytes(), &mut output);
+        for breakpoint in breakpoints {
+            debugger.add_breakpoint(breakpoint.clone());
+        }
+        let mut vm = VMBuilder::new()
+            .memory(VMMemory::default())
+            .alu(MinimumALU)
+            .debugger(debugger)
+            .build()
+            .unwrap();
+        vm.load(asm);
+        vm.run("main")
+    };
+    (result, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn pauses_before_first_instruction() {
+    let (result, output) = debug("", &[]);
+    assert_eq!(result.unwrap(), 4);
+    assert!(output.starts_with("0x0000000000000001 in main: PublicLabel(\"main\")"));
+}
+
+#[test]
+fn breakpoint_on_label() {
+    let (result, output) = debug("c\nbt\nstack\nc\n", &[Breakpoint::parse("double")]);
+    assert_eq!(result.unwrap(), 4);
+    assert!(
+        output.contains("in double: PublicLabel(\"double\")"),
+        "{output}"
+    );
+    assert!(
+        output.contains("#0 0x000000000000000A in double"),
+        "{output}"
+    );
+    assert!(output.contains("#1 0x0000000000000008 in main"), "{output}");
+    assert!(output.contains("0: 2"), "{output}");
+}
+
+#[test]
+fn break_command_on_index() {
+    let (result, output) = debug("b 0xC\nc\nvars\nc\n", &[]);
+    assert_eq!(result.unwrap(), 4);
+    assert!(
+        output.contains("breakpoint 0 at 0x000000000000000C"),
+        "{output}"
+    );
+    assert!(output.contains("in double: GetVar(0)"), "{output}");
+    assert!(output.contains("var 0 = 2"), "{output}");
+}
+
+#[test]
+fn source_locations_and_names() {
+    let mut debug_info = DebugInfo {
+        source_file: Some(PathBuf::from("double.jdn")),
+        ..Default::default()
+    };
+    debug_info.lines.push(9, Some(SourcePosition::new(1, 1)));
+    debug_info.lines.push(10, Some(SourcePosition::new(2, 5)));
+    debug_info
+        .locals
+        .insert("double".to_string(), BTreeMap::from([(0, "n".to_string())]));
+    let object = CompilationObject::new(
+        PathBuf::from("double.jobj"),
+        Identifier::empty(),
+        vec![],
+        program(),
+    )
+    .with_debug_info(debug_info);
+
+    let (result, output) = debug_asm(object, "b 0xC\nc\nvars\nbt\nc\n", &[]);
+    assert_eq!(result.unwrap(), 4);
+    assert!(
+        output.contains("in double at double.jdn:2:5: GetVar(0)"),
+        "{output}"
+    );
+    assert!(output.contains("var 0 (n) = 2"), "{output}");
+    assert!(
+        output.contains("#0 0x000000000000000C in double at double.jdn:2:5"),
+        "{output}"
+    );
+    assert!(
+        output.contains("#1 0x0000000000000008 in main\n"),
+        "{output}"
+    );
+}
+
+#[test]
+fn next_steps_over_calls() {
+    let script = "n\n".repeat(9);
+    let (_, output) = debug(&script, &[]);
+    assert!(output.contains("in main: SendMessage"), "{output}");
+    assert!(output.contains("in main: Return"), "{output}");
+    assert!(!output.contains("in double"), "{output}");
+}
+
+#[test]
+fn step_enters_calls() {
+    let script = "s\n".repeat(8);
+    let (_, output) = debug(&script, &[]);
+    assert!(
+        output.contains("in double: PublicLabel(\"double\")"),
+        "{output}"
+    );
+}
+
+#[test]
+fn quit_stops_the_vm() {
+    let (result, _) = debug("s\nq\n", &[]);
+    match result {
+        Err(VMError::DebuggerQuit { location }) => assert_eq!(location.pc, 2),
+        r => panic!("expected the debugger to stop the vm, got {r:?}"),
+    }
+}
@@ -0,0 +1,314 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::observer::VMObserver;
+use jodin_rs_vm::vm::{Engine, VMBuilder, VM};
+
+const ENGINES: [Engine; 3] = [Engine::Interpreter, Engine::Threaded, Engine::Register];
+
+fn build<'l>(
+    builder: VMBuilder<'l, MinimumALU, MinimumMemory>,
+) -> VM<'l, MinimumMemory, MinimumALU> {
+    builder
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap()
+}
+
+/// Sums the numbers below `n` in a loop
+fn sum_below(n: u64) -> Assembly {
+    vec![
+        Asm::pub_label("main"),
+        Asm::push(0u64),
+        Asm::SetVar(0),
+        Asm::push(0u64),
+        Asm::SetVar(1),
+        Asm::label("loop"),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::push(n),
+        Asm::Gt,
+        Asm::cond_goto("body"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::Return,
+        Asm::label("body"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Add,
+        Asm::SetVar(1),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::push(1u64),
+        Asm::Add,
+        Asm::SetVar(0),
+        Asm::goto("loop"),
+    ]
+}
+
+#[test]
+fn default_engine() {
+    let vm = build(VMBuilder::new());
+    assert_eq!(vm.engine(), Engine::default());
+    let vm = build(VMBuilder::new().engine(Engine::Threaded));
+    assert_eq!(vm.engine(), Engine::Threaded);
+}
+
+#[test]
+fn arithmetic_loop() {
+    for engine in ENGINES {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load(sum_below(100));
+        assert_eq!(vm.run("main").unwrap(), 4950, "{engine:?}");
+    }
+}
+
+#[test]
+fn same_instruction_count() {
+    let counts = ENGINES.map(|engine| {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load(sum_below(10));
+        vm.run("main").unwrap();
+        vm.executed_instructions()
+    });
+    assert_eq!(counts[0], counts[1]);
+    assert_eq!(counts[0], counts[2]);
+}
+
+#[test]
+fn fused_error_location() {
+    let locations = ENGINES.map(|engine| {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::push(0u64),
+            Asm::push(10u64),
+            Asm::Divide,
+            Asm::Halt,
+        ]);
+        let error = vm.run("main").expect_err("division by zero should fail");
+        assert!(matches!(error, VMError::DivisionByZero { .. }), "{error}");
+        error.location().cloned().expect("no location given")
+    });
+    assert_eq!(locations[0], locations[1]);
+    assert_eq!(locations[0], locations[2]);
+}
+
+#[test]
+fn fused_variable_not_set() {
+    let locations = ENGINES.map(|engine| {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::GetVar(0),
+            Asm::Deref,
+            Asm::push(1u64),
+            Asm::Add,
+            Asm::SetVar(1),
+            Asm::Halt,
+        ]);
+        let error = vm.run("main").expect_err("variable 0 was never set");
+        assert!(
+            matches!(error, VMError::VariableNotSet { var: 0, .. }),
+            "{error}"
+        );
+        error.location().cloned().expect("no location given")
+    });
+    assert_eq!(locations[0], locations[1]);
+    assert_eq!(locations[0], locations[2]);
+}
+
+#[test]
+fn fused_mixed_integers() {
+    for engine in ENGINES {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::push(-3i64),
+            Asm::SetVar(0),
+            Asm::GetVar(0),
+            Asm::Deref,
+            Asm::push(5u64),
+            Asm::Add,
+            Asm::SetVar(1),
+            Asm::GetVar(1),
+            Asm::Deref,
+            Asm::push(7u64),
+            Asm::Multiply,
+            Asm::SetVar(1),
+            Asm::push(14u64),
+            Asm::GetVar(1),
+            Asm::Deref,
+            Asm::Gt,
+            Asm::cond_goto("too_big"),
+            Asm::push(13u64),
+            Asm::GetVar(1),
+            Asm::Deref,
+            Asm::Gt,
+            Asm::cond_goto("fourteen"),
+            Asm::push(1u64),
+            Asm::Return,
+            Asm::label("too_big"),
+            Asm::push(2u64),
+            Asm::Return,
+            Asm::label("fourteen"),
+            Asm::push(0u64),
+            Asm::Return,
+        ]);
+        assert_eq!(vm.run("main").unwrap(), 0, "{engine:?}");
+    }
+}
+
+#[derive(Default)]
+struct Counter(usize);
+
+impl VMObserver for Counter {
+    fn on_instruction(&mut self, _pc: usize, _instruction: &Asm) {
+        self.0 += 1;
+    }
+}
+
+#[test]
+fn observed_instructions_are_not_fused() {
+    let counts = ENGINES.map(|engine| {
+        let mut counter = Counter::default();
+        {
+            let mut vm = build(VMBuilder::new().engine(engine).observer(&mut counter));
+            vm.load(sum_below(10));
+            vm.run("main").unwrap();
+        }
+        counter.0
+    });
+    assert_eq!(counts[0], counts[1]);
+    assert_eq!(counts[0], counts[2]);
+}
+
+#[test]
+fn late_loaded_functions() {
+    for engine in ENGINES {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::push(3u64),
+            Asm::call("double", 1),
+            Asm::Return,
+        ]);
+        vm.load(vec![
+            Asm::pub_label("double"),
+            Asm::SetVar(0),
+            Asm::GetVar(0),
+            Asm::Deref,
+            Asm::push(2u64),
+            Asm::Multiply,
+            Asm::Return,
+        ]);
+        assert_eq!(vm.run("main").unwrap(), 6, "{engine:?}");
+    }
+}
+
+#[test]
+fn resume_after_fault_in_registers() {
+    for engine in ENGINES {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load_static(vec![
+            Asm::Push(Value::Function(AsmLocation::Label("handler".to_string()))),
+            Asm::push("division_by_zero"),
+            Asm::native_method("@set_fault_handler", 2),
+            Asm::push(0u64),
+            Asm::Return,
+            Asm::label("handler"),
+            Asm::Pop,
+            Asm::push(5u64),
+            Asm::push("resume"),
+            Asm::Return,
+        ]);
+        // 1000 is held in a register when the division faults
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::push(1000u64),
+            Asm::push(0u64),
+            Asm::push(10u64),
+            Asm::Divide,
+            Asm::Add,
+            Asm::Return,
+        ]);
+        assert_eq!(vm.run("main").unwrap(), 1005, "{engine:?}");
+    }
+}
+
+#[test]
+fn retry_after_fault_in_registers() {
+    for engine in ENGINES {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        // the handler retries with the divisor fixed and the dividend it was given
+        vm.load_static(vec![
+            Asm::Push(Value::Function(AsmLocation::Label("handler".to_string()))),
+            Asm::push("division_by_zero"),
+            Asm::native_method("@set_fault_handler", 2),
+            Asm::push(0u64),
+            Asm::Return,
+            Asm::label("handler"),
+            Asm::SetVar(0),
+            Asm::push(5u64),
+            Asm::GetVar(0),
+            Asm::Deref,
+            Asm::get_attribute("operands"),
+            Asm::Index(1),
+            Asm::push("retry"),
+            Asm::Return,
+        ]);
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::push(1000u64),
+            Asm::push(0u64),
+            Asm::push(10u64),
+            Asm::Divide,
+            Asm::Add,
+            Asm::Return,
+        ]);
+        assert_eq!(vm.run("main").unwrap(), 1002, "{engine:?}");
+    }
+}
+
+#[test]
+fn jumps_before_the_first_instruction() {
+    for engine in ENGINES {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        vm.load(vec![
+            Asm::pub_label("main"),
+            // the conditional jump isn't taken, so only the second jump fails
+            Asm::push(2u64),
+            Asm::push(1u64),
+            Asm::Gt,
+            Asm::CondGoto(AsmLocation::InstructionDiff(-100)),
+            Asm::Goto(AsmLocation::InstructionDiff(-100)),
+        ]);
+        match vm.run("main") {
+            Err(VMError::JumpOutOfBounds { diff, location }) => {
+                assert_eq!(diff, -100, "{engine:?}");
+                assert_eq!(location.pc, 6, "{engine:?}");
+                assert_eq!(location.function.as_deref(), Some("main"), "{engine:?}");
+            }
+            result => panic!("{engine:?} should have failed: {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn spills_when_registers_run_out() {
+    for engine in ENGINES {
+        let mut vm = build(VMBuilder::new().engine(engine));
+        let mut program = vec![Asm::pub_label("main")];
+        program.extend((0..40u64).map(Asm::push));
+        program.extend((0..39).map(|_| Asm::Add));
+        program.push(Asm::Return);
+        vm.load(program);
+        assert_eq!(vm.run("main").unwrap(), 780, "{engine:?}");
+    }
+}
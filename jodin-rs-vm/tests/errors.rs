@@ -0,0 +1,191 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::vm::VMBuilder;
+
+fn run_for_error(asm: Assembly) -> VMError {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .with_stdout(&mut buffer)
+        .build()
+        .unwrap();
+    vm.load(asm);
+    vm.run("main").expect_err("vm should have failed")
+}
+
+#[test]
+fn stack_underflow() {
+    let error = run_for_error(vec![Asm::pub_label("main"), Asm::Pop, Asm::Halt]);
+    assert!(matches!(error, VMError::StackUnderflow { .. }), "{error}");
+    let location = error.location().expect("no location given");
+    assert_eq!(location.function.as_deref(), Some("main"));
+}
+
+#[test]
+fn unknown_label() {
+    let error = run_for_error(vec![Asm::pub_label("main"), Asm::goto("nowhere")]);
+    match error {
+        VMError::UnknownLabel { label, .. } => assert_eq!(label, "nowhere"),
+        e => panic!("wrong error: {e}"),
+    }
+}
+
+#[test]
+fn invalid_write_fd() {
+    let error = run_for_error(vec![
+        Asm::pub_label("main"),
+        Asm::push("hello"),
+        Asm::push(7u64),
+        Asm::native_method("write", 2),
+        Asm::Halt,
+    ]);
+    match error {
+        VMError::InvalidNativeArguments { native, .. } => assert_eq!(native, "write"),
+        e => panic!("wrong error: {e}"),
+    }
+}
+
+#[test]
+fn division_by_zero() {
+    let error = run_for_error(vec![
+        Asm::pub_label("main"),
+        Asm::push(0u64),
+        Asm::push(10u64),
+        Asm::Divide,
+        Asm::Halt,
+    ]);
+    assert!(matches!(error, VMError::DivisionByZero { .. }), "{error}");
+    // the division is the 4th instruction of the loaded block, which starts after the vm's nop
+    assert_eq!(error.location().unwrap().pc, 4);
+}
+
+#[test]
+fn type_mismatch() {
+    let error = run_for_error(vec![
+        Asm::pub_label("main"),
+        Asm::push(Value::Empty),
+        Asm::BooleanNot,
+        Asm::Halt,
+    ]);
+    match error {
+        VMError::TypeMismatch {
+            value, expected, ..
+        } => {
+            assert_eq!(value, Value::Empty);
+            assert_eq!(expected, "boolean");
+        }
+        e => panic!("wrong error: {e}"),
+    }
+}
+
+#[test]
+fn bad_reference() {
+    let error = run_for_error(vec![
+        Asm::pub_label("main"),
+        Asm::push(Value::Empty),
+        Asm::Deref,
+        Asm::Halt,
+    ]);
+    match error {
+        VMError::BadReference { value, .. } => assert_eq!(value, Value::Empty),
+        e => panic!("wrong error: {e}"),
+    }
+}
+
+#[test]
+fn stack_trace() {
+    let error = run_for_error(vec![
+        Asm::pub_label("inner"),
+        Asm::Pop,
+        Asm::Return,
+        Asm::pub_label("outer"),
+        Asm::Call(AsmLocation::Label("inner".to_string()), 0),
+        Asm::Return,
+        Asm::pub_label("main"),
+        Asm::Call(AsmLocation::Label("outer".to_string()), 0),
+        Asm::Halt,
+    ]);
+    assert!(matches!(error, VMError::StackUnderflow { .. }), "{error}");
+    let stack_trace = error.stack_trace().expect("no stack trace given");
+    let functions = stack_trace
+        .frames
+        .iter()
+        .map(|frame| frame.function.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(functions, [Some("inner"), Some("outer"), Some("main")]);
+    assert_eq!(stack_trace.frames[0].pc, error.location().unwrap().pc);
+    let printed = stack_trace.to_string();
+    let lines = printed.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "{printed}");
+    assert!(
+        lines[1].trim_start().starts_with("at outer (0x"),
+        "{printed}"
+    );
+}
+
+#[test]
+fn jump_before_the_first_instruction() {
+    let error = run_for_error(vec![
+        Asm::pub_label("main"),
+        Asm::Nop,
+        Asm::Goto(AsmLocation::InstructionDiff(-10)),
+    ]);
+    match &error {
+        VMError::JumpOutOfBounds { diff, .. } => assert_eq!(*diff, -10),
+        e => panic!("wrong error: {e}"),
+    }
+    let location = error.location().expect("no location given");
+    assert_eq!(location.function.as_deref(), Some("main"));
+    assert_eq!(location.pc, 3);
+}
+
+#[test]
+fn loading_a_label_twice() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    vm.try_load(vec![Asm::pub_label("main"), Asm::push(1u64), Asm::Return])
+        .unwrap();
+    let error = vm
+        .try_load(vec![
+            Asm::pub_label("other"),
+            Asm::push(3u64),
+            Asm::Return,
+            Asm::pub_label("main"),
+            Asm::push(2u64),
+            Asm::Return,
+        ])
+        .expect_err("main was already loaded");
+    match error {
+        VMError::DuplicateLabel(label) => assert_eq!(label, "main"),
+        e => panic!("wrong error: {e}"),
+    }
+    assert_eq!(vm.run("main").unwrap(), 1);
+    assert!(matches!(vm.run("other"), Err(VMError::UnknownLabel { .. })));
+}
+
+#[test]
+fn failing_static_code() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    let error = vm
+        .try_load_static(vec![Asm::Pop, Asm::Return])
+        .expect_err("static code should have failed");
+    assert!(matches!(error, VMError::StackUnderflow { .. }), "{error}");
+    let error = vm
+        .try_load_static(vec![Asm::push(1u64), Asm::Return])
+        .expect_err("static code should have failed");
+    assert!(matches!(error, VMError::StaticCodeFailed(1)), "{error}");
+    vm.try_load_static(vec![Asm::push(0u64), Asm::Return])
+        .unwrap();
+}
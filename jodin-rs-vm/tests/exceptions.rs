@@ -0,0 +1,158 @@
+#[macro_use]
+extern crate jasm_macros;
+
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::jvm_runner::{JVMResult, JVMRunner};
+
+fn run_jasm(asm: Assembly) -> JVMResult {
+    JVMRunner::default().with_jasm(asm).execute().unwrap()
+}
+
+#[test]
+fn catch_thrown_value() {
+    let asm = jasm![
+        label!(pub main);
+        try_!({
+            block![
+                value!(1u64);
+                value!(2u64);
+                throw!(value!(40u64));
+                value!(3u64);
+            ]
+        } catch {
+            block![
+                value!(2u64);
+                Asm::Add;
+            ]
+        });
+        return_!();
+    ];
+    let result = run_jasm(asm);
+    assert_eq!(result.exit_code(), 42);
+}
+
+#[test]
+fn unwind_through_calls() {
+    let asm = jasm![
+        label!(pub main);
+        try_!({
+            block![
+                call!(~ thrower, 5u64);
+                value!(0u64);
+            ]
+        } catch {
+            block![
+                value!(3u64);
+                Asm::Multiply;
+            ]
+        });
+        return_!();
+
+        label!(pub thrower);
+        scope!(push);
+        var!(=> 0);
+        throw!(dvar!(0));
+    ];
+    let result = run_jasm(asm);
+    assert_eq!(result.exit_code(), 15);
+}
+
+#[test]
+fn rethrow_to_outer_handler() {
+    let asm = jasm![
+        label!(pub main);
+        try_!({
+            try_!({
+                throw!(value!(7u64))
+            } catch {
+                throw!(block![value!(1u64); Asm::Add;])
+            })
+        } catch {
+            block![]
+        });
+        return_!();
+    ];
+    let result = run_jasm(asm);
+    assert_eq!(result.exit_code(), 8);
+}
+
+#[test]
+fn scopes_are_unwound() {
+    let mut vm = VMBuilder::new()
+        .memory(VMMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    let asm = jasm![
+        label!(pub main);
+        value!(1u64);
+        var!(=> 0);
+        try_!({
+            block![
+                scope!(push);
+                value!(2u64);
+                var!(=> 0);
+                scope!(push);
+                throw!(value!(()));
+            ]
+        } catch {
+            pop!()
+        });
+        dvar!(0);
+        return_!();
+    ];
+    vm.load(asm);
+    assert_eq!(vm.run("main").unwrap(), 1);
+}
+
+#[test]
+fn uncaught_exception() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push("oops"),
+        Asm::Throw,
+        Asm::Halt,
+    ]);
+    match vm.run("main") {
+        Err(VMError::UncaughtException { value, .. }) => assert_eq!(value, Value::from("oops")),
+        other => panic!("expected uncaught exception, got {other:?}"),
+    }
+}
+
+#[test]
+fn returning_drops_handlers() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    let asm = jasm![
+        label!(pub main);
+        call!(~ returns_in_try);
+        pop!();
+        throw!(value!(1u64));
+
+        label!(pub returns_in_try);
+        try_!({
+            return_!(value!(()))
+        } catch {
+            return_!(value!(()))
+        });
+    ];
+    vm.load(asm);
+    assert!(matches!(
+        vm.run("main"),
+        Err(VMError::UncaughtException { .. })
+    ));
+}
@@ -0,0 +1,206 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::natives::{Arity, NativeCall};
+use jodin_rs_vm::vm::VMBuilder;
+use std::cell::Cell;
+
+/// Creates static code that registers `handler` for `fault`, followed by the handler's body
+fn register_handler(fault: &str, handler: Assembly) -> Assembly {
+    let label = format!("{fault}_handler");
+    let mut asm = vec![
+        Asm::Push(Value::Function(AsmLocation::Label(label.clone()))),
+        Asm::push(fault),
+        Asm::native_method("@set_fault_handler", 2),
+        Asm::push(0u64),
+        Asm::Return,
+        Asm::label(label),
+    ];
+    asm.extend(handler);
+    asm
+}
+
+fn run_with_handler(
+    fault: &str,
+    handler: Assembly,
+    main: Assembly,
+) -> (Result<u32, VMError>, String) {
+    let mut buffer: Vec<u8> = Vec::new();
+    let result = {
+        let mut vm = VMBuilder::new()
+            .memory(MinimumMemory::default())
+            .alu(MinimumALU)
+            .with_stdout(&mut buffer)
+            .build()
+            .unwrap();
+        vm.load_static(register_handler(fault, handler));
+        let mut program = vec![Asm::pub_label("main")];
+        program.extend(main);
+        vm.load(program);
+        vm.run("main")
+    };
+    (result, String::from_utf8(buffer).unwrap())
+}
+
+fn divide_by_zero() -> Assembly {
+    vec![
+        Asm::push(0u64),
+        Asm::push(10u64),
+        Asm::Divide,
+        Asm::push(7u64),
+        Asm::Add,
+        Asm::Return,
+    ]
+}
+
+#[test]
+fn resume_with_value() {
+    let (result, _) = run_with_handler(
+        "division_by_zero",
+        vec![Asm::Pop, Asm::push(5u64), Asm::push("resume"), Asm::Return],
+        divide_by_zero(),
+    );
+    assert_eq!(result.unwrap(), 12);
+}
+
+#[test]
+fn retry_after_defining_symbol() {
+    let (result, _) = run_with_handler(
+        "missing_symbol",
+        vec![
+            Asm::Pop,
+            Asm::Push(Value::Function(AsmLocation::Label("fallback".to_string()))),
+            Asm::SetSymbol("missing".to_string()),
+            Asm::push("retry"),
+            Asm::Return,
+            Asm::label("fallback"),
+            Asm::push(42u64),
+            Asm::Return,
+        ],
+        vec![Asm::goto("missing")],
+    );
+    assert_eq!(result.unwrap(), 42);
+}
+
+#[test]
+fn retry_gets_operands_back() {
+    let attempts = Cell::new(0);
+    let mut buffer: Vec<u8> = Vec::new();
+    let result = {
+        let mut vm = VMBuilder::new()
+            .memory(MinimumMemory::default())
+            .alu(MinimumALU)
+            .with_stdout(&mut buffer)
+            .native("attempt", Arity::Exactly(0), |_: &mut NativeCall| {
+                attempts.set(attempts.get() + 1);
+                Ok(Some(Value::from(match attempts.get() {
+                    1 => "retry",
+                    _ => "abort",
+                })))
+            })
+            .build()
+            .unwrap();
+        // nothing is left for the retry, so the division is given the operands it popped again
+        vm.load_static(register_handler(
+            "division_by_zero",
+            vec![
+                Asm::get_attribute("operands"),
+                Asm::native_method("print", 1),
+                Asm::Pop,
+                Asm::native_method("attempt", 0),
+                Asm::Return,
+            ],
+        ));
+        let mut program = vec![Asm::pub_label("main")];
+        program.extend(divide_by_zero());
+        vm.load(program);
+        vm.run("main")
+    };
+    assert!(
+        matches!(result, Err(VMError::DivisionByZero { .. })),
+        "{result:?}"
+    );
+    assert_eq!(attempts.get(), 2);
+    let out = String::from_utf8(buffer).unwrap();
+    assert_eq!(out, r#"["0u64", "10u64"]["0u64", "10u64"]"#);
+}
+
+#[test]
+fn abort_returns_original_error() {
+    let (result, _) = run_with_handler(
+        "division_by_zero",
+        vec![Asm::Pop, Asm::push("abort"), Asm::Return],
+        divide_by_zero(),
+    );
+    assert!(
+        matches!(result, Err(VMError::DivisionByZero { .. })),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn handler_receives_fault_info() {
+    let (result, out) = run_with_handler(
+        "type_error",
+        vec![
+            Asm::SetVar(0),
+            Asm::GetVar(0),
+            Asm::Deref,
+            Asm::get_attribute("fault"),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::GetVar(0),
+            Asm::Deref,
+            Asm::get_attribute("expected"),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::push(1u64),
+            Asm::push("resume"),
+            Asm::Return,
+        ],
+        vec![Asm::push(3u64), Asm::BooleanNot, Asm::Return],
+    );
+    assert_eq!(result.unwrap(), 1);
+    assert_eq!(out, "type_errorboolean");
+}
+
+#[test]
+fn unhandled_faults_are_errors() {
+    let (result, _) = run_with_handler(
+        "type_error",
+        vec![Asm::Pop, Asm::push("abort"), Asm::Return],
+        divide_by_zero(),
+    );
+    assert!(
+        matches!(result, Err(VMError::DivisionByZero { .. })),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn fault_in_handler_is_double_fault() {
+    let mut handler = vec![Asm::Pop];
+    handler.extend(divide_by_zero());
+    let (result, _) = run_with_handler("division_by_zero", handler, divide_by_zero());
+    assert!(matches!(result, Err(VMError::DoubleFault(_))), "{result:?}");
+}
+
+#[test]
+fn handlers_require_kernel_mode() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    let mut program = vec![Asm::pub_label("main")];
+    program.extend(register_handler("division_by_zero", vec![]));
+    vm.load(program);
+    let result = vm.run("main");
+    assert!(
+        matches!(result, Err(VMError::NotKernelMode { .. })),
+        "{result:?}"
+    );
+}
@@ -0,0 +1,204 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::fiber::MAIN_FIBER;
+use jodin_rs_vm::frame_memory::FrameMemory;
+use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::{Engine, VMBuilder, VM};
+
+const ENGINES: [Engine; 3] = [Engine::Interpreter, Engine::Threaded, Engine::Register];
+
+fn build<'l>(builder: VMBuilder<'l, MinimumALU, FrameMemory>) -> VM<'l, FrameMemory, MinimumALU> {
+    builder
+        .memory(FrameMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap()
+}
+
+fn function(label: &str) -> Value {
+    Value::Function(AsmLocation::Label(label.to_string()))
+}
+
+/// Prints its argument twice, yielding in between, then returns ten times its argument. The
+/// argument is kept in a variable of the fiber's own scope.
+fn worker() -> Assembly {
+    vec![
+        Asm::pub_label("worker"),
+        Asm::native_method("@push_scope", None),
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::native_method("print", 1),
+        Asm::native_method("@yield", None),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::native_method("print", 1),
+        Asm::push(10u64),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Multiply,
+        Asm::native_method("@pop_scope", None),
+        Asm::Return,
+    ]
+}
+
+/// Spawns a worker for 1 and 2, then returns the sum of what they return
+fn spawn_workers() -> Assembly {
+    vec![
+        Asm::pub_label("main"),
+        Asm::push(2u64),
+        Asm::push(function("worker")),
+        Asm::native_method("@spawn", 2),
+        Asm::SetVar(1),
+        Asm::push(1u64),
+        Asm::push(function("worker")),
+        Asm::native_method("@spawn", 2),
+        Asm::native_method("@join", 1),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::native_method("@join", 1),
+        Asm::Add,
+        Asm::Return,
+    ]
+}
+
+#[test]
+fn round_robin() {
+    for engine in ENGINES {
+        let mut out = Vec::<u8>::new();
+        {
+            let mut vm = build(VMBuilder::new().engine(engine).with_stdout(&mut out));
+            vm.load(worker());
+            vm.load(spawn_workers());
+            assert_eq!(vm.run("main").unwrap(), 30, "{engine:?}");
+            assert_eq!(vm.current_fiber(), MAIN_FIBER);
+        }
+        assert_eq!(String::from_utf8(out).unwrap(), "2121", "{engine:?}");
+    }
+}
+
+#[test]
+fn scoped_memory_fibers() {
+    let mut out = Vec::<u8>::new();
+    {
+        let mut vm = VMBuilder::new()
+            .memory(VMMemory::default())
+            .alu(MinimumALU)
+            .with_stdout(&mut out)
+            .build()
+            .unwrap();
+        vm.load(worker());
+        vm.load(spawn_workers());
+        assert_eq!(vm.run("main").unwrap(), 30);
+    }
+    assert_eq!(String::from_utf8(out).unwrap(), "2121");
+}
+
+#[test]
+fn runs_can_be_repeated() {
+    let mut vm = build(VMBuilder::new());
+    vm.load(worker());
+    vm.load(spawn_workers());
+    assert_eq!(vm.run("main").unwrap(), 30);
+    assert_eq!(vm.run("main").unwrap(), 30);
+}
+
+/// Prints its argument three times without yielding
+fn busy_worker() -> Assembly {
+    let mut asm = vec![
+        Asm::pub_label("busy"),
+        Asm::native_method("@push_scope", None),
+        Asm::SetVar(0),
+    ];
+    for _ in 0..3 {
+        asm.extend([Asm::GetVar(0), Asm::Deref, Asm::native_method("print", 1)]);
+    }
+    asm.extend([
+        Asm::native_method("@pop_scope", None),
+        Asm::push(0u64),
+        Asm::Return,
+    ]);
+    asm
+}
+
+fn run_busy_workers(preemption: Option<u64>) -> String {
+    let mut out = Vec::<u8>::new();
+    {
+        let mut builder = VMBuilder::new().with_stdout(&mut out);
+        if let Some(preemption) = preemption {
+            builder = builder.preemption(preemption);
+        }
+        let mut vm = build(builder);
+        vm.load(busy_worker());
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::push(1u64),
+            Asm::push(function("busy")),
+            Asm::native_method("@spawn", 2),
+            Asm::SetVar(1),
+            Asm::push(2u64),
+            Asm::push(function("busy")),
+            Asm::native_method("@spawn", 2),
+            Asm::GetVar(1),
+            Asm::Deref,
+            Asm::native_method("@join", 1),
+            Asm::Pop,
+            Asm::native_method("@join", 1),
+            Asm::Return,
+        ]);
+        assert_eq!(vm.run("main").unwrap(), 0);
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn preemption() {
+    assert_eq!(run_busy_workers(None), "111222");
+    let preempted = run_busy_workers(Some(2));
+    assert_eq!(preempted, "112122");
+    assert_eq!(
+        run_busy_workers(Some(2)),
+        preempted,
+        "scheduling should be deterministic"
+    );
+}
+
+#[test]
+fn joining_each_other_deadlocks() {
+    let mut vm = build(VMBuilder::new());
+    vm.load(vec![
+        Asm::pub_label("join_main"),
+        Asm::push(MAIN_FIBER),
+        Asm::native_method("@join", 1),
+        Asm::Return,
+    ]);
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push(function("join_main")),
+        Asm::native_method("@spawn", 1),
+        Asm::native_method("@join", 1),
+        Asm::Return,
+    ]);
+    let error = vm.run("main").expect_err("fibers should deadlock");
+    assert!(matches!(error, VMError::Deadlock { .. }), "{error}");
+}
+
+#[test]
+fn fiber_can_not_join_itself() {
+    let mut vm = build(VMBuilder::new());
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push(MAIN_FIBER),
+        Asm::native_method("@join", 1),
+        Asm::Return,
+    ]);
+    let error = vm.run("main").expect_err("join should fail");
+    assert!(
+        matches!(error, VMError::InvalidNativeArguments { .. }),
+        "{error}"
+    );
+}
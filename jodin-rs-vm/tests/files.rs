@@ -0,0 +1,262 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::input::{READ_EOF, READ_ERROR, READ_OK, READ_VALUE};
+use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::vm_fixture::{main_function, output_of_main};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The variable the descriptor of the last opened file is kept in
+const FD: u64 = 1;
+
+/// Creates an empty directory to use as the file root of a test
+fn root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("jodin-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// Pushes the descriptor of the last opened file
+fn fd() -> Assembly {
+    vec![Asm::GetVar(FD), Asm::Deref]
+}
+
+/// Pushes a value
+fn arg(value: impl Into<Value>) -> Assembly {
+    vec![Asm::push(value.into())]
+}
+
+/// Calls a native with the values some instructions push, then prints the given entries of what
+/// it answered, separated by spaces
+fn call(native: &str, args: Vec<Assembly>, entries: &[&str]) -> Assembly {
+    let count = args.len();
+    let mut asm = args.into_iter().rev().flatten().collect::<Assembly>();
+    asm.push(Asm::native_method(native, count));
+    asm.push(Asm::SetVar(0));
+    for entry in entries {
+        asm.extend([
+            Asm::push(Value::Array(vec![Value::from(*entry)])),
+            Asm::push("get"),
+            Asm::GetVar(0),
+            Asm::SendMessage,
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::push(" "),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+        ]);
+    }
+    asm
+}
+
+/// Opens a file, keeping its descriptor in [FD]
+fn open(path: &str, mode: &str) -> Assembly {
+    let mut asm = call("open", vec![arg(path), arg(mode)], &[]);
+    asm.extend([
+        Asm::push(Value::Array(vec![Value::from(READ_VALUE)])),
+        Asm::push("get"),
+        Asm::GetVar(0),
+        Asm::SendMessage,
+        Asm::SetVar(FD),
+    ]);
+    asm
+}
+
+/// Prints the size of a file, and whether it is a file or a directory
+fn stat(path: &str) -> Assembly {
+    let mut asm = call("stat", vec![arg(path)], &[]);
+    asm.extend([
+        Asm::push(Value::Array(vec![Value::from(READ_VALUE)])),
+        Asm::push("get"),
+        Asm::GetVar(0),
+        Asm::SendMessage,
+        Asm::SetVar(0),
+    ]);
+    for entry in ["size", "is_file", "is_dir"] {
+        asm.extend([
+            Asm::push(Value::Array(vec![Value::from(entry)])),
+            Asm::push("get"),
+            Asm::GetVar(0),
+            Asm::SendMessage,
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::push(" "),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+        ]);
+    }
+    asm
+}
+
+fn write(s: &str) -> Assembly {
+    call("write", vec![fd(), arg(s)], &[])
+}
+
+fn close() -> Assembly {
+    call("close", vec![fd()], &[])
+}
+
+/// Runs the calls as main, returning what they printed
+fn run(root: Option<&Path>, calls: Vec<Assembly>) -> Result<String, VMError> {
+    let mut builder = VMBuilder::<MinimumALU, VMMemory>::new();
+    if let Some(root) = root {
+        builder = builder.file_root(root);
+    }
+    output_of_main(builder, [main_function(calls.into_iter().flatten())])
+}
+
+#[test]
+fn write_then_read() {
+    let root = root("write-then-read");
+    let output = run(
+        Some(&root),
+        vec![
+            open("notes.txt", "write"),
+            write("hello\n"),
+            write("world"),
+            close(),
+            open("notes.txt", "append"),
+            write("!"),
+            close(),
+            open("notes.txt", "read"),
+            call("read", vec![fd(), arg(2u64)], &[READ_VALUE]),
+            call("read", vec![fd()], &[READ_VALUE]),
+            call("read", vec![fd()], &[READ_OK, READ_EOF]),
+            call("seek", vec![fd(), arg(-3i64), arg("end")], &[READ_VALUE]),
+            call("read", vec![fd(), arg(8u64)], &[READ_VALUE]),
+            close(),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        r#"["104u8", "101u8"] llo
+world! 0 1 9 ["108u8", "100u8", "33u8"] "#
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        "hello\nworld!"
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn directories() {
+    let root = root("directories");
+    fs::create_dir(root.join("data")).unwrap();
+    fs::write(root.join("data/b.csv"), "1,2").unwrap();
+    fs::write(root.join("data/a.csv"), "").unwrap();
+    let output = run(
+        Some(&root),
+        vec![
+            call("list_dir", vec![arg("/data")], &[READ_VALUE]),
+            stat("data/b.csv"),
+            call("remove", vec![arg("data/a.csv")], &[READ_OK]),
+            call("list_dir", vec![arg("data")], &[READ_VALUE]),
+            call("remove", vec![arg("data")], &[READ_OK]),
+            stat("./data/../data/"),
+        ],
+    )
+    .unwrap();
+    let dir_size = fs::metadata(root.join("data")).unwrap().len();
+    assert_eq!(
+        output,
+        format!(r#"["a.csv", "b.csv"] 3 1 0 1 ["b.csv"] 0 {dir_size} 0 1 "#)
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn paths_outside_of_the_root() {
+    let root = root("outside");
+    let outside = root.with_file_name(format!("jodin-{}-outside-secret", std::process::id()));
+    fs::write(&outside, "secret").unwrap();
+    let mut calls = vec![
+        call(
+            "open",
+            vec![arg("../x"), arg("write")],
+            &[READ_OK, READ_ERROR],
+        ),
+        call("stat", vec![arg("a/../../x")], &[READ_ERROR]),
+        call("remove", vec![arg("/")], &[READ_ERROR]),
+    ];
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        calls.push(call("open", vec![arg("link"), arg("read")], &[READ_ERROR]));
+    }
+    let output = run(Some(&root), calls).unwrap();
+    let mut expected = concat!(
+        r#"0 "../x" is outside of the file root "#,
+        r#""a/../../x" is outside of the file root "#,
+        "can not remove the file root ",
+    )
+    .to_string();
+    if cfg!(unix) {
+        expected.push_str(r#""link" is outside of the file root "#);
+    }
+    assert_eq!(output, expected);
+    assert_eq!(fs::read_to_string(&outside).unwrap(), "secret");
+    fs::remove_file(outside).unwrap();
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn files_that_can_not_be_used() {
+    let root = root("unusable");
+    fs::write(root.join("exists"), "").unwrap();
+    let output = run(
+        Some(&root),
+        vec![
+            call("open", vec![arg("missing"), arg("read")], &[READ_OK]),
+            call("open", vec![arg("exists"), arg("create")], &[READ_OK]),
+            call("open", vec![arg("new"), arg("create")], &[READ_OK]),
+        ],
+    )
+    .unwrap();
+    assert_eq!(output, "0 0 1 ");
+    fs::remove_dir_all(root).unwrap();
+
+    let output = run(None, vec![call("stat", vec![arg("exists")], &[READ_ERROR])]).unwrap();
+    assert_eq!(output, "the vm has no file root ");
+}
+
+#[test]
+fn writing_to_a_file_opened_for_reading() {
+    let root = root("read-only");
+    fs::write(root.join("notes.txt"), "hello").unwrap();
+    let output = run(
+        Some(&root),
+        vec![
+            open("notes.txt", "read"),
+            call("write", vec![fd(), arg("world")], &[READ_OK, READ_ERROR]),
+        ],
+    )
+    .unwrap();
+    assert!(
+        output.starts_with("0 "),
+        "write should have failed: {output}"
+    );
+    assert!(output.len() > 2, "write should say why it failed");
+    assert_eq!(fs::read_to_string(root.join("notes.txt")).unwrap(), "hello");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn closed_descriptors() {
+    let root = root("closed");
+    let error = run(
+        Some(&root),
+        vec![open("notes.txt", "write"), close(), write("late")],
+    )
+    .expect_err("file should be closed");
+    match error {
+        VMError::InvalidNativeArguments { native, .. } => assert_eq!(native, "write"),
+        e => panic!("wrong error: {e}"),
+    }
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,167 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::{MemoryTrait, VirtualMachine};
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::frame_memory::FrameMemory;
+use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::VMBuilder;
+
+fn run<M: MemoryTrait>(memory: M, program: Vec<Assembly>) -> u32 {
+    let mut vm = VMBuilder::new()
+        .memory(memory)
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    for asm in program {
+        vm.load(asm);
+    }
+    vm.run("main").expect("vm failed")
+}
+
+/// Enters a function's frame the same way compiled functions do
+fn prologue(name: &str, vars: u64) -> Assembly {
+    vec![
+        Asm::pub_label(name),
+        Asm::push(name),
+        Asm::native_method("@load_scope", 1),
+        Asm::native_method("@push_scope", None),
+        Asm::push(vars),
+        Asm::native_method("@reserve_vars", 1),
+    ]
+}
+
+fn factorial() -> Assembly {
+    let mut asm = prologue("factorial", 1);
+    asm.extend([
+        Asm::SetVar(0),
+        Asm::push(1u64),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Gt,
+        Asm::cond_goto("recurse"),
+        Asm::push(1u64),
+        Asm::native_method("@back_scope", None),
+        Asm::Return,
+        Asm::label("recurse"),
+        Asm::push(1u64),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Subtract,
+        Asm::call("factorial", 1),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Multiply,
+        Asm::native_method("@back_scope", None),
+        Asm::Return,
+    ]);
+    asm
+}
+
+#[test]
+fn recursive_locals() {
+    let main = vec![
+        Asm::pub_label("main"),
+        Asm::push(5u64),
+        Asm::call("factorial", 1),
+        Asm::Return,
+    ];
+    assert_eq!(
+        run(FrameMemory::default(), vec![factorial(), main.clone()]),
+        120
+    );
+    assert_eq!(run(VMMemory::default(), vec![factorial(), main]), 120);
+}
+
+#[test]
+fn closures_capture_variables() {
+    // `increment` loads the scope saved by main, like a closure over main's variable
+    let increment = vec![
+        Asm::pub_label("increment"),
+        Asm::push("counter"),
+        Asm::native_method("@load_scope", 1),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::push(1u64),
+        Asm::Add,
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::native_method("@back_scope", None),
+        Asm::Return,
+    ];
+    let main = vec![
+        Asm::pub_label("main"),
+        Asm::native_method("@push_scope", None),
+        Asm::push(10u64),
+        Asm::SetVar(0),
+        Asm::push("counter"),
+        Asm::native_method("@save_scope", 1),
+        Asm::native_method("@pop_scope", None),
+        Asm::call("increment", 0),
+        Asm::Pop,
+        Asm::call("increment", 0),
+        Asm::Return,
+    ];
+    assert_eq!(run(FrameMemory::default(), vec![increment, main]), 12);
+}
+
+#[test]
+fn frames_are_unwound_by_exceptions() {
+    let main = vec![
+        Asm::pub_label("main"),
+        Asm::push(1u64),
+        Asm::SetVar(0),
+        Asm::PushHandler(AsmLocation::Label("catch".to_string())),
+        Asm::native_method("@push_scope", None),
+        Asm::push(2u64),
+        Asm::SetVar(0),
+        Asm::native_method("@push_scope", None),
+        Asm::push(Value::Empty),
+        Asm::Throw,
+        Asm::label("catch"),
+        Asm::Pop,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Return,
+    ];
+    assert_eq!(run(FrameMemory::default(), vec![main]), 1);
+}
+
+#[test]
+fn reserved_slots_are_unset() {
+    let mut memory = FrameMemory::default();
+    memory.push_scope();
+    memory.reserve_vars(4);
+    assert!(memory.get_var(3).is_err());
+    memory.set_var(0, Value::UInteger(7));
+    assert_eq!(memory.next_var_number(), 1);
+    memory.push_scope();
+    assert!(memory.get_var(0).is_err());
+    memory.pop_scope().unwrap();
+    assert_eq!(*memory.get_var(0).unwrap().borrow(), Value::UInteger(7));
+}
+
+#[test]
+fn leaving_scopes_that_were_never_entered_errors() {
+    for native in ["@pop_scope", "@back_scope"] {
+        let mut vm = VMBuilder::new()
+            .memory(FrameMemory::default())
+            .alu(MinimumALU)
+            .build()
+            .unwrap();
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::native_method(native, None),
+            Asm::push(1u64),
+            Asm::SetVar(0),
+            Asm::push(0u64),
+            Asm::Return,
+        ]);
+        match vm.run("main") {
+            Err(VMError::ScopeUnderflow { native: left, .. }) => assert_eq!(left, native),
+            result => panic!("{native} should have underflowed: {result:?}"),
+        }
+    }
+}
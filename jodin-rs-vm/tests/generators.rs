@@ -0,0 +1,213 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::frame_memory::FrameMemory;
+use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::{Engine, VMBuilder, VM};
+
+const ENGINES: [Engine; 3] = [Engine::Interpreter, Engine::Threaded, Engine::Register];
+
+fn build<'l>(builder: VMBuilder<'l, MinimumALU, FrameMemory>) -> VM<'l, FrameMemory, MinimumALU> {
+    builder
+        .memory(FrameMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap()
+}
+
+fn function(label: &str) -> Value {
+    Value::Function(AsmLocation::Label(label.to_string()))
+}
+
+/// Sends a message with no arguments to the generator in a variable
+fn send(message: &str, var: u64) -> [Asm; 5] {
+    [
+        Asm::push(Value::Array(vec![])),
+        Asm::push(message),
+        Asm::GetVar(var),
+        Asm::Deref,
+        Asm::SendMessage,
+    ]
+}
+
+/// Yields every number from 0 up to its argument, which is kept in a local along with the count
+fn count() -> Assembly {
+    vec![
+        Asm::pub_label("count"),
+        Asm::native_method("@push_scope", None),
+        Asm::SetVar(0),
+        Asm::push(0u64),
+        Asm::SetVar(1),
+        Asm::label("count_loop"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Gt,
+        Asm::BooleanNot,
+        Asm::cond_goto("count_end"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::native_method("@yield_value", 1),
+        Asm::push(1u64),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::Add,
+        Asm::SetVar(1),
+        Asm::goto("count_loop"),
+        Asm::label("count_end"),
+        Asm::native_method("@pop_scope", None),
+        Asm::push(Value::Empty),
+        Asm::Return,
+    ]
+}
+
+/// Creates a generator counting to `n` in variable 5
+fn create_count(n: u64) -> [Asm; 4] {
+    [
+        Asm::push(n),
+        Asm::push(function("count")),
+        Asm::native_method("@generator", 2),
+        Asm::SetVar(5),
+    ]
+}
+
+/// Prints every value of a generator counting to 3
+fn print_all() -> Assembly {
+    let mut asm = vec![Asm::pub_label("main")];
+    asm.extend(create_count(3));
+    asm.push(Asm::label("main_loop"));
+    asm.extend(send("has_next", 5));
+    asm.extend([Asm::BooleanNot, Asm::cond_goto("main_end")]);
+    asm.extend(send("next", 5));
+    asm.extend([
+        Asm::native_method("print", 1),
+        Asm::Pop,
+        Asm::goto("main_loop"),
+        Asm::label("main_end"),
+        Asm::push(0u64),
+        Asm::Return,
+    ]);
+    asm
+}
+
+#[test]
+fn lazy_sequence() {
+    for engine in ENGINES {
+        let mut out = Vec::<u8>::new();
+        {
+            let mut vm = build(VMBuilder::new().engine(engine).with_stdout(&mut out));
+            vm.load(count());
+            vm.load(print_all());
+            assert_eq!(vm.run("main").unwrap(), 0, "{engine:?}");
+        }
+        assert_eq!(String::from_utf8(out).unwrap(), "012", "{engine:?}");
+    }
+}
+
+#[test]
+fn scoped_memory_generators() {
+    let mut out = Vec::<u8>::new();
+    {
+        let mut vm = VMBuilder::new()
+            .memory(VMMemory::default())
+            .alu(MinimumALU)
+            .with_stdout(&mut out)
+            .build()
+            .unwrap();
+        vm.load(count());
+        vm.load(print_all());
+        assert_eq!(vm.run("main").unwrap(), 0);
+        assert_eq!(vm.run("main").unwrap(), 0);
+    }
+    assert_eq!(String::from_utf8(out).unwrap(), "012012");
+}
+
+#[test]
+fn has_next_keeps_the_next_value() {
+    let mut vm = build(VMBuilder::new());
+    vm.load(count());
+    let mut main = vec![Asm::pub_label("main")];
+    main.extend(create_count(10));
+    for _ in 0..2 {
+        main.extend(send("has_next", 5));
+        main.push(Asm::Pop);
+    }
+    main.extend(send("next", 5));
+    main.extend(send("next", 5));
+    main.extend(send("next", 5));
+    main.extend([Asm::Add, Asm::Add, Asm::Return]);
+    vm.load(main);
+    assert_eq!(vm.run("main").unwrap(), 3);
+}
+
+#[test]
+fn next_fails_once_finished() {
+    let mut vm = build(VMBuilder::new());
+    vm.load(count());
+    let mut main = vec![Asm::pub_label("main")];
+    main.extend(create_count(1));
+    main.extend(send("next", 5));
+    main.extend(send("next", 5));
+    main.push(Asm::Return);
+    vm.load(main);
+    let error = vm.run("main").expect_err("generator should be finished");
+    assert!(
+        matches!(error, VMError::GeneratorFinished { .. }),
+        "{error}"
+    );
+}
+
+#[test]
+fn uncaught_exceptions_are_thrown_where_resumed() {
+    let mut vm = build(VMBuilder::new());
+    vm.load(vec![Asm::pub_label("thrower"), Asm::push(7u64), Asm::Throw]);
+    let mut main = vec![
+        Asm::pub_label("main"),
+        Asm::push(function("thrower")),
+        Asm::native_method("@generator", 1),
+        Asm::SetVar(5),
+        Asm::PushHandler(AsmLocation::Label("caught".to_string())),
+    ];
+    main.extend(send("next", 5));
+    main.extend([
+        Asm::PopHandler,
+        Asm::Return,
+        Asm::label("caught"),
+        Asm::SetVar(6),
+    ]);
+    main.extend(send("has_next", 5));
+    main.extend([
+        Asm::BooleanNot,
+        Asm::cond_goto("finished"),
+        Asm::push(0u64),
+        Asm::Return,
+        Asm::label("finished"),
+        Asm::push(1u64),
+        Asm::GetVar(6),
+        Asm::Deref,
+        Asm::Add,
+        Asm::Return,
+    ]);
+    vm.load(main);
+    assert_eq!(vm.run("main").unwrap(), 8);
+}
+
+#[test]
+fn yield_value_needs_a_generator() {
+    let mut vm = build(VMBuilder::new());
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push(0u64),
+        Asm::native_method("@yield_value", 1),
+        Asm::Return,
+    ]);
+    let error = vm.run("main").expect_err("yield should fail");
+    assert!(
+        matches!(error, VMError::InvalidNativeArguments { .. }),
+        "{error}"
+    );
+}
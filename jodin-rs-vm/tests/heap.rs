@@ -0,0 +1,124 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::heap::{CollectionPolicy, Heap};
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::vm::{VMBuilder, VM};
+
+fn build<'l>(policy: CollectionPolicy) -> VM<'l, MinimumMemory, MinimumALU> {
+    VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .collection_policy(policy)
+        .build()
+        .unwrap()
+}
+
+/// Creates an array in the heap that contains a reference to itself, and keeps it in a variable
+/// if `keep` is set
+fn make_cycle(keep: bool) -> Assembly {
+    let mut asm = vec![
+        Asm::pub_label("make_cycle"),
+        Asm::push(Value::Array(vec![])),
+        Asm::GetRef,
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Pack(1),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::SetRef,
+    ];
+    if !keep {
+        asm.extend([Asm::push(Value::Empty), Asm::SetVar(0)]);
+    }
+    asm.extend([Asm::push(0u64), Asm::Return]);
+    asm
+}
+
+#[test]
+fn unreachable_cycles_are_collected() {
+    let mut vm = build(CollectionPolicy::Manual);
+    vm.load(make_cycle(false));
+    vm.run("make_cycle").unwrap();
+    assert_eq!(
+        vm.heap_stats().live_objects,
+        1,
+        "cycle should leak until collected"
+    );
+    assert_eq!(vm.collect_garbage(), 1);
+    let stats = vm.heap_stats();
+    assert_eq!(stats.live_objects, 0);
+    assert_eq!(stats.bytes, 0);
+    assert_eq!(stats.collections, 1);
+    assert_eq!(stats.collected, 1);
+}
+
+#[test]
+fn reachable_cycles_are_kept() {
+    let mut vm = build(CollectionPolicy::Manual);
+    let mut program = make_cycle(true);
+    program.truncate(program.len() - 2);
+    program.extend([
+        Asm::native_method("@collect_garbage", None),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Deref,
+        Asm::Index(0),
+        Asm::Deref,
+        Asm::Index(0),
+        Asm::Pop,
+        Asm::push(3u64),
+        Asm::Return,
+    ]);
+    vm.load(program);
+    assert_eq!(vm.run("make_cycle").unwrap(), 3);
+    let stats = vm.heap_stats();
+    assert_eq!(stats.collections, 1);
+    assert_eq!(stats.collected, 0);
+    assert_eq!(stats.live_objects, 1);
+    assert!(stats.bytes > 0);
+}
+
+#[test]
+fn threshold_collects_while_running() {
+    let mut vm = build(CollectionPolicy::Threshold(8));
+    vm.load(make_cycle(false));
+    for _ in 0..100 {
+        vm.run("make_cycle").unwrap();
+    }
+    let stats = vm.heap_stats();
+    assert_eq!(stats.allocations, 100);
+    assert!(stats.collections >= 10, "{stats:?}");
+    assert!(stats.live_objects <= 8, "{stats:?}");
+
+    let mut vm = build(CollectionPolicy::Manual);
+    vm.load(make_cycle(false));
+    for _ in 0..100 {
+        vm.run("make_cycle").unwrap();
+    }
+    assert_eq!(vm.heap_stats().collections, 0);
+    assert_eq!(vm.heap_stats().live_objects, 100);
+}
+
+#[test]
+fn host_references_are_roots() {
+    let mut heap = Heap::new(CollectionPolicy::Manual);
+    let a = heap.allocate(Value::Empty);
+    let b = heap.allocate(Value::Empty);
+    let (a_ref, b_ref) = match (&a, &b) {
+        (Value::Reference(a), Value::Reference(b)) => (a.clone(), b.clone()),
+        _ => unreachable!(),
+    };
+    a_ref.replace(Value::Array(vec![b.clone()]));
+    b_ref.replace(Value::Array(vec![a.clone()]));
+    // cycles can still be shown
+    assert!(format!("{:?}", a_ref.borrow()).starts_with("Array([Reference(JRef("));
+    drop((a, b, b_ref));
+
+    assert_eq!(heap.collect(), 0, "a is still held by the host");
+    assert_eq!(heap.stats().live_objects, 2);
+    drop(a_ref);
+    assert_eq!(heap.collect(), 2);
+    assert_eq!(heap.stats().live_objects, 0);
+}
@@ -0,0 +1,141 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::input::{READ_EOF, READ_ERROR, READ_OK, READ_VALUE};
+use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::replay::TraceCall;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::{VMBuilder, VM};
+
+fn build<'l>(builder: VMBuilder<'l, MinimumALU, VMMemory>) -> VM<'l, VMMemory, MinimumALU> {
+    builder
+        .memory(VMMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap()
+}
+
+/// Reads with a native, then prints the given entries of what it answered, separated by spaces
+fn read(native: &str, args: Vec<Value>, entries: &[&str]) -> Assembly {
+    let count = args.len();
+    let mut asm = args.into_iter().rev().map(Asm::push).collect::<Assembly>();
+    asm.push(Asm::native_method(native, count));
+    asm.push(Asm::SetVar(0));
+    for entry in entries {
+        asm.extend([
+            Asm::push(Value::Array(vec![Value::from(*entry)])),
+            Asm::push("get"),
+            Asm::GetVar(0),
+            Asm::SendMessage,
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::push(" "),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+        ]);
+    }
+    asm
+}
+
+/// Runs the reads as main with some input, returning what they printed
+fn run(input: &str, reads: Vec<Assembly>) -> String {
+    let mut out = Vec::<u8>::new();
+    {
+        let mut vm = build(
+            VMBuilder::new()
+                .with_stdin(input.as_bytes())
+                .with_stdout(&mut out),
+        );
+        vm.load(program(reads));
+        vm.run("main").unwrap();
+    }
+    String::from_utf8(out).unwrap()
+}
+
+fn program(reads: Vec<Assembly>) -> Assembly {
+    let mut instructions = vec![Asm::pub_label("main")];
+    instructions.extend(reads.into_iter().flatten());
+    instructions.push(Asm::push(0u64));
+    instructions.push(Asm::Return);
+    instructions
+}
+
+#[test]
+fn lines() {
+    let line = || read("read_line", vec![], &[READ_OK, READ_VALUE]);
+    let output = run(
+        "first\r\nsecond\nlast",
+        vec![
+            line(),
+            line(),
+            line(),
+            read("read_line", vec![], &[READ_OK, READ_EOF]),
+        ],
+    );
+    assert_eq!(output, "1 first 1 second 1 last 0 1 ");
+}
+
+#[test]
+fn numbers() {
+    let output = run(
+        "42\n 2.5 \nforty-two\n",
+        vec![
+            read("read_int", vec![], &[READ_VALUE]),
+            read("read_float", vec![], &[READ_VALUE]),
+            read("read_int", vec![], &[READ_OK, READ_EOF, READ_ERROR]),
+            read("read_float", vec![], &[READ_OK, READ_EOF]),
+        ],
+    );
+    assert_eq!(
+        output,
+        "42 2.5 0 0 can not parse \"forty-two\": invalid digit found in string 0 1 "
+    );
+}
+
+#[test]
+fn bytes_and_everything_else() {
+    let output = run(
+        "hi there\nworld",
+        vec![
+            read("read_bytes", vec![2u64.into()], &[READ_VALUE]),
+            read("read_line", vec![], &[READ_VALUE]),
+            read("read_all", vec![], &[READ_VALUE]),
+            read("read_bytes", vec![2u64.into()], &[READ_EOF]),
+            read("read_all", vec![], &[READ_EOF]),
+        ],
+    );
+    assert_eq!(output, r#"["104u8", "105u8"]  there world 1 1 "#);
+}
+
+#[test]
+fn reads_are_replayed() {
+    let reads = || {
+        vec![
+            read("read_line", vec![], &[READ_VALUE]),
+            read("read_int", vec![], &[READ_VALUE]),
+        ]
+    };
+    let mut out = Vec::<u8>::new();
+    let trace = {
+        let mut vm = build(
+            VMBuilder::new()
+                .record()
+                .with_stdin("name\n7\n".as_bytes())
+                .with_stdout(&mut out),
+        );
+        vm.load(program(reads()));
+        vm.run("main").unwrap();
+        vm.trace().unwrap().clone()
+    };
+    assert_eq!(String::from_utf8(out).unwrap(), "name 7 ");
+    assert_eq!(
+        trace.events[0].call,
+        TraceCall::Native("read_line".to_string())
+    );
+
+    // the input isn't given again, so the reads can only be answered from the trace
+    let mut vm = build(VMBuilder::new().replay(trace.clone()).with_stdin(&[][..]));
+    vm.load(program(reads()));
+    vm.run("main").unwrap();
+    assert_eq!(vm.trace(), Some(&trace));
+}
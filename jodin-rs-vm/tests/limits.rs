@@ -0,0 +1,83 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_common::core::function_names::CALL;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::MinimumMemory;
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::vm_fixture::build_vm;
+use std::time::Duration;
+
+/// `while (true) {}`
+fn spin() -> Assembly {
+    vec![
+        Asm::pub_label("main"),
+        Asm::label("loop"),
+        Asm::goto("loop"),
+    ]
+}
+
+#[test]
+fn out_of_fuel() {
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().fuel(100));
+    vm.load(spin());
+    let error = vm.run("main").expect_err("spinning should run out of fuel");
+    assert!(matches!(error, VMError::OutOfFuel { .. }), "{error}");
+    assert_eq!(vm.remaining_fuel(), Some(0));
+    assert_eq!(vm.executed_instructions(), 100);
+}
+
+#[test]
+fn fuel_is_enough() {
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().fuel(3));
+    vm.load(vec![Asm::pub_label("main"), Asm::push(1u64), Asm::Return]);
+    assert_eq!(vm.run("main").unwrap(), 1);
+    assert_eq!(vm.remaining_fuel(), Some(0));
+}
+
+#[test]
+fn deadline_exceeded() {
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().timeout(Duration::from_millis(20)));
+    vm.load(spin());
+    let error = vm
+        .run("main")
+        .expect_err("spinning should pass the deadline");
+    assert!(matches!(error, VMError::DeadlineExceeded { .. }), "{error}");
+}
+
+#[test]
+fn max_stack_size() {
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().max_stack_size(16));
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::label("loop"),
+        Asm::push(1u64),
+        Asm::goto("loop"),
+    ]);
+    match vm
+        .run("main")
+        .expect_err("stack should have grown too large")
+    {
+        VMError::StackLimitExceeded { len, max, .. } => {
+            assert_eq!(len, 17);
+            assert_eq!(max, 16);
+        }
+        e => panic!("wrong error: {e}"),
+    }
+}
+
+#[test]
+fn max_call_depth() {
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().max_call_depth(8));
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::Pack(0),
+        Asm::push(CALL),
+        Asm::push(Value::Function(AsmLocation::Label("main".to_string()))),
+        Asm::SendMessage,
+        Asm::Return,
+    ]);
+    let error = vm.run("main").expect_err("recursion should overflow");
+    assert!(matches!(error, VMError::StackOverflow { .. }), "{error}");
+}
@@ -0,0 +1,89 @@
+use jodin_common::assembly::instructions::Asm;
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_common::core::function_names::CALL;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::vm::VMBuilder;
+
+fn call_label(label: &str) -> Vec<Asm> {
+    vec![
+        Asm::Pack(0),
+        Asm::push(CALL),
+        Asm::push(Value::Function(AsmLocation::Label(label.to_string()))),
+        Asm::SendMessage,
+    ]
+}
+
+#[test]
+fn labels_are_resolved_on_load() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::goto("end"),
+        Asm::push(1u64),
+        Asm::Return,
+        Asm::label("end"),
+        Asm::push(2u64),
+        Asm::Return,
+    ]);
+    assert_eq!(vm.instructions()[2], Asm::Goto(AsmLocation::ByteIndex(5)));
+    assert_eq!(vm.run("main").unwrap(), 2);
+}
+
+#[test]
+fn forward_references_are_relocated() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    let mut main = vec![Asm::pub_label("main")];
+    main.extend(call_label("later"));
+    main.push(Asm::Return);
+    vm.load(main);
+    assert_eq!(
+        vm.instructions()[4],
+        Asm::Push(Value::Function(AsmLocation::Label("later".to_string())))
+    );
+
+    vm.load(vec![Asm::pub_label("later"), Asm::push(3u64), Asm::Return]);
+    let later = vm.label_location("later").unwrap();
+    assert_eq!(
+        vm.instructions()[4],
+        Asm::Push(Value::Function(AsmLocation::ByteIndex(later)))
+    );
+    assert_eq!(vm.run("main").unwrap(), 3);
+}
+
+#[test]
+fn set_symbol_relinks_references() {
+    let mut vm = VMBuilder::new()
+        .memory(MinimumMemory::default())
+        .alu(MinimumALU)
+        .build()
+        .unwrap();
+    let mut main = vec![Asm::pub_label("main")];
+    main.extend(call_label("answer"));
+    main.push(Asm::Return);
+    vm.load(main);
+    vm.load(vec![
+        Asm::pub_label("answer"),
+        Asm::push(1u64),
+        Asm::Return,
+        Asm::pub_label("real_answer"),
+        Asm::push(42u64),
+        Asm::Return,
+    ]);
+    vm.load_static(vec![
+        Asm::GetSymbol("real_answer".to_string()),
+        Asm::SetSymbol("answer".to_string()),
+        Asm::push(0u64),
+        Asm::Return,
+    ]);
+    assert_eq!(vm.run("main").unwrap(), 42);
+}
@@ -3,7 +3,8 @@ extern crate jasm_macros;
 
 use log::{info, LevelFilter, trace};
 use jodin_common::assembly::asm_block::AssemblyBlock;
-use jodin_common::assembly::instructions::Assembly;
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::value::Value;
 use jodin_common::init_logging;
 use jodin_tests_common::jvm_runner::JVMRunner;
 
@@ -65,3 +66,60 @@ fn fibonacci() {
     }
 }
 
+
+fn run_jasm(asm: Assembly) -> jodin_tests_common::jvm_runner::JVMResult {
+    JVMRunner::default()
+        .with_jasm(asm)
+        .execute()
+        .expect("VM should not fail")
+}
+
+#[test]
+fn division_and_remainder() {
+    init_logging(LevelFilter::Info);
+    let asm = jasm![
+        label!(pub main);
+        return_!(
+            expr!(+,
+                expr!(*, expr!(/, 17u32, 5u32), 10u32),
+                expr!(%, 17u32, 5u32)
+            )
+        );
+    ];
+    assert_eq!(run_jasm(asm).exit_code(), 32);
+}
+
+#[test]
+fn bitwise_operations() {
+    init_logging(LevelFilter::Info);
+    let asm = jasm![
+        label!(pub main);
+        return_!(
+            expr!(|,
+                expr!(^, 0b1100u32, 0b1010u32),
+                expr!(&, expr!(<<, 1u32, 4u32), expr!(>>, 0xFFu32, 2u32))
+            )
+        );
+    ];
+    assert_eq!(run_jasm(asm).exit_code(), 0b0001_0110);
+}
+
+#[test]
+fn mixed_type_promotion() {
+    init_logging(LevelFilter::Info);
+    let asm = jasm![
+        label!(pub main);
+        expr!(*, Value::from(1.5), 3u32);
+        Asm::native_method("print", 1);
+        expr!(>>, Value::from(-8i64), 1u8);
+        Asm::native_method("print", 1);
+        return_!(expr!(+, 200u8, 100u32));
+    ];
+    let result = run_jasm(asm);
+    assert_eq!(result.out(), "4.5-4");
+    assert_eq!(
+        result.exit_code(),
+        300,
+        "byte should have been promoted to an unsigned integer"
+    );
+}
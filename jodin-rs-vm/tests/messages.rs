@@ -0,0 +1,161 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::vm_fixture::{main_function, output_of_main};
+
+/// Sends a message to the value pushed by `target`, leaving the answer on the stack
+fn send(target: Asm, message: &str, args: Vec<Value>) -> Assembly {
+    vec![
+        Asm::push(Value::Array(args)),
+        Asm::push(message),
+        target,
+        Asm::SendMessage,
+    ]
+}
+
+/// Sends a message to the value pushed by `target` and prints the answer
+fn print_send(target: Asm, message: &str, args: Vec<Value>) -> Assembly {
+    let mut asm = send(target, message, args);
+    asm.push(Asm::native_method("print", 1));
+    asm.push(Asm::Pop);
+    asm
+}
+
+/// Runs the instructions as main, returning what they printed
+fn run(body: Vec<Assembly>) -> Result<String, VMError> {
+    output_of_main::<VMMemory>(
+        VMBuilder::new(),
+        [main_function(body.into_iter().flatten())],
+    )
+}
+
+/// Prints the answer to one message sent to a value
+fn answer(target: impl Into<Value>, message: &str, args: Vec<Value>) -> Result<String, VMError> {
+    run(vec![print_send(Asm::push(target.into()), message, args)])
+}
+
+#[test]
+fn strings() {
+    let s = "héllo, world";
+    assert_eq!(answer(s, "length", vec![]).unwrap(), "12");
+    assert_eq!(answer(s, "index", vec![1i64.into()]).unwrap(), "é");
+    assert_eq!(
+        answer(s, "slice", vec![7i64.into(), 12i64.into()]).unwrap(),
+        "world"
+    );
+    assert_eq!(
+        answer(s, "concat", vec!["!".into()]).unwrap(),
+        "héllo, world!"
+    );
+    assert_eq!(answer(s, "find", vec!["llo".into()]).unwrap(), "2");
+    assert_eq!(answer(s, "find", vec!["z".into()]).unwrap(), "-1");
+    assert_eq!(
+        answer(s, "split", vec![", ".into()]).unwrap(),
+        r#"["héllo", "world"]"#
+    );
+    assert_eq!(answer(s, "to_upper", vec![]).unwrap(), "HÉLLO, WORLD");
+
+    let error = answer(s, "index", vec![12i64.into()]).expect_err("index should be out of bounds");
+    assert!(
+        matches!(
+            error,
+            VMError::IndexOutOfBounds {
+                index: 12,
+                len: 12,
+                ..
+            }
+        ),
+        "{error}"
+    );
+}
+
+#[test]
+fn arrays_change_behind_references() {
+    let array = Asm::GetVar(0);
+    let output = run(vec![
+        vec![Asm::push(vec![1i64, 2]), Asm::SetVar(0)],
+        send(array.clone(), "push", vec![3i64.into()]),
+        send(array.clone(), "insert", vec![0i64.into(), 0i64.into()]),
+        print_send(array.clone(), "remove", vec![1i64.into()]),
+        print_send(array.clone(), "pop", vec![]),
+        print_send(array.clone(), "len", vec![]),
+        vec![Asm::GetVar(0), Asm::native_method("print", 1)],
+    ])
+    .unwrap();
+    assert_eq!(output, r#"132*["+0i64", "+2i64"]"#);
+}
+
+#[test]
+fn iterate_arrays() {
+    let iterator = Asm::GetVar(1);
+    let output = run(vec![
+        send(Asm::push(vec![10u64, 20]), "iterate", vec![]),
+        vec![Asm::SetVar(1)],
+        print_send(iterator.clone(), "has_next", vec![]),
+        print_send(iterator.clone(), "next", vec![]),
+        print_send(iterator.clone(), "next", vec![]),
+        print_send(iterator.clone(), "has_next", vec![]),
+    ])
+    .unwrap();
+    assert_eq!(output, "110200");
+}
+
+#[test]
+fn dictionaries() {
+    let dict = Value::from([("b", 2i64), ("a", 1i64)]);
+    assert_eq!(
+        answer(dict.clone(), "keys", vec![]).unwrap(),
+        r#"["a", "b"]"#
+    );
+    assert_eq!(
+        answer(dict.clone(), "values", vec![]).unwrap(),
+        r#"["+1i64", "+2i64"]"#
+    );
+    assert_eq!(
+        answer(dict.clone(), "contains", vec!["a".into()]).unwrap(),
+        "1"
+    );
+    assert_eq!(
+        answer(dict.clone(), "contains", vec!["c".into()]).unwrap(),
+        "0"
+    );
+    assert_eq!(answer(dict.clone(), "len", vec![]).unwrap(), "2");
+
+    let output = run(vec![
+        vec![Asm::push(dict), Asm::SetVar(0)],
+        print_send(Asm::GetVar(0), "remove", vec!["b".into()]),
+        print_send(Asm::GetVar(0), "len", vec![]),
+    ])
+    .unwrap();
+    assert_eq!(output, "21");
+}
+
+#[test]
+fn numbers() {
+    assert_eq!(answer(-3i64, "to_string", vec![]).unwrap(), "-3");
+    assert_eq!(answer(2.5f64, "to_string", vec![]).unwrap(), "2.5");
+    assert_eq!(answer(3i64, "lt", vec![4u64.into()]).unwrap(), "1");
+    assert_eq!(answer(2.5f64, "gt", vec![2i64.into()]).unwrap(), "1");
+    assert_eq!(answer(4u64, "eq", vec![4i64.into()]).unwrap(), "1");
+    assert_eq!(answer(4u64, "ne", vec![4i64.into()]).unwrap(), "0");
+    assert_eq!(answer(4u64, "ge", vec![5u64.into()]).unwrap(), "0");
+    assert_eq!(answer(4u64, "le", vec![5u64.into()]).unwrap(), "1");
+    assert_eq!(answer(1i64, "compare", vec![2i64.into()]).unwrap(), "-1");
+    assert_eq!(answer(2i64, "compare", vec![2i64.into()]).unwrap(), "0");
+}
+
+#[test]
+fn unknown_messages_fail() {
+    for target in [
+        Value::from(1i64),
+        Value::from("string"),
+        Value::from(vec![1i64]),
+        Value::from([("a", 1i64)]),
+    ] {
+        let error =
+            answer(target, "frobnicate", vec![]).expect_err("message shouldn't be answered");
+        assert!(matches!(error, VMError::InvalidMessage { .. }), "{error}");
+    }
+}
@@ -0,0 +1,150 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::natives::{Arity, NativeCall};
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::vm_fixture::{build_vm, main_function, output_of_main};
+use std::cell::Cell;
+
+/// Answers the sum of two integers
+fn add(call: &mut NativeCall) -> Result<Option<Value>, VMError> {
+    let a = call.args().int()?;
+    let b = call.args().int()?;
+    Ok(Some(Value::Integer(a + b)))
+}
+
+/// Runs the instructions as main with a vm given some natives, returning what they printed
+fn run(builder: VMBuilder<MinimumALU, MinimumMemory>, body: Assembly) -> Result<String, VMError> {
+    output_of_main(builder, [main_function(body)])
+}
+
+#[test]
+fn registered_natives() {
+    let output = run(
+        VMBuilder::new().native("add", Arity::Exactly(2), add),
+        vec![
+            Asm::push(2i64),
+            Asm::push(3i64),
+            Asm::native_method("add", 2),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            // natives can also be sent messages, with their arguments in order
+            Asm::push(Value::from(vec![Value::from(4i64), Value::from(5u64)])),
+            Asm::push("add"),
+            Asm::push(Value::Native),
+            Asm::SendMessage,
+            Asm::native_method("print", 1),
+            Asm::Pop,
+        ],
+    )
+    .unwrap();
+    assert_eq!(output, "59");
+}
+
+#[test]
+fn natives_write_to_the_vm_stdio() {
+    let output = run(
+        VMBuilder::new().native("shout", Arity::Exactly(1), |call: &mut NativeCall| {
+            let s = call.args().str()?;
+            write!(call.stdout(), "{}!", s.to_uppercase())?;
+            Ok(Some(Value::Empty))
+        }),
+        vec![Asm::push("hello"), Asm::native_method("shout", 1), Asm::Pop],
+    )
+    .unwrap();
+    assert_eq!(output, "HELLO!");
+}
+
+#[test]
+fn natives_can_borrow_from_the_embedder() {
+    let calls = Cell::new(0);
+    let output = run(
+        VMBuilder::new().native("count", Arity::AtLeast(0), |call: &mut NativeCall| {
+            calls.set(calls.get() + call.args().len());
+            Ok(None)
+        }),
+        vec![
+            Asm::push(1u64),
+            Asm::push(2u64),
+            Asm::native_method("count", 2),
+            Asm::native_method("count", 0),
+        ],
+    )
+    .unwrap();
+    assert_eq!(output, "");
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn natives_replace_builtins() {
+    let output = run(
+        VMBuilder::new().native("print", Arity::Exactly(1), |call: &mut NativeCall| {
+            let value = call.args().value()?;
+            write!(call.stdout(), "<{value:#}>")?;
+            Ok(Some(Value::Empty))
+        }),
+        vec![Asm::push(7u64), Asm::native_method("print", 1), Asm::Pop],
+    )
+    .unwrap();
+    assert_eq!(output, "<7>");
+}
+
+#[test]
+fn wrong_arity() {
+    let error = run(
+        VMBuilder::new().native("add", Arity::Exactly(2), add),
+        vec![Asm::push(2i64), Asm::native_method("add", 1)],
+    )
+    .expect_err("add takes two arguments");
+    match error {
+        VMError::InvalidNativeArguments { native, .. } => assert_eq!(native, "add"),
+        e => panic!("wrong error: {e}"),
+    }
+}
+
+#[test]
+fn wrong_argument_type() {
+    let error = run(
+        VMBuilder::new().native("add", Arity::Exactly(2), add),
+        vec![
+            Asm::push("two"),
+            Asm::push(2i64),
+            Asm::native_method("add", 2),
+        ],
+    )
+    .expect_err("add takes integers");
+    assert!(
+        matches!(error, VMError::TypeMismatch { ref expected, .. } if expected == "Integer"),
+        "{error}"
+    );
+}
+
+#[test]
+fn unknown_native() {
+    let error = run(VMBuilder::new(), vec![Asm::native_method("add", 0)])
+        .expect_err("add isn't registered");
+    match error {
+        VMError::InvalidNative { native, .. } => assert_eq!(native, "add"),
+        e => panic!("wrong error: {e}"),
+    }
+}
+
+#[test]
+fn register_while_running() {
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new());
+    vm.register_native("add", Arity::Exactly(2), |call: &mut NativeCall| {
+        let a = call.args().uint()?;
+        let b = call.args().uint()?;
+        Ok(Some(Value::UInteger(a + b)))
+    });
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push(40u64),
+        Asm::push(2u64),
+        Asm::native_method("add", 2),
+        Asm::Return,
+    ]);
+    assert_eq!(vm.run("main").unwrap(), 42);
+}
@@ -0,0 +1,147 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_common::core::function_names::CALL;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::ErrorLocation;
+use jodin_rs_vm::fault::{Fault, FaultAction};
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::observer::VMObserver;
+use jodin_rs_vm::vm::VMBuilder;
+
+/// Records every event as a string
+#[derive(Default)]
+struct Recorder {
+    instructions: usize,
+    events: Vec<String>,
+}
+
+impl VMObserver for Recorder {
+    fn on_instruction(&mut self, _pc: usize, _instruction: &Asm) {
+        self.instructions += 1;
+    }
+
+    fn on_call(&mut self, _pc: usize, function: Option<&str>, depth: usize) {
+        self.events
+            .push(format!("call {} {depth}", function.unwrap_or("<none>")));
+    }
+
+    fn on_return(&mut self, depth: usize) {
+        self.events.push(format!("return {depth}"));
+    }
+
+    fn on_send_message(&mut self, _target: &Value, message: &str, args: &[Value]) {
+        self.events
+            .push(format!("message {message} {}", args.len()));
+    }
+
+    fn on_native(&mut self, native: &str, args: &[Value]) {
+        self.events.push(format!("native {native} {}", args.len()));
+    }
+
+    fn on_fault(&mut self, fault: &Fault, _location: &ErrorLocation) {
+        self.events.push(format!("fault {}", fault.name()));
+    }
+
+    fn on_fault_end(&mut self, fault: &Fault, action: FaultAction) {
+        self.events
+            .push(format!("fault end {} {action:?}", fault.name()));
+    }
+}
+
+fn observe(static_code: Assembly, program: Assembly) -> (u32, Recorder) {
+    let mut recorder = Recorder::default();
+    let mut buffer: Vec<u8> = Vec::new();
+    let result = {
+        let mut vm = VMBuilder::new()
+            .memory(MinimumMemory::default())
+            .alu(MinimumALU)
+            .with_stdout(&mut buffer)
+            .observer(&mut recorder)
+            .build()
+            .unwrap();
+        if !static_code.is_empty() {
+            vm.load_static(static_code);
+        }
+        vm.load(program);
+        vm.run("main").unwrap()
+    };
+    (result, recorder)
+}
+
+#[test]
+fn calls_messages_and_natives() {
+    let (result, recorder) = observe(
+        vec![],
+        vec![
+            Asm::pub_label("main"),
+            Asm::push(3u64),
+            Asm::Pack(1),
+            Asm::push(CALL),
+            Asm::push(Value::Function(AsmLocation::Label("identity".to_string()))),
+            Asm::SendMessage,
+            Asm::Return,
+            Asm::pub_label("identity"),
+            Asm::push("hi"),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::Return,
+        ],
+    );
+    assert_eq!(result, 3);
+    assert_eq!(
+        recorder.events,
+        vec![
+            "call main 1",
+            "message @call 1",
+            "call identity 2",
+            "native print 1",
+            "return 1",
+            "return 0",
+        ]
+    );
+    assert_eq!(recorder.instructions, 12);
+}
+
+#[test]
+fn faults() {
+    let static_code = vec![
+        Asm::Push(Value::Function(AsmLocation::Label("handler".to_string()))),
+        Asm::push("division_by_zero"),
+        Asm::native_method("@set_fault_handler", 2),
+        Asm::push(0u64),
+        Asm::Return,
+        Asm::label("handler"),
+        Asm::Pop,
+        Asm::push(1u64),
+        Asm::push("resume"),
+        Asm::Return,
+    ];
+    let (result, recorder) = observe(
+        static_code,
+        vec![
+            Asm::pub_label("main"),
+            Asm::push(0u64),
+            Asm::push(10u64),
+            Asm::Divide,
+            Asm::Return,
+        ],
+    );
+    assert_eq!(result, 1);
+    // the handler runs in its own counter stack, so its return is at the same depth as main's
+    let events = recorder
+        .events
+        .iter()
+        .skip_while(|e| !e.starts_with("fault"))
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            "fault division_by_zero",
+            "return 1",
+            "fault end division_by_zero Resume",
+            "return 1",
+        ]
+    );
+}
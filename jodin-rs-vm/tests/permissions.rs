@@ -0,0 +1,221 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::MinimumMemory;
+use jodin_rs_vm::natives::{Arity, NativeCall};
+use jodin_rs_vm::permissions::{Capability, Permissions};
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::vm_fixture::{build_vm, main_function, run_main};
+
+/// Calls a native with some arguments, dropping what it answers. `@` natives don't answer
+/// anything.
+fn call(native: &str, args: Vec<Value>) -> Assembly {
+    let count = args.len();
+    let mut asm = args.into_iter().rev().map(Asm::push).collect::<Assembly>();
+    asm.push(Asm::native_method(native, count));
+    if !native.starts_with('@') {
+        asm.push(Asm::Pop);
+    }
+    asm
+}
+
+/// Runs the calls as main with some permissions, returning what they printed
+fn run(permissions: Permissions, calls: Vec<Assembly>) -> (Result<u32, VMError>, String) {
+    run_main::<MinimumMemory>(
+        VMBuilder::new().permissions(permissions),
+        [main_function(calls.into_iter().flatten())],
+    )
+}
+
+fn assert_denied(result: Result<u32, VMError>, call: &str, capability: Capability) {
+    match result {
+        Err(VMError::PermissionDenied {
+            call: denied,
+            capability: needed,
+            ..
+        }) => {
+            assert_eq!(denied, call);
+            assert_eq!(needed, capability);
+        }
+        result => panic!("{call:?} should have been denied: {result:?}"),
+    }
+}
+
+#[test]
+fn everything_is_allowed_by_default() {
+    let permissions = Permissions::default();
+    assert!(Capability::ALL.iter().all(|&c| permissions.allows(c)));
+    let (result, out) = run(
+        permissions,
+        vec![
+            call("print", vec!["hi".into()]),
+            call("@global_scope", vec![]),
+            call("@back_scope", vec![]),
+        ],
+    );
+    result.unwrap();
+    assert_eq!(out, "hi");
+}
+
+#[test]
+fn denied_natives_do_not_run() {
+    let (result, out) = run(Permissions::none(), vec![call("print", vec!["hi".into()])]);
+    assert_denied(result, "print", Capability::Console);
+    assert_eq!(out, "");
+
+    let (result, _) = run(
+        Permissions::all().deny(Capability::Plugins),
+        vec![call("dynamic_call", vec!["plugin_function".into()])],
+    );
+    assert_denied(result, "dynamic_call", Capability::Plugins);
+
+    let (result, _) = run(
+        Permissions::all().deny(Capability::Scopes),
+        vec![call("@global_scope", vec![])],
+    );
+    assert_denied(result, "@global_scope", Capability::Scopes);
+
+    let (result, _) = run(
+        Permissions::all().deny(Capability::Scopes),
+        vec![call("@save_scope", vec!["saved".into()])],
+    );
+    assert_denied(result, "@save_scope", Capability::Scopes);
+}
+
+#[test]
+fn functions_can_only_enter_their_own_scope() {
+    // compiled functions load the scope saved under their label, then back out of it
+    let (result, _) = run(
+        Permissions::none(),
+        vec![
+            call("@load_scope", vec!["main".into()]),
+            call("@push_scope", vec![]),
+            call("@back_scope", vec![]),
+        ],
+    );
+    result.unwrap();
+
+    let (result, _) = run(
+        Permissions::none(),
+        vec![call("@load_scope", vec!["anything".into()])],
+    );
+    assert_denied(result, "@load_scope", Capability::Scopes);
+
+    // backing out again would leave the scopes of the caller
+    let (result, _) = run(
+        Permissions::none(),
+        vec![
+            call("@load_scope", vec!["main".into()]),
+            call("@back_scope", vec![]),
+            call("@back_scope", vec![]),
+        ],
+    );
+    assert_denied(result, "@back_scope", Capability::Scopes);
+}
+
+#[test]
+fn only_allowed_groups_can_be_used() {
+    let (result, out) = run(
+        Permissions::none().allow(Capability::Console),
+        vec![
+            call("print", vec!["hi".into()]),
+            call("write", vec![1u64.into(), " there".into()]),
+            call("ref", vec![3u64.into()]),
+            call("read_line", vec![]),
+        ],
+    );
+    assert_denied(result, "read_line", Capability::Stdin);
+    assert_eq!(out, "hi there");
+
+    // writing to a file needs the filesystem, not the console
+    let (result, _) = run(
+        Permissions::none().allow(Capability::Console),
+        vec![call("write", vec![3u64.into(), "x".into()])],
+    );
+    assert_denied(result, "write", Capability::Filesystem);
+    let (result, _) = run(
+        Permissions::all().deny(Capability::Console),
+        vec![call("write", vec![2u64.into(), "x".into()])],
+    );
+    assert_denied(result, "write", Capability::Console);
+}
+
+#[test]
+fn registered_natives_can_need_capabilities() {
+    let now = |_: &mut NativeCall| Ok(Some(Value::UInteger(1000)));
+    let roll = |_: &mut NativeCall| Ok(Some(Value::UInteger(4)));
+    let mut vm = build_vm::<MinimumMemory>(
+        VMBuilder::new()
+            .permissions(
+                Permissions::all()
+                    .deny(Capability::Clock)
+                    .deny(Capability::Random),
+            )
+            .native_in(Capability::Clock, "now", Arity::Exactly(0), now)
+            .native("answer", Arity::Exactly(0), |_: &mut NativeCall| {
+                Ok(Some(Value::UInteger(42)))
+            }),
+    );
+    vm.register_native_in(Capability::Random, "roll", Arity::Exactly(0), roll);
+    assert!(!vm.permissions().allows(Capability::Clock));
+
+    vm.load(vec![
+        Asm::pub_label("answer"),
+        Asm::native_method("answer", 0),
+        Asm::Return,
+        Asm::pub_label("now"),
+        Asm::native_method("now", 0),
+        Asm::Return,
+        Asm::pub_label("roll"),
+        Asm::native_method("roll", 0),
+        Asm::Return,
+    ]);
+    assert_eq!(vm.run("answer").unwrap(), 42);
+    assert_denied(vm.run("now"), "now", Capability::Clock);
+    assert_denied(vm.run("roll"), "roll", Capability::Random);
+}
+
+#[test]
+fn denials_can_be_handled() {
+    let mut out = Vec::<u8>::new();
+    let result = {
+        let mut vm = build_vm::<MinimumMemory>(
+            VMBuilder::new()
+                .permissions(Permissions::none().allow(Capability::Console))
+                .with_stdout(&mut out),
+        );
+        vm.load_static(vec![
+            Asm::Push(Value::Function(AsmLocation::Label("handler".to_string()))),
+            Asm::push("permission_denied"),
+            Asm::native_method("@set_fault_handler", 2),
+            Asm::push(0u64),
+            Asm::Return,
+            Asm::label("handler"),
+            Asm::SetVar(0),
+            Asm::GetVar(0),
+            Asm::get_attribute("call"),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::push(" "),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::GetVar(0),
+            Asm::get_attribute("capability"),
+            Asm::native_method("print", 1),
+            Asm::Pop,
+            Asm::push(7u64),
+            Asm::push("resume"),
+            Asm::Return,
+        ]);
+        vm.load(vec![
+            Asm::pub_label("main"),
+            Asm::native_method("read_int", 0),
+            Asm::Return,
+        ]);
+        vm.run("main")
+    };
+    assert_eq!(result.unwrap(), 7);
+    assert_eq!(String::from_utf8(out).unwrap(), "read_int stdin");
+}
@@ -0,0 +1,92 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_common::core::function_names::CALL;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::mvp::{MinimumALU, MinimumMemory};
+use jodin_rs_vm::profiler::{ProfileWeight, Profiler};
+use jodin_rs_vm::vm::VMBuilder;
+
+fn call_countdown() -> Assembly {
+    vec![
+        Asm::Pack(1),
+        Asm::push(CALL),
+        Asm::push(Value::Function(AsmLocation::Label("countdown".to_string()))),
+        Asm::SendMessage,
+    ]
+}
+
+/// `countdown(n)` recurses until `n` is 0, then returns 0
+fn program(n: u64) -> Assembly {
+    let mut program = vec![Asm::pub_label("main"), Asm::push(n)];
+    program.extend(call_countdown());
+    program.extend([
+        Asm::Return,
+        Asm::pub_label("countdown"),
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::GT0,
+        Asm::cond_goto("countdown_recurse"),
+        Asm::push(0u64),
+        Asm::Return,
+        Asm::label("countdown_recurse"),
+        Asm::push(1u64),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Subtract,
+    ]);
+    program.extend(call_countdown());
+    program.push(Asm::Return);
+    program
+}
+
+fn profile(n: u64) -> Profiler {
+    let mut profiler = Profiler::new();
+    {
+        let mut vm = VMBuilder::new()
+            .memory(MinimumMemory::default())
+            .alu(MinimumALU)
+            .observer(&mut profiler)
+            .build()
+            .unwrap();
+        vm.load(program(n));
+        assert_eq!(vm.run("main").unwrap(), 0);
+    }
+    profiler
+}
+
+#[test]
+fn folded_stacks() {
+    let profiler = profile(2);
+    assert_eq!(
+        profiler.folded_stacks(ProfileWeight::Instructions),
+        "main 7\nmain;countdown 16\nmain;countdown;countdown 16\nmain;countdown;countdown;countdown 8\n"
+    );
+}
+
+#[test]
+fn inclusive_and_exclusive_totals() {
+    let profiler = profile(3);
+    let functions = profiler.functions();
+    assert_eq!(functions[0].name, "main");
+    assert_eq!(functions[0].calls, 1);
+    assert_eq!(functions[0].exclusive_instructions, 7);
+    assert_eq!(
+        functions[0].inclusive_instructions,
+        profiler.total_instructions()
+    );
+
+    let countdown = &functions[1];
+    assert_eq!(countdown.name, "countdown");
+    assert_eq!(countdown.calls, 4);
+    // recursion is only counted once towards inclusive totals
+    assert_eq!(countdown.inclusive_instructions, 3 * 16 + 8);
+    assert_eq!(
+        countdown.exclusive_instructions,
+        countdown.inclusive_instructions
+    );
+
+    let report = profiler.report();
+    assert!(report.lines().any(|line| line.starts_with("countdown")));
+}
@@ -0,0 +1,132 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::VirtualMachine;
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::MinimumMemory;
+use jodin_rs_vm::replay::{Trace, TraceCall};
+use jodin_rs_vm::vm::VMBuilder;
+use jodin_tests_common::vm_fixture::build_vm;
+use jodin_vm_plugins::plugins::VMHandle;
+use jodin_vm_plugins::Plugin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Provides `next`, which adds a count that goes up with every call to its argument
+struct Counter(Arc<AtomicU64>);
+
+impl Plugin for Counter {
+    fn labels(&self, buffer: &mut [&'static str]) {
+        buffer[0] = "next";
+    }
+
+    fn labels_count(&self) -> i32 {
+        1
+    }
+
+    fn call_label(
+        &self,
+        _label: &str,
+        handle: &mut dyn VMHandle,
+        output: &mut Option<Result<Value, String>>,
+    ) {
+        let mut arg = None;
+        handle.stack().pop(&mut arg);
+        let count = self.0.fetch_add(1, Ordering::Relaxed) + 1;
+        *output = Some(match arg {
+            Some(Value::UInteger(arg)) => Ok(Value::UInteger(arg + count)),
+            v => Err(format!("expected an unsigned int, found {v:?}")),
+        });
+    }
+}
+
+/// Prints the count added to 10, then writes "!" and returns the count added to 20
+fn counting() -> Assembly {
+    vec![
+        Asm::pub_label("main"),
+        Asm::push(10u64),
+        Asm::Call(AsmLocation::Label("next".to_string()), 1),
+        Asm::native_method("print", 1),
+        Asm::Pop,
+        Asm::push("!"),
+        Asm::push(1u64),
+        Asm::native_method("write", 2),
+        Asm::Pop,
+        Asm::push(20u64),
+        Asm::Call(AsmLocation::Label("next".to_string()), 1),
+        Asm::Return,
+    ]
+}
+
+fn record() -> (Trace, String) {
+    let mut out = Vec::<u8>::new();
+    let trace = {
+        let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().record().with_stdout(&mut out));
+        vm.with_plugin(Counter(Arc::new(AtomicU64::new(0))));
+        vm.load(counting());
+        assert_eq!(vm.run("main").unwrap(), 22);
+        vm.trace().unwrap().clone()
+    };
+    (trace, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn record_calls() {
+    let (trace, out) = record();
+    assert_eq!(out, "11!");
+    let calls = trace
+        .events
+        .iter()
+        .map(|event| event.call.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        calls,
+        [
+            TraceCall::Plugin("next".to_string()),
+            TraceCall::Native("print".to_string()),
+            TraceCall::Native("write".to_string()),
+            TraceCall::Plugin("next".to_string()),
+        ]
+    );
+    assert_eq!(trace.events[0].args, [Value::from(10u64)]);
+    assert_eq!(trace.events[0].result, Some(Value::from(11u64)));
+}
+
+#[test]
+fn replay_without_outside_effects() {
+    let (trace, _) = record();
+    let mut saved = vec![];
+    trace.save(&mut saved).unwrap();
+    let trace = Trace::load(&saved[..]).unwrap();
+
+    let count = Arc::new(AtomicU64::new(100));
+    let mut out = Vec::<u8>::new();
+    {
+        let mut vm =
+            build_vm::<MinimumMemory>(VMBuilder::new().replay(trace).with_stdout(&mut out));
+        vm.with_plugin(Counter(count.clone()));
+        vm.load(counting());
+        assert_eq!(vm.run("main").unwrap(), 22);
+    }
+    assert!(out.is_empty(), "nothing should be written while replaying");
+    assert_eq!(
+        count.load(Ordering::Relaxed),
+        100,
+        "plugins shouldn't be called"
+    );
+}
+
+#[test]
+fn replay_diverges() {
+    let (trace, _) = record();
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().replay(trace));
+    vm.with_plugin(Counter(Arc::new(AtomicU64::new(0))));
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push(11u64),
+        Asm::Call(AsmLocation::Label("next".to_string()), 1),
+        Asm::Return,
+    ]);
+    let error = vm.run("main").expect_err("replay should diverge");
+    assert!(matches!(error, VMError::ReplayDiverged { .. }), "{error}");
+}
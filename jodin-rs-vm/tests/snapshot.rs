@@ -0,0 +1,231 @@
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_common::assembly::location::AsmLocation;
+use jodin_common::assembly::value::Value;
+use jodin_rs_vm::core_traits::{MemoryTrait, VirtualMachine};
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::frame_memory::FrameMemory;
+use jodin_rs_vm::mvp::MinimumMemory;
+use jodin_rs_vm::scoped_memory::VMMemory;
+use jodin_rs_vm::vm::{Engine, VMBuilder};
+use jodin_tests_common::vm_fixture::build_vm;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::path::PathBuf;
+
+fn snapshot_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("jodin-{}-{name}.snapshot", std::process::id()))
+}
+
+/// Sums 0 to 9 into a heap value through one variable, then returns it through another variable
+/// that refers to the same value
+fn sum_through_alias() -> Assembly {
+    vec![
+        Asm::pub_label("main"),
+        Asm::push(0u64),
+        Asm::GetRef,
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::SetVar(1),
+        Asm::push(0u64),
+        Asm::SetVar(2),
+        Asm::label("loop"),
+        Asm::push(9u64),
+        Asm::GetVar(2),
+        Asm::Deref,
+        Asm::Gt,
+        Asm::cond_goto("end"),
+        Asm::GetVar(2),
+        Asm::Deref,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Deref,
+        Asm::Add,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::SetRef,
+        Asm::push(1u64),
+        Asm::GetVar(2),
+        Asm::Deref,
+        Asm::Add,
+        Asm::SetVar(2),
+        Asm::goto("loop"),
+        Asm::label("end"),
+        Asm::GetVar(1),
+        Asm::Deref,
+        Asm::Deref,
+        Asm::Return,
+    ]
+}
+
+/// Runs a program until it runs out of fuel, then snapshots it to a file and finishes the run in
+/// a new vm restored from it
+fn checkpoint<M>(name: &str, program: Assembly, fuel: u64, engine: Engine) -> u32
+where
+    M: MemoryTrait + Default + Serialize + DeserializeOwned,
+{
+    let path = snapshot_path(name);
+    {
+        let mut vm = build_vm::<M>(VMBuilder::new().fuel(fuel));
+        vm.load(program);
+        let error = vm.run("main").expect_err("run should stop");
+        assert!(matches!(error, VMError::OutOfFuel { .. }), "{error}");
+        vm.snapshot(File::create(&path).unwrap()).unwrap();
+    }
+    let mut vm = build_vm::<M>(VMBuilder::new().engine(engine));
+    vm.restore(File::open(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    vm.resume().unwrap()
+}
+
+#[test]
+fn resume_after_restoring() {
+    let mut vm = build_vm::<FrameMemory>(VMBuilder::new());
+    vm.load(sum_through_alias());
+    assert_eq!(vm.run("main").unwrap(), 45);
+
+    for fuel in [5, 40, 100] {
+        for engine in [Engine::Interpreter, Engine::Threaded, Engine::Register] {
+            let name = format!("frames-{fuel}-{engine:?}");
+            assert_eq!(
+                checkpoint::<FrameMemory>(&name, sum_through_alias(), fuel, engine),
+                45,
+                "{name}"
+            );
+        }
+        let name = format!("scoped-{fuel}");
+        let result = checkpoint::<VMMemory>(&name, sum_through_alias(), fuel, Engine::Interpreter);
+        assert_eq!(result, 45, "{name}");
+        let name = format!("minimum-{fuel}");
+        let result =
+            checkpoint::<MinimumMemory>(&name, sum_through_alias(), fuel, Engine::Interpreter);
+        assert_eq!(result, 45, "{name}");
+    }
+}
+
+#[test]
+fn references_to_variables_stay_shared() {
+    // stops with a reference to variable 0 on the stack, then sets the variable through another
+    let program = vec![
+        Asm::pub_label("main"),
+        Asm::push(1u64),
+        Asm::SetVar(0),
+        Asm::GetVar(0),
+        Asm::push(5u64),
+        Asm::GetVar(0),
+        Asm::SetRef,
+        Asm::Deref,
+        Asm::Return,
+    ];
+    assert_eq!(
+        checkpoint::<FrameMemory>("variables", program.clone(), 4, Engine::Interpreter),
+        5
+    );
+    assert_eq!(
+        checkpoint::<VMMemory>("scoped-variables", program, 4, Engine::Interpreter),
+        5
+    );
+}
+
+#[test]
+fn cycles_survive() {
+    let mut vm = build_vm::<FrameMemory>(VMBuilder::new().fuel(8));
+    // a dictionary that refers to itself
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push(Value::from([("value", 3u64)])),
+        Asm::GetRef,
+        Asm::SetVar(0),
+        Asm::push("self"),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::Pack(2),
+        Asm::push("put"),
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::SendMessage,
+        Asm::Pop,
+        Asm::GetVar(0),
+        Asm::Deref,
+        Asm::get_attribute("self"),
+        Asm::get_attribute("self"),
+        Asm::get_attribute("value"),
+        Asm::Return,
+    ]);
+    vm.run("main").expect_err("run should stop");
+    vm.add_fuel(8);
+    vm.resume().expect_err("run should stop");
+
+    let mut snapshot = vec![];
+    vm.snapshot(&mut snapshot).unwrap();
+    let mut restored = build_vm::<FrameMemory>(VMBuilder::new());
+    restored.restore(&snapshot[..]).unwrap();
+    assert_eq!(restored.heap_stats().live_objects, 1);
+    assert_eq!(restored.resume().unwrap(), 3);
+}
+
+#[test]
+fn restored_in_fault_handler() {
+    let mut vm = build_vm::<MinimumMemory>(VMBuilder::new().fuel(9));
+    vm.load_static(vec![
+        Asm::Push(Value::Function(AsmLocation::Label("handler".to_string()))),
+        Asm::push("division_by_zero"),
+        Asm::native_method("@set_fault_handler", 2),
+        Asm::push(0u64),
+        Asm::Return,
+        Asm::label("handler"),
+        Asm::Pop,
+        Asm::push(5u64),
+        Asm::push("resume"),
+        Asm::Return,
+    ]);
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::push(0u64),
+        Asm::push(10u64),
+        Asm::Divide,
+        Asm::push(7u64),
+        Asm::Add,
+        Asm::Return,
+    ]);
+    // runs out of fuel in the fault handler
+    vm.run("main").expect_err("run should stop");
+    assert!(vm.in_fault());
+
+    let mut snapshot = vec![];
+    vm.snapshot(&mut snapshot).unwrap();
+    let mut restored = build_vm::<MinimumMemory>(VMBuilder::new());
+    restored.restore(&snapshot[..]).unwrap();
+    assert_eq!(restored.resume().unwrap(), 12);
+}
+
+#[test]
+fn no_snapshots_with_live_generators() {
+    let mut vm = build_vm::<FrameMemory>(VMBuilder::new().fuel(5));
+    vm.load(vec![
+        Asm::pub_label("count"),
+        Asm::push(Value::Empty),
+        Asm::Return,
+    ]);
+    vm.load(vec![
+        Asm::pub_label("main"),
+        Asm::Push(Value::Function(AsmLocation::Label("count".to_string()))),
+        Asm::native_method("@generator", 1),
+        Asm::SetVar(0),
+        Asm::push(0u64),
+        Asm::Return,
+    ]);
+    vm.run("main").expect_err("run should stop");
+    let error = vm.snapshot(vec![]).expect_err("snapshot should fail");
+    assert!(matches!(error, VMError::SnapshotUnavailable(_)), "{error}");
+}
+
+#[test]
+fn nothing_to_resume() {
+    let mut vm = build_vm::<FrameMemory>(VMBuilder::new());
+    vm.load(vec![Asm::pub_label("main"), Asm::push(0u64), Asm::Return]);
+    assert_eq!(vm.run("main").unwrap(), 0);
+    let error = vm.resume().expect_err("the run has finished");
+    assert!(matches!(error, VMError::NothingToResume), "{error}");
+}
@@ -73,7 +73,7 @@ impl<M: MemoryTrait, A: ArithmeticsTrait> JVMRunner<M, A> {
 
         let mut vm = vm_builder.build()?;
         for asm in jasm {
-            vm.load(asm);
+            vm.try_load(asm)?;
         }
 
         let result = vm.run(main_label.as_str())?;
@@ -146,6 +146,8 @@ mod tests {
     use jasm_macros::{jasm, label, pop, return_};
     use jodin_common::{init_logging, LevelFilter};
     use jodin_common::assembly::prelude::Asm;
+    use jodin_common::assembly::value::Value;
+    use jodin_rs_vm::input::READ_VALUE;
 
     #[test]
     fn create_jvm_runner() {
@@ -180,4 +182,24 @@ mod tests {
         assert_eq!(res.out(), HELLO_WORLD, "Expected stdout output to be {HELLO_WORLD:?}");
         assert_eq!(res.err(), HELLO_WORLD, "Expected stderr output to be {HELLO_WORLD:?}");
     }
+
+    #[test]
+    fn read_input() {
+        init_logging(LevelFilter::Info);
+        let mut runner = JVMRunner::default().with_jasm(vec![
+            Asm::pub_label("main"),
+            Asm::native_method("read_line", 0),
+            Asm::SetVar(0),
+            Asm::push(Value::from(vec![Value::from(READ_VALUE)])),
+            Asm::push("get"),
+            Asm::GetVar(0),
+            Asm::SendMessage,
+            Asm::native_method("print", 1),
+            Asm::push(0u64),
+            Asm::Return,
+        ]);
+        runner.set_input("Hello, World!\nGoodbye!\n");
+        let res = runner.execute().expect("Shouldn't fail");
+        assert_eq!(res.out(), "Hello, World!", "Expected the first line of the input to be printed");
+    }
 }
@@ -3,5 +3,7 @@ pub use jodin_common::*;
 
 #[cfg(feature = "jvm")]
 pub mod jvm_runner;
+#[cfg(feature = "jvm")]
+pub mod vm_fixture;
 
 pub mod mathematics;
\ No newline at end of file
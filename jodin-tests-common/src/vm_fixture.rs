@@ -0,0 +1,53 @@
+//! Builds and runs vms for tests, so a test only has to say how its vm is configured and what
+//! it runs
+
+use jodin_common::assembly::instructions::{Asm, Assembly};
+use jodin_rs_vm::core_traits::{MemoryTrait, VirtualMachine};
+use jodin_rs_vm::error::VMError;
+use jodin_rs_vm::mvp::MinimumALU;
+use jodin_rs_vm::vm::{VMBuilder, VM};
+
+/// Builds a vm with a default memory and the minimum alu from a builder configured by a test
+pub fn build_vm<'l, M: MemoryTrait + Default>(
+    builder: VMBuilder<'l, MinimumALU, M>,
+) -> VM<'l, M, MinimumALU> {
+    builder
+        .memory(M::default())
+        .alu(MinimumALU)
+        .build()
+        .expect("vm failed to build")
+}
+
+/// A public `main` function that runs some instructions, then returns 0
+pub fn main_function(body: impl IntoIterator<Item = Asm>) -> Assembly {
+    let mut instructions = vec![Asm::pub_label("main")];
+    instructions.extend(body);
+    instructions.push(Asm::push(0u64));
+    instructions.push(Asm::Return);
+    instructions
+}
+
+/// Loads some code in order into a vm built like [build_vm], then runs its `main` function.
+/// Answers the result of the run and what was printed to stdout.
+pub fn run_main<'l, M: MemoryTrait + Default>(
+    builder: VMBuilder<'l, MinimumALU, M>,
+    code: impl IntoIterator<Item = Assembly>,
+) -> (Result<u32, VMError>, String) {
+    let mut out = Vec::<u8>::new();
+    let result = {
+        let mut vm = build_vm(builder.with_stdout(&mut out));
+        code.into_iter()
+            .try_for_each(|asm| vm.try_load(asm))
+            .and_then(|_| vm.run("main"))
+    };
+    (result, String::from_utf8(out).unwrap())
+}
+
+/// Like [run_main], but only answers what was printed if the run succeeded
+pub fn output_of_main<'l, M: MemoryTrait + Default>(
+    builder: VMBuilder<'l, MinimumALU, M>,
+    code: impl IntoIterator<Item = Assembly>,
+) -> Result<String, VMError> {
+    let (result, out) = run_main(builder, code);
+    result.map(|_| out)
+}
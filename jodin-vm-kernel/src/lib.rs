@@ -1,6 +1,6 @@
 use jodin_common::assembly::value::Value;
 use jodin_vm_plugins::declare_plugin;
-use jodin_vm_plugins::plugins::{LoadablePlugin, Stack, VMHandle};
+use jodin_vm_plugins::plugins::{LoadablePlugin, VMHandle};
 use jodin_vm_plugins::Plugin;
 use std::ffi::CStr;
 
@@ -31,7 +31,6 @@ impl Plugin for KernelPlugin {
     fn call_label(
         &self,
         label: &str,
-        _stack: &mut dyn Stack,
         handle: &mut dyn VMHandle,
         output: &mut Option<Result<Value, String>>,
     ) {
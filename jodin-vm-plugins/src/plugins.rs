@@ -24,6 +24,9 @@ pub trait Stack {
 
 pub trait VMHandle {
     fn native(&mut self, method: &str, values: &[Value], output: &mut Option<Value>);
+
+    /// The stack of the vm, which plugin functions pop their arguments from
+    fn stack(&mut self) -> &mut dyn Stack;
 }
 
 /// A plugin which allows you to add functionality to the jodin VM
@@ -35,7 +38,6 @@ pub trait Plugin: Any + Send + Sync {
     fn call_label(
         &self,
         label: &str,
-        stack: &mut dyn Stack,
         handle: &mut dyn VMHandle,
         output: &mut Option<Result<Value, String>>,
     );
@@ -123,10 +125,9 @@ impl PluginManager {
         self.loaded_labels.contains_key(label.as_ref())
     }
 
-    pub fn call_function<S: Stack, V: VMHandle>(
+    pub fn call_function<V: VMHandle>(
         &self,
         label: &str,
-        stack: &mut S,
         handle: &mut V,
     ) -> Result<Value, PluginError> {
         let uuid = self
@@ -135,9 +136,8 @@ impl PluginManager {
             .ok_or(PluginError::LabelNotRegister(label.to_string()))?;
 
         let plugin = self.plugins.get(uuid).unwrap();
-        let stack: &mut dyn Stack = stack;
         let mut output: Option<Result<_, _>> = None;
-        plugin.call_label(label, stack, handle, &mut output);
+        plugin.call_label(label, handle, &mut output);
         output
             .unwrap()
             .map_err(|s| PluginError::FunctionError(unsafe { s }))
@@ -1,8 +1,8 @@
 //! The expression compiler
 
-use crate::compilation::jodin_vm_compiler::VariableUseTracker;
+use crate::compilation::jodin_vm_compiler::{invalid_tree_type, mark_source, VariableUseTracker};
 use crate::compilation::JodinVM;
-use crate::{JodinNode, JodinResult};
+use crate::{JodinError, JodinNode, JodinResult};
 use jodin_common::assembly::asm_block::{AssemblyBlock, InsertAsm};
 use jodin_common::ast::JodinNodeType;
 use jodin_common::compilation::MicroCompiler;
@@ -12,6 +12,7 @@ use jodin_common::core::NATIVE_OBJECT;
 use jodin_common::error::JodinErrorType;
 
 use jodin_common::assembly::instructions::Asm;
+use jodin_common::assembly::location::AsmLocation;
 
 use jasm_macros::expr;
 use jodin_common::assembly::value::Value;
@@ -29,6 +30,12 @@ impl ExpressionCompiler {
     }
 
     fn expr(&self, tree: &JodinNode) -> JodinResult<AssemblyBlock> {
+        let mut output = self.unmarked_expr(tree)?;
+        mark_source(tree, &mut output);
+        Ok(output)
+    }
+
+    fn unmarked_expr(&self, tree: &JodinNode) -> JodinResult<AssemblyBlock> {
         let mut output = AssemblyBlock::new(None);
 
         match tree.r#type() {
@@ -69,11 +76,37 @@ impl ExpressionCompiler {
                         return Ok(output);
                     }
                 }
-                // todo: Need to decide if theres a way to call a function without always having to
-                // todo: rely on the call method
+                // methods are sent as messages to the value they're a member of
+                if let JodinNodeType::GetMember { compound, id } = called.r#type() {
+                    let message = match id.r#type() {
+                        JodinNodeType::Identifier(id) => id.to_string(),
+                        _ => return Err(JodinError::new(invalid_tree_type("Identifier"))),
+                    };
+                    for arg in arguments.iter() {
+                        output.insert_asm(block![self.expr(arg)?,]);
+                    }
+                    output.insert_asm(block![
+                        Asm::Pack(arguments.len()),
+                        Asm::Push(Value::Str(message)),
+                        self.expr(compound)?,
+                        Asm::SendMessage
+                    ]);
+                    return Ok(output);
+                }
+                // functions that are known statically are called directly with the first argument
+                // on top of the stack, anything else is sent the call message with the arguments
+                // packed in order
+                if let Some(function) = self.static_function(called)? {
+                    for arg in arguments.iter().rev() {
+                        output.insert_asm(block![self.expr(arg)?,]);
+                    }
+                    output.insert_asm(Asm::Call(AsmLocation::Label(function), arguments.len()));
+                    return Ok(output);
+                }
+
                 let mut arg_count = 0;
 
-                for arg in arguments.iter().rev() {
+                for arg in arguments.iter() {
                     output.insert_asm(block![self.expr(arg)?,]);
                     arg_count += 1;
                 }
@@ -101,6 +134,17 @@ impl ExpressionCompiler {
         Ok(output)
     }
 
+    /// Gets the label of a called expression if it's a function that can be called directly
+    fn static_function(&self, called: &JodinNode) -> JodinResult<Option<String>> {
+        if let JodinNodeType::Identifier(_) = called.r#type() {
+            let id = called.resolved_id()?;
+            if self.0.borrow().get_id(id).is_none() {
+                return Ok(Some(id.os_compat().unwrap().to_str().unwrap().to_string()));
+            }
+        }
+        Ok(None)
+    }
+
     fn atom(&self, tree: &JodinNode) -> JodinResult<AssemblyBlock> {
         match tree.r#type() {
             JodinNodeType::Literal(l) => Ok(AssemblyBlock::from(Asm::Push(l.into()))),
@@ -161,7 +205,9 @@ impl ExpressionCompiler {
                         output.insert_asm(Asm::Divide)
                     }
                     Operator::Xor => {
-                        todo!("XOR not implemented")
+                        output.insert_asm(right);
+                        output.insert_asm(left);
+                        output.insert_asm(Asm::Xor)
                     }
                     Operator::Dand | Operator::And => {
                         output.insert_asm(right);
@@ -196,10 +242,14 @@ impl ExpressionCompiler {
                     Operator::Gt => output.insert_asm(expr![>, left, right]),
                     Operator::Gte => output.insert_asm(expr![>=, left, right]),
                     Operator::LShift => {
-                        todo!("<< not implemented")
+                        output.insert_asm(right);
+                        output.insert_asm(left);
+                        output.insert_asm(Asm::ShiftLeft)
                     }
                     Operator::RShift => {
-                        todo!(">> not implemented")
+                        output.insert_asm(right);
+                        output.insert_asm(left);
+                        output.insert_asm(Asm::ShiftRight)
                     }
                     _ => {
                         return Err(JodinErrorType::InvalidTreeTypeGivenToCompiler(
@@ -1,15 +1,17 @@
 //! The micro-compiler for functions
 
 use crate::compilation::jodin_vm_compiler::statement_compiler::StatementCompiler;
-use crate::compilation::jodin_vm_compiler::{invalid_tree_type, VariableUseTracker};
+use crate::compilation::jodin_vm_compiler::{invalid_tree_type, mark_source, VariableUseTracker};
 use crate::compilation::JodinVM;
 use crate::{JodinError, JodinNode, JodinResult};
 use jodin_common::assembly::asm_block::{rel_label, temp_label, AssemblyBlock, InsertAsm};
+use jodin_common::assembly::debug_info::DebugMarker;
 use jodin_common::ast::JodinNodeType;
 use jodin_common::compilation::MicroCompiler;
 use jodin_common::core::tags::TagTools;
 
 use jodin_common::assembly::instructions::Asm;
+use jodin_common::assembly::location::AsmLocation;
 
 use jasm_macros::{push, scope};
 use jodin_common::assembly::value::Value;
@@ -37,12 +39,6 @@ impl FunctionCompiler {
 
 impl MicroCompiler<JodinVM, AssemblyBlock> for FunctionCompiler {
     fn create_compilable(&mut self, tree: &JodinNode) -> JodinResult<AssemblyBlock> {
-        let mut output = AssemblyBlock::with_id(tree.resolved_id().unwrap());
-        output.insert_asm(Asm::PublicLabel(tree.resolved_id().unwrap().to_string()));
-        output.insert_asm(push!(tree.resolved_id().unwrap().to_string()));
-        output.insert_asm(scope!(load));
-        output.insert_asm(scope!(push));
-        output.insert_asm(temp_label("__func_params__"));
         let (return_type, args, block) = {
             if let JodinNodeType::FunctionDefinition {
                 name: _,
@@ -56,6 +52,22 @@ impl MicroCompiler<JodinVM, AssemblyBlock> for FunctionCompiler {
                 return Err(JodinError::new(invalid_tree_type("FunctionDefinition")));
             }
         };
+        let mut output = AssemblyBlock::with_id(tree.resolved_id().unwrap());
+        output.insert_asm(Asm::PublicLabel(tree.resolved_id().unwrap().to_string()));
+        // calling a generator only creates it, the body runs each time the generator is resumed
+        let generator = yields(block);
+        if generator {
+            let body = format!("{}@generator", tree.resolved_id().unwrap());
+            output.insert_asm(push!(Value::Function(AsmLocation::Label(body.clone()))));
+            output.insert_asm(Asm::native_method("@generator", args.len() + 1));
+            output.insert_asm(Asm::Return);
+            output.insert_asm(Asm::label(body));
+        }
+        output.insert_asm(push!(tree.resolved_id().unwrap().to_string()));
+        output.insert_asm(scope!(load));
+        output.insert_asm(scope!(push));
+        output.insert_asm(temp_label("__func_locals__"));
+        output.insert_asm(temp_label("__func_params__"));
         let mut args_block = AssemblyBlock::new(None);
         for arg in args.iter() {
             if let JodinNodeType::NamedValue { name, .. } = arg.r#type() {
@@ -73,16 +85,35 @@ impl MicroCompiler<JodinVM, AssemblyBlock> for FunctionCompiler {
 
         output.insert_after_label(block, rel_label("__func_start__"));
 
+        // the frame is sized once every variable the function uses is known
+        let var_count = self.0.borrow().var_count();
+        let mut locals_block = AssemblyBlock::new(None);
+        locals_block.insert_asm(Asm::push(var_count as u64));
+        locals_block.insert_asm(Asm::native_method("@reserve_vars", 1));
+        for (var, name) in self.0.borrow().var_names() {
+            locals_block.insert_asm(DebugMarker::Local(var as u64, name.to_string()));
+        }
+        output.insert_after_label(locals_block, temp_label("__func_locals__"));
+
         output.insert_asm(Asm::label(rel_label("__func_end__")));
 
-        if return_type.is_void() {
+        if return_type.is_void() || generator {
             output.insert_asm(scope!(back));
             output.insert_asm(Asm::Push(Value::Empty));
             output.insert_asm(Asm::Return);
         }
 
+        mark_source(tree, &mut output);
         debug!("Compiled function: {output:#?}");
 
         Ok(output)
     }
 }
+
+/// Whether a statement yields, which makes the function it's in a generator
+fn yields(tree: &JodinNode) -> bool {
+    match tree.r#type() {
+        JodinNodeType::YieldStatement { .. } => true,
+        _ => tree.into_iter().any(yields),
+    }
+}
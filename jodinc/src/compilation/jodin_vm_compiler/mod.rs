@@ -4,6 +4,7 @@ use crate::{JodinError, JodinNode, JodinResult};
 use anyhow::anyhow;
 use jodin_common::asm_version::Version;
 use jodin_common::assembly::asm_block::{AssemblyBlock, InsertAsm};
+use jodin_common::assembly::debug_info::DebugMarker;
 use jodin_common::assembly::instructions::{Asm, Bytecode, Encode};
 use jodin_common::ast::JodinNodeType;
 use jodin_common::compilation::{
@@ -11,6 +12,7 @@ use jodin_common::compilation::{
 };
 use jodin_common::compilation_settings::CompilationSettings;
 use jodin_common::core::privacy::VisibilityTag;
+use jodin_common::core::span::SpanTag;
 use jodin_common::core::tags::{ResolvedIdentityTag, TagTools};
 use jodin_common::error::JodinErrorType;
 use jodin_common::identifier::Identifier;
@@ -18,7 +20,7 @@ use jodin_common::types::{AsIntermediate, StorageModifier, TypeTag};
 use jodin_common::unit::{CompilationObject, TranslationUnit};
 
 use std::borrow::Borrow;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::ffi::OsString;
 use std::fmt::{Display, Formatter, Write as fmtWrite};
 use std::fs::OpenOptions;
@@ -28,9 +30,9 @@ use std::io::Write;
 use std::marker::PhantomData;
 
 use jodin_common::block;
+use jodin_common::types::resolved_type::ResolvedType;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-use jodin_common::types::resolved_type::ResolvedType;
 
 mod expression_compiler;
 mod function_compiler;
@@ -65,7 +67,6 @@ impl<'c> JodinVMCompiler<'c> {
         }
     }
 
-
     pub fn set_originating_file_path(&mut self, originating_file_path: impl AsRef<Path>) {
         self.originating_file_path = Some(originating_file_path.as_ref().to_path_buf());
     }
@@ -116,7 +117,8 @@ impl<'c> Compiler<JodinVM> for JodinVMCompiler<'c> {
 
         match tree.r#type() {
             JodinNodeType::InNamespace {
-                namespace: found_namespace, inner
+                namespace: found_namespace,
+                inner,
             } => {
                 namespace = Some(found_namespace.resolved_id()?.clone());
                 to_compile = inner;
@@ -124,33 +126,39 @@ impl<'c> Compiler<JodinVM> for JodinVMCompiler<'c> {
             JodinNodeType::TopLevelDeclarations { .. } => {
                 to_compile = tree;
             }
-            _ => return Err(JodinError::new(JodinErrorType::InvalidTreeTypeGivenToCompiler("Must be a valid top level declartion".to_string())))
+            _ => {
+                return Err(JodinError::new(
+                    JodinErrorType::InvalidTreeTypeGivenToCompiler(
+                        "Must be a valid top level declartion".to_string(),
+                    ),
+                ))
+            }
         }
 
-
-
-
         let file_name = match &self.originating_file_path {
-            None => { OsString::from("a.out") }
-            Some(file) => { file.file_name().unwrap().to_os_string() }
+            None => OsString::from("a.out"),
+            Some(file) => file.file_name().unwrap().to_os_string(),
         };
 
         let id_to_path = match &namespace {
-            None => { PathBuf::new() }
-            Some(id) => { PathBuf::from(id.clone()) }
+            None => PathBuf::new(),
+            Some(id) => PathBuf::from(id.clone()),
         };
 
-
         let output_path = PathBuf::from_iter(&[
             settings.target_directory.as_path(),
             id_to_path.as_path(),
-            Path::new(&file_name)
+            Path::new(&file_name),
         ]);
 
         info!("Compiling to file {output_path:?}");
         let mut file_compiler = SingleUseCompiler::new(
-            output_path.clone(), namespace.unwrap_or(Identifier::empty())
+            output_path.clone(),
+            namespace.unwrap_or(Identifier::empty()),
         );
+        if let Some(source_file) = &self.originating_file_path {
+            file_compiler.set_source_file(source_file);
+        }
 
         let compilable = file_compiler.create_compilable(to_compile)?;
         let mut file = OpenOptions::new()
@@ -161,19 +169,28 @@ impl<'c> Compiler<JodinVM> for JodinVMCompiler<'c> {
 
         let mut writer = PaddedWriter::new(file);
 
-       Compilable::<JodinVM>::compile(compilable, &Context::new(), &mut writer)
+        Compilable::<JodinVM>::compile(compilable, &Context::new(), &mut writer)
     }
 }
 
-
 pub struct SingleUseCompiler {
     file: PathBuf,
-    in_module: Identifier
+    in_module: Identifier,
+    source_file: Option<PathBuf>,
 }
 
 impl SingleUseCompiler {
     pub fn new(file: PathBuf, in_module: Identifier) -> Self {
-        SingleUseCompiler { file, in_module }
+        SingleUseCompiler {
+            file,
+            in_module,
+            source_file: None,
+        }
+    }
+
+    /// Sets the source file recorded in the debug info of the compiled object
+    pub fn set_source_file(&mut self, source_file: impl AsRef<Path>) {
+        self.source_file = Some(source_file.as_ref().to_path_buf());
     }
 }
 
@@ -187,7 +204,10 @@ impl MicroCompiler<JodinVM, CompilationObject> for SingleUseCompiler {
         let mut created: Vec<CompilationObject> = vec![];
 
         match tree.inner() {
-            JodinNodeType::InNamespace { namespace: _, inner } => {
+            JodinNodeType::InNamespace {
+                namespace: _,
+                inner,
+            } => {
                 created.push(self.create_compilable(inner)?);
             }
             JodinNodeType::TopLevelDeclarations { decs } => {
@@ -211,7 +231,15 @@ impl MicroCompiler<JodinVM, CompilationObject> for SingleUseCompiler {
             }
         }
 
-        let mut output = CompilationObject::new(self.file.clone(), self.in_module.clone(), translation_units, assembly.normalize());
+        let (jasm, mut debug_info) = assembly.normalize_with_debug_info();
+        debug_info.source_file = self.source_file.clone();
+        let mut output = CompilationObject::new(
+            self.file.clone(),
+            self.in_module.clone(),
+            translation_units,
+            jasm,
+        )
+        .with_debug_info(debug_info);
         for object in created {
             output += object;
         }
@@ -219,7 +247,6 @@ impl MicroCompiler<JodinVM, CompilationObject> for SingleUseCompiler {
     }
 }
 
-
 pub struct ObjectCompilerBuilder {
     dir_path: PathBuf,
     module_id: Identifier,
@@ -487,13 +514,102 @@ impl Display for CompiledObject {
 
 #[cfg(test)]
 mod tests {
+    use crate::compilation::jodin_vm_compiler::function_compiler::FunctionCompiler;
     use crate::compilation::jodin_vm_compiler::JodinVMCompiler;
+    use crate::compilation::JodinVM;
     use crate::process_jodin_node;
-    use jodin_common::compilation::Compiler;
+    use jasm_macros::{block, call, jasm, label, return_, value};
+    use jodin_common::assembly::debug_info::DebugInfo;
+    use jodin_common::assembly::instructions::{Asm, GetAsm};
+    use jodin_common::assembly::location::AsmLocation;
+    use jodin_common::assembly::value::Value;
+    use jodin_common::ast::JodinNodeType;
+    use jodin_common::compilation::{Compilable, Compiler, Context, MicroCompiler, PaddedWriter};
     use jodin_common::compilation_settings::CompilationSettings;
+    use jodin_common::core::function_names::CALL;
+    use jodin_common::core::tags::TagTools;
+    use jodin_common::identifier::Identifier;
     use jodin_common::init_logging;
     use jodin_common::parsing::parse_program;
+    use jodin_common::unit::CompilationObject;
+    use jodin_rs_vm::core_traits::{MemoryTrait, VirtualMachine};
+    use jodin_rs_vm::error::VMError;
+    use jodin_rs_vm::frame_memory::FrameMemory;
+    use jodin_rs_vm::mvp::MinimumALU;
+    use jodin_rs_vm::permissions::{Capability, Permissions};
+    use jodin_rs_vm::scoped_memory::VMMemory;
+    use jodin_rs_vm::vm::VMBuilder;
     use log::LevelFilter;
+    use std::path::PathBuf;
+
+    /// Compiles the first function declared in some source, answering its label and its code
+    fn compile_function(src: &str) -> (String, Vec<Asm>) {
+        let (label, compiled, _) = compile_function_with_debug_info(src);
+        (label, compiled)
+    }
+
+    /// Compiles the first function declared in some source, answering its label, its code and the
+    /// debug info of the code
+    fn compile_function_with_debug_info(src: &str) -> (String, Vec<Asm>, DebugInfo) {
+        init_logging(LevelFilter::Info);
+        let declaration = parse_program(src).expect("Couldn't parse function");
+        let (processed, _) = process_jodin_node(declaration).expect("Should be processable");
+        let function = match processed.inner() {
+            JodinNodeType::TopLevelDeclarations { decs } => &decs[0],
+            _ => &processed,
+        };
+        let label = function.resolved_id().unwrap().to_string();
+        let (compiled, debug_info) = FunctionCompiler::default()
+            .create_compilable(function)
+            .expect("function failed to compile")
+            .normalize_with_debug_info();
+        (label, compiled, debug_info)
+    }
+
+    /// Runs a main function that prints what a loaded function answers when called with some
+    /// arguments, answering the result of the vm and what was printed
+    fn run_main<M: MemoryTrait + Default, G: GetAsm>(
+        code: G,
+        label: &str,
+        args: Vec<Value>,
+    ) -> (Result<u32, VMError>, String) {
+        run_main_with_permissions::<M, G>(code, label, args, Permissions::default())
+    }
+
+    /// Like [run_main], but in a vm that only has some permissions
+    fn run_main_with_permissions<M: MemoryTrait + Default, G: GetAsm>(
+        code: G,
+        label: &str,
+        args: Vec<Value>,
+        permissions: Permissions,
+    ) -> (Result<u32, VMError>, String) {
+        let count = args.len();
+        let mut main = vec![Asm::pub_label("main")];
+        main.extend(args.into_iter().map(Asm::push));
+        main.extend([
+            Asm::Pack(count),
+            Asm::push(CALL),
+            Asm::Push(Value::Function(AsmLocation::Label(label.to_string()))),
+            Asm::SendMessage,
+            Asm::native_method("print", 1),
+            Asm::push(0u64),
+            Asm::Return,
+        ]);
+        let mut out = Vec::<u8>::new();
+        let result = {
+            let mut vm = VMBuilder::new()
+                .memory(M::default())
+                .alu(MinimumALU)
+                .permissions(permissions)
+                .with_stdout(&mut out)
+                .build()
+                .unwrap();
+            vm.load(code);
+            vm.load(main);
+            vm.run("main")
+        };
+        (result, String::from_utf8(out).unwrap())
+    }
 
     #[test]
     fn fibonacci() {
@@ -534,6 +650,195 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn try_catch() {
+        const TRY_FUNCTION: &str = r#"
+        fn clamped_half(n: int, max: int) -> int {
+            try {
+                if (n > max) {
+                    throw max;
+                }
+                return n / 2;
+            } catch (e) {
+                return e;
+            }
+        }
+        "#;
+
+        let (label, compiled) = compile_function(TRY_FUNCTION);
+        let clamped_half = |n: i64, max: i64| {
+            let (result, out) =
+                run_main::<VMMemory, _>(compiled.clone(), &label, vec![n.into(), max.into()]);
+            assert_eq!(result.expect("vm failed"), 0);
+            out
+        };
+        assert_eq!(clamped_half(10, 20), "5");
+        assert_eq!(clamped_half(30, 20), "20");
+    }
+
+    #[test]
+    fn frame_sized_locals() {
+        const SUM_SQUARES_FUNCTION: &str = r#"
+        fn sum_squares(a: int, b: int) -> int {
+            let x: int = a * a;
+            let y: int = b * b;
+            return x + y;
+        }
+        "#;
+
+        let (label, compiled) = compile_function(SUM_SQUARES_FUNCTION);
+        let reserve = compiled
+            .iter()
+            .position(|asm| asm == &Asm::native_method("@reserve_vars", 1))
+            .expect("frame was never sized");
+        assert_eq!(compiled[reserve - 1], Asm::push(4u64));
+
+        let (result, out) =
+            run_main::<FrameMemory, _>(compiled, &label, vec![3i64.into(), 4i64.into()]);
+        assert_eq!(result.expect("vm failed"), 0);
+        assert_eq!(out, "25");
+    }
+
+    #[test]
+    fn compiled_functions_run_without_scopes() {
+        const ABS_FUNCTION: &str = r#"
+        fn abs(a: int) -> int {
+            if (a < 0) {
+                return 0 - a;
+            }
+            return a;
+        }
+        "#;
+
+        let (label, compiled) = compile_function(ABS_FUNCTION);
+        for arg in [-3i64, 3] {
+            let (result, out) = run_main_with_permissions::<FrameMemory, _>(
+                compiled.clone(),
+                &label,
+                vec![arg.into()],
+                Permissions::all().deny(Capability::Scopes),
+            );
+            assert_eq!(result.expect("vm failed"), 0);
+            assert_eq!(out, "3");
+        }
+    }
+
+    #[test]
+    fn debug_info() {
+        const RATIO_FUNCTION: &str =
+            "fn ratio(a: int, b: int) -> int {\n    let q: int = a / b;\n    return q;\n}\n";
+
+        let (label, jasm, mut debug_info) = compile_function_with_debug_info(RATIO_FUNCTION);
+        debug_info.source_file = Some(PathBuf::from("ratio.jdn"));
+        let object = CompilationObject::new(
+            PathBuf::from("ratio.jobj"),
+            Identifier::empty(),
+            vec![],
+            jasm,
+        )
+        .with_debug_info(debug_info);
+
+        // the line table is kept when the object is written and read back
+        let mut buffer = Vec::<u8>::new();
+        Compilable::<JodinVM>::compile(
+            object,
+            &Context::new(),
+            &mut PaddedWriter::new(&mut buffer),
+        )
+        .unwrap();
+        let object = CompilationObject::try_from(&buffer[..]).expect("object couldn't be read");
+        let debug_info = object.debug_info.clone().expect("debug info wasn't kept");
+        assert_eq!(debug_info.local_name(&label, 0), Some("a"));
+        assert_eq!(debug_info.local_name(&label, 1), Some("b"));
+        assert_eq!(debug_info.local_name(&label, 2), Some("q"));
+
+        let (result, _) = run_main::<VMMemory, _>(object, &label, vec![1i64.into(), 0i64.into()]);
+        let error = result.expect_err("division by zero should fail");
+        let frame = &error.stack_trace().expect("no stack trace").frames[0];
+        assert_eq!(
+            frame.source.as_ref().map(ToString::to_string).as_deref(),
+            Some("ratio.jdn:2:18")
+        );
+    }
+
+    #[test]
+    fn builtin_messages() {
+        const SHOUT_FUNCTION: &str = r#"
+        fn shout(words: int) -> int {
+            let shouted: int = "";
+            foreach (word: int in words.split(" ").iterate()) {
+                if (shouted.length() > 0) {
+                    shouted = shouted.concat("-");
+                }
+                shouted = shouted.concat(word.to_upper());
+            }
+            return shouted;
+        }
+        "#;
+
+        let (label, compiled) = compile_function(SHOUT_FUNCTION);
+        let (result, out) =
+            run_main::<FrameMemory, _>(compiled, &label, vec![Value::from("hello big world")]);
+        assert_eq!(result.expect("vm failed"), 0);
+        assert_eq!(out, "HELLO-BIG-WORLD");
+    }
+
+    #[test]
+    fn generators() {
+        const GENERATOR_FUNCTIONS: &str = r#"
+        fn count_to(n: int) -> int {
+            let i: int = 0;
+            while (i < n) {
+                yield i;
+                i = i + 1;
+            }
+        }
+
+        fn sum_to(n: int) -> int {
+            let total: int = 0;
+            foreach (i: int in count_to(n)) {
+                total = total + i;
+            }
+            return total;
+        }
+        "#;
+
+        init_logging(LevelFilter::Info);
+        let declaration =
+            parse_program(GENERATOR_FUNCTIONS).expect("Couldn't parse generator functions");
+        let (processed, _) = process_jodin_node(declaration).expect("Should be processable");
+        let functions = match processed.inner() {
+            JodinNodeType::TopLevelDeclarations { decs } => decs,
+            _ => panic!("expected two functions"),
+        };
+        let label = functions[1].resolved_id().unwrap().to_string();
+
+        let mut out = Vec::<u8>::new();
+        let mut vm = VMBuilder::new()
+            .memory(FrameMemory::default())
+            .alu(MinimumALU)
+            .with_stdout(&mut out)
+            .build()
+            .unwrap();
+        for function in functions {
+            let compiled = FunctionCompiler::default()
+                .create_compilable(function)
+                .expect("generator functions failed to compile")
+                .normalize();
+            vm.load(compiled);
+        }
+        let target = Value::Function(AsmLocation::Label(label));
+        vm.load(jasm![
+            label!(pub main);
+            call!(target, 5i64);
+            Asm::native_method("print", 1);
+            return_!(value!(0u64));
+        ]);
+        assert_eq!(vm.run("main").expect("vm failed"), 0);
+        drop(vm);
+        assert_eq!(String::from_utf8(out).unwrap(), "10");
+    }
 }
 
 #[derive(Default)]
@@ -541,6 +846,8 @@ pub struct VariableUseTracker {
     next_variable: usize,
     unused_vars: Vec<usize>,
     id_to_var_number: HashMap<Identifier, usize>,
+    /// The source names of every variable number, which has more than one once it's reused
+    var_names: BTreeMap<usize, Vec<String>>,
 }
 
 impl VariableUseTracker {
@@ -553,10 +860,27 @@ impl VariableUseTracker {
             self.unused_vars.pop().unwrap()
         };
         self.id_to_var_number.insert(id.clone(), num);
+        let names = self.var_names.entry(num).or_default();
+        if !names.contains(&id.this().to_string()) {
+            names.push(id.this().to_string());
+        }
         debug!("Set id {} to var #{}", id, num);
         num
     }
 
+    /// The number of variable numbers that have been handed out, which every variable number is
+    /// below
+    pub fn var_count(&self) -> usize {
+        self.next_variable
+    }
+
+    /// The source names of every variable number handed out, separated by `/` when it was reused
+    pub fn var_names(&self) -> impl Iterator<Item = (usize, String)> + '_ {
+        self.var_names
+            .iter()
+            .map(|(&var, names)| (var, names.join("/")))
+    }
+
     pub fn next_var_asm(&mut self, id: &Identifier) -> Asm {
         let var = self.next_var(id);
         Asm::SetVar(var as u64)
@@ -590,6 +914,14 @@ impl VariableUseTracker {
     }
 }
 
+/// Marks the code of a block as compiled from a node, if the node has a span
+pub fn mark_source(tree: &JodinNode, block: &mut AssemblyBlock) {
+    if let Ok(tag) = tree.get_tag::<SpanTag>() {
+        block.insert_asm_front(DebugMarker::Enter(tag.span().position));
+        block.insert_asm(DebugMarker::Exit);
+    }
+}
+
 pub fn invalid_tree_type(expected: impl AsRef<str>) -> JodinErrorType {
     JodinErrorType::InvalidTreeTypeGivenToCompiler(expected.as_ref().to_string())
 }
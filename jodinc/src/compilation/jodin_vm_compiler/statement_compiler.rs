@@ -1,7 +1,7 @@
 //! The statement compiler
 
 use crate::compilation::jodin_vm_compiler::expression_compiler::ExpressionCompiler;
-use crate::compilation::jodin_vm_compiler::VariableUseTracker;
+use crate::compilation::jodin_vm_compiler::{mark_source, VariableUseTracker};
 use crate::compilation::JodinVM;
 use crate::{JodinError, JodinNode, JodinResult};
 use jodin_common::assembly::asm_block::{rel_label, AssemblyBlock, InsertAsm};
@@ -11,7 +11,7 @@ use jodin_common::ast::JodinNodeType;
 use jodin_common::compilation::MicroCompiler;
 use jodin_common::error::JodinErrorType;
 
-use jasm_macros::{cond, if_, pop, return_, scope, value, var, while_};
+use jasm_macros::{cond, if_, pop, return_, scope, throw, try_, value, var, while_};
 use jodin_common::block;
 use jodin_common::core::operator::Operator;
 use jodin_common::core::tags::TagTools;
@@ -136,6 +136,30 @@ impl MicroCompiler<JodinVM, AssemblyBlock> for StatementCompiler {
                     (cond) { statement }
                 })
             }
+            JodinNodeType::TryStatement {
+                statement,
+                catch_id,
+                catch_statement,
+            } => {
+                let statement = self.create_compilable(statement)?;
+                let id = catch_id.resolved_id()?;
+                let catch_var = self.tracker.borrow_mut().next_var_asm(id);
+                let catch_statement = self.create_compilable(catch_statement)?;
+                block.insert_asm(try_! {
+                    { statement } catch { block![catch_var, catch_statement,] }
+                })
+            }
+            JodinNodeType::ThrowStatement { expression } => {
+                let mut expr_c = ExpressionCompiler::new(&self.tracker);
+                let expr = expr_c.create_compilable(expression)?;
+                block.insert_asm(throw!(expr))
+            }
+            JodinNodeType::YieldStatement { expression } => {
+                let mut expr_c = ExpressionCompiler::new(&self.tracker);
+                let expr = expr_c.create_compilable(expression)?;
+                block.insert_asm(expr);
+                block.insert_asm(Asm::native_method("@yield_value", 1));
+            }
             JodinNodeType::AssignmentExpression {
                 maybe_assignment_operator,
                 lhs,
@@ -161,6 +185,7 @@ impl MicroCompiler<JodinVM, AssemblyBlock> for StatementCompiler {
                 // ))
             }
         }
+        mark_source(tree, &mut block);
         Ok(block)
     }
 }
@@ -288,6 +288,20 @@ impl IdentifierCreator {
                     self.end_block(id_resolver);
                 }
             }
+            JodinNodeType::TryStatement {
+                statement,
+                catch_id,
+                catch_statement,
+            } => {
+                self.start_block(id_resolver);
+                self.create_identities(statement, id_resolver, visibility_registry)?;
+                self.end_block(id_resolver);
+
+                self.start_block(id_resolver);
+                self.create_identities(catch_id, id_resolver, visibility_registry)?;
+                self.create_identities(catch_statement, id_resolver, visibility_registry)?;
+                self.end_block(id_resolver);
+            }
             JodinNodeType::SwitchStatement {
                 to_switch: _,
                 labeled_statements,
@@ -572,6 +586,20 @@ impl IdentifierSetter {
                     self.end_block(id_resolver);
                 }
             }
+            JodinNodeType::TryStatement {
+                statement,
+                catch_id,
+                catch_statement,
+            } => {
+                self.start_block(id_resolver);
+                self.set_identities(statement, id_resolver, visibility_resolver)?;
+                self.end_block(id_resolver);
+
+                self.start_block(id_resolver);
+                self.set_identities(catch_id, id_resolver, visibility_resolver)?;
+                self.set_identities(catch_statement, id_resolver, visibility_resolver)?;
+                self.end_block(id_resolver);
+            }
             JodinNodeType::SwitchStatement {
                 to_switch,
                 labeled_statements,
@@ -607,6 +635,10 @@ impl IdentifierSetter {
             JodinNodeType::NamedValue { name: _, var_type } => {
                 self.resolve_type(var_type, id_resolver, visibility_resolver)?;
             }
+            // members are looked up on the value when the program runs
+            JodinNodeType::GetMember { compound, id: _ } => {
+                self.set_identities(compound, id_resolver, visibility_resolver)?;
+            }
             other => {
                 for child in other.children_mut() {
                     self.set_identities(child, id_resolver, visibility_resolver)?;
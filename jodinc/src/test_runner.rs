@@ -193,7 +193,7 @@ impl ProjectBuilder {
             .build()?;
 
         let obj = CompilationObject::try_from(path)?;
-        virtual_machine.load(obj);
+        virtual_machine.try_load(obj)?;
         let start = function
             .os_compat_str()
             .ok_or("Function name incompatible")?;